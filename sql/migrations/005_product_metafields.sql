@@ -0,0 +1,16 @@
+-- 005_product_metafields.sql
+-- product_metafields: merchant-defined custom data attached to products (e.g. supplier, season)
+CREATE TABLE product_metafields (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	product_id              UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+	shopify_metafield_id    BIGINT NOT NULL,
+	namespace               TEXT NOT NULL,
+	key                     TEXT NOT NULL,
+	value                   TEXT,
+	type                    TEXT,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_product_metafields_key ON product_metafields(product_id, namespace, key);
+CREATE INDEX idx_product_metafields_merchant ON product_metafields(merchant_id, namespace, key);
@@ -0,0 +1,20 @@
+-- 006_merchant_deletion.sql
+-- Merchant removal (DELETE /merchants/{id}).
+--
+-- Default: soft delete. merchants.deleted_at is set and the same timestamp is
+-- cascaded to products, orders and inventory_items; users are deactivated.
+-- ?purge=true: hard delete inside a transaction, children first, then the merchant.
+--
+-- Foreign keys that cascade on a hard delete of merchants(id):
+--   shopify_installs.merchant_id    ON DELETE CASCADE (001)
+--   app_settings.merchant_id        ON DELETE CASCADE (001)
+--   products.merchant_id            ON DELETE CASCADE (002)
+--   variants.merchant_id            ON DELETE CASCADE (002)
+--   inventory_items.merchant_id     ON DELETE CASCADE (002)
+--   orders.merchant_id              ON DELETE CASCADE (003)
+--   users.merchant_id               ON DELETE CASCADE (004)
+--   product_metafields.merchant_id  ON DELETE CASCADE (005)
+--   product_metafields.product_id   ON DELETE CASCADE from products(id) (005)
+
+ALTER TABLE orders ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE inventory_items ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- 007_shopify_access_token.sql
+-- Admin API access token captured at install time, used for live calls to the
+-- merchant's store (e.g. GET /merchants/{id}/shop). NULL for installs that predate it.
+
+ALTER TABLE shopify_installs ADD COLUMN access_token TEXT;
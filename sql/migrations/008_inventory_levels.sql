@@ -0,0 +1,14 @@
+-- 008_inventory_levels.sql
+-- Available stock per inventory item and Shopify location.
+-- Written by POST /inventory/{id}/adjust after Shopify accepts the adjustment.
+
+CREATE TABLE inventory_levels (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	inventory_item_id       UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+	shopify_location_id     BIGINT NOT NULL,
+	available               INTEGER NOT NULL DEFAULT 0,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_inventory_levels_location ON inventory_levels(inventory_item_id, shopify_location_id);
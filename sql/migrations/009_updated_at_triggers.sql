@@ -0,0 +1,21 @@
+-- 009_updated_at_triggers.sql
+-- Keep updated_at current on every UPDATE (including ON CONFLICT DO UPDATE upserts)
+-- so handlers and the sync don't each have to remember to set it.
+
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+	NEW.updated_at = NOW();
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_products_updated_at BEFORE UPDATE ON products
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+CREATE TRIGGER trg_variants_updated_at BEFORE UPDATE ON variants
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+CREATE TRIGGER trg_orders_updated_at BEFORE UPDATE ON orders
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+CREATE TRIGGER trg_inventory_items_updated_at BEFORE UPDATE ON inventory_items
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+CREATE TRIGGER trg_inventory_levels_updated_at BEFORE UPDATE ON inventory_levels
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,13 @@
+-- 010_refresh_tokens.sql
+-- Server-side refresh tokens for REFRESH_STRATEGY=opaque.
+-- Only the SHA-256 hash is stored; revoking a token is setting revoked_at.
+
+CREATE TABLE refresh_tokens (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id             UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	token_hash          TEXT NOT NULL UNIQUE,
+	expires_at          TIMESTAMPTZ NOT NULL,
+	revoked_at          TIMESTAMPTZ,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_refresh_tokens_user ON refresh_tokens(user_id);
@@ -0,0 +1,17 @@
+-- 011_order_refunds.sql
+-- Refunds per order, synced from Shopify's /orders/{id}/refunds.json.
+-- An order can have several (partial) refunds; net revenue = total_price - SUM(amount).
+
+CREATE TABLE order_refunds (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id            BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_refund_id   BIGINT NOT NULL,
+	amount              NUMERIC(14,4) NOT NULL DEFAULT 0,
+	reason              TEXT,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_order_refunds_shopify ON order_refunds(order_id, shopify_refund_id);
+
+-- The order sync upserts on the Shopify id
+CREATE UNIQUE INDEX ux_orders_shopify ON orders(merchant_id, shopify_order_id);
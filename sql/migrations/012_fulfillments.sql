@@ -0,0 +1,19 @@
+-- 012_fulfillments.sql
+-- Shipments per order, from the order sync and the fulfillments/create webhook.
+-- created_at is Shopify's fulfillment time, so time-to-ship = created_at - orders.processed_at.
+
+CREATE TABLE fulfillments (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_fulfillment_id  BIGINT NOT NULL,
+	status                  TEXT,
+	tracking_number         TEXT,
+	tracking_company        TEXT,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_fulfillments_shopify ON fulfillments(order_id, shopify_fulfillment_id);
+
+CREATE TRIGGER trg_fulfillments_updated_at BEFORE UPDATE ON fulfillments
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
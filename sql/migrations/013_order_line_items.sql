@@ -0,0 +1,19 @@
+-- 013_order_line_items.sql
+-- Line items per order, synced with the order. Product/variant ids are Shopify ids so
+-- sales join to variants/products when those rows exist; title and sku are kept as sold
+-- so sales of since-deleted products still report.
+
+CREATE TABLE order_line_items (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_line_item_id    BIGINT NOT NULL,
+	shopify_product_id      BIGINT,
+	shopify_variant_id      BIGINT,
+	title                   TEXT,
+	sku                     TEXT,
+	quantity                INTEGER NOT NULL DEFAULT 0,
+	price                   NUMERIC(14,4) NOT NULL DEFAULT 0
+);
+CREATE UNIQUE INDEX ux_order_line_items_shopify ON order_line_items(order_id, shopify_line_item_id);
+CREATE INDEX idx_order_line_items_variant ON order_line_items(merchant_id, shopify_variant_id);
@@ -0,0 +1,13 @@
+-- 014_clients.sql
+-- Service clients for the OAuth client-credentials grant (POST /auth/token).
+-- Only the SHA-256 hash of the secret is stored; scopes are space-delimited, e.g. 'viewer manager'.
+
+CREATE TABLE clients (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	client_id           TEXT NOT NULL UNIQUE,
+	client_secret_hash  TEXT NOT NULL,
+	scopes              TEXT NOT NULL DEFAULT 'viewer',
+	description         TEXT,
+	is_active           BOOLEAN NOT NULL DEFAULT TRUE,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,7 @@
+-- 015_order_status.sql
+-- Order status derived from cancelled_at, so setting or syncing a cancellation flips it.
+-- 'open' covers every non-cancelled order; fulfillment state lives in fulfillments.
+
+ALTER TABLE orders
+	ADD COLUMN status TEXT NOT NULL
+	GENERATED ALWAYS AS (CASE WHEN cancelled_at IS NULL THEN 'open' ELSE 'cancelled' END) STORED;
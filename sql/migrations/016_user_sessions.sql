@@ -0,0 +1,7 @@
+-- 016_user_sessions.sql
+-- "Log out everywhere": refresh tokens issued before tokens_valid_after are rejected.
+-- JWT refresh tokens are stateless, so this cutoff is the only way to revoke them per user.
+
+ALTER TABLE users ADD COLUMN tokens_valid_after TIMESTAMPTZ;
+
+COMMENT ON COLUMN users.tokens_valid_after IS 'Refresh tokens issued before this time are rejected. NULL means no cutoff.';
@@ -0,0 +1,4 @@
+-- 017_argon2_password_hash.sql
+-- New passwords are Argon2id PHC strings; legacy SHA-256 hex digests are upgraded on login.
+
+COMMENT ON COLUMN users.password_hash IS 'Argon2id PHC string ($argon2id$...), or a legacy SHA-256 hex digest until the user next logs in. NULL for OAuth-only users.';
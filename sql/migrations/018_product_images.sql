@@ -0,0 +1,15 @@
+-- 018_product_images.sql
+-- product_images: Shopify product images, mirrored on sync for dashboard thumbnails.
+-- The image with the lowest position (Shopify numbers them from 1) is the primary one.
+CREATE TABLE product_images (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	product_id          UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+	shopify_image_id    BIGINT NOT NULL,
+	src                 TEXT NOT NULL,
+	position            INTEGER NOT NULL,
+	alt                 TEXT,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_product_images_shopify ON product_images(product_id, shopify_image_id);
+CREATE INDEX idx_product_images_position ON product_images(product_id, position);
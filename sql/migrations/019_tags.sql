@@ -0,0 +1,28 @@
+-- 019_tags.sql
+-- tags: a merchant's Shopify tags, shared by products and orders.
+-- product_tags / order_tags link them many-to-many; both are replaced wholesale on sync
+-- from Shopify's comma-separated `tags` field. Shopify tags are case-insensitive, so a
+-- tag is unique per merchant by lower(name) and keeps the spelling it was first seen with.
+CREATE TABLE tags (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	name                TEXT NOT NULL,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_tags_name ON tags(merchant_id, lower(name));
+
+CREATE TABLE product_tags (
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	product_id          UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+	tag_id              UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+	PRIMARY KEY (product_id, tag_id)
+);
+CREATE INDEX idx_product_tags_tag ON product_tags(tag_id);
+
+CREATE TABLE order_tags (
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id            BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	tag_id              UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+	PRIMARY KEY (order_id, tag_id)
+);
+CREATE INDEX idx_order_tags_tag ON order_tags(tag_id);
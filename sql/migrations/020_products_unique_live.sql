@@ -0,0 +1,7 @@
+-- 020_products_unique_live.sql
+-- Only live products need a unique Shopify id. A soft-deleted product no longer blocks
+-- re-adding the same shopify_product_id: the re-added product gets a new row and the
+-- deleted one is kept, still deleted, as history.
+DROP INDEX ux_products_shopify;
+CREATE UNIQUE INDEX ux_products_shopify ON products(merchant_id, shopify_product_id)
+	WHERE deleted_at IS NULL;
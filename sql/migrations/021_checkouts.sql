@@ -0,0 +1,26 @@
+-- 021_checkouts.sql
+-- checkouts: Shopify abandoned checkouts, mirrored for recovery analytics.
+-- A checkout that converted is linked to its order through orders.shopify_checkout_id
+-- (Shopify only records the link on the order), so either side can sync first.
+ALTER TABLE orders ADD COLUMN shopify_checkout_id BIGINT;
+CREATE INDEX idx_orders_checkout ON orders(merchant_id, shopify_checkout_id)
+	WHERE shopify_checkout_id IS NOT NULL;
+
+CREATE TABLE checkouts (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	shopify_checkout_id     BIGINT NOT NULL,
+	email                   TEXT,
+	abandoned_checkout_url  TEXT,
+	currency                TEXT,
+	total_price             NUMERIC(14,4),
+	shopify_created_at      TIMESTAMPTZ NOT NULL,
+	completed_at            TIMESTAMPTZ,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_checkouts_shopify ON checkouts(merchant_id, shopify_checkout_id);
+CREATE INDEX idx_checkouts_created ON checkouts(merchant_id, shopify_created_at DESC);
+
+CREATE TRIGGER trg_checkouts_updated_at BEFORE UPDATE ON checkouts
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
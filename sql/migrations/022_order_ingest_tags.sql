@@ -0,0 +1,6 @@
+-- 022_order_ingest_tags.sql
+-- Per-merchant allowlist for orders arriving by webhook: only orders carrying at least
+-- one of these tags (case-insensitive) are stored. NULL or empty ingests every order.
+-- Settings are now written by the API, so each merchant gets at most one row.
+ALTER TABLE app_settings ADD COLUMN order_ingest_tags TEXT[];
+CREATE UNIQUE INDEX ux_app_settings_merchant ON app_settings(merchant_id);
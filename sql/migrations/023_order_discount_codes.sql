@@ -0,0 +1,15 @@
+-- 023_order_discount_codes.sql
+-- order_discount_codes: the discount codes applied to an order, from Shopify's
+-- `discount_codes` array. An order may carry several; they are replaced wholesale on
+-- every order sync. Shopify matches codes case-insensitively, and so do reports.
+CREATE TABLE order_discount_codes (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id            BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	code                TEXT NOT NULL,
+	amount              NUMERIC(14,4) NOT NULL DEFAULT 0, -- Discount the code gave on this order
+	discount_type       TEXT NOT NULL,                    -- fixed_amount|percentage|shipping
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_order_discount_codes_order ON order_discount_codes(order_id);
+CREATE INDEX idx_order_discount_codes_code ON order_discount_codes(merchant_id, lower(code));
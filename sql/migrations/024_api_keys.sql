@@ -0,0 +1,19 @@
+-- 024_api_keys.sql
+-- Static, merchant-scoped API keys for partners that can't refresh JWTs (X-Api-Key header).
+-- Only the SHA-256 hash is stored; key_prefix is kept in clear so a key can be recognised
+-- in listings. Revoking a key is setting revoked_at. last_used_at is updated at most once a
+-- minute per key, so dormant keys can be found without a write per request.
+
+CREATE TABLE api_keys (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	key_hash            TEXT NOT NULL UNIQUE,
+	key_prefix          TEXT NOT NULL,
+	scopes              TEXT NOT NULL DEFAULT 'viewer', -- Space-delimited, as in clients.scopes
+	description         TEXT,
+	created_by          UUID REFERENCES users(id) ON DELETE SET NULL,
+	last_used_at        TIMESTAMPTZ,
+	revoked_at          TIMESTAMPTZ,
+	created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_api_keys_merchant ON api_keys(merchant_id);
@@ -0,0 +1,10 @@
+-- 025_merchant_sync_status.sql
+-- Sync health per merchant (GET /merchants/{id}/sync-status), written by sync_merchant.
+-- sync_started_at is set while a sync runs and cleared when it ends, so one that stays
+-- set long after starting is stuck. last_synced_at is when the last successful sync
+-- started: everything in Shopify before it is mirrored. last_sync_error holds the
+-- error of the last sync if it failed, and is cleared by the next successful one.
+ALTER TABLE merchants ADD COLUMN sync_started_at TIMESTAMPTZ;
+ALTER TABLE merchants ADD COLUMN last_synced_at TIMESTAMPTZ;
+ALTER TABLE merchants ADD COLUMN last_sync_error TEXT;
+ALTER TABLE merchants ADD COLUMN last_sync_error_at TIMESTAMPTZ;
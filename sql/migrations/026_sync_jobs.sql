@@ -0,0 +1,19 @@
+-- 026_sync_jobs.sql
+-- Manual syncs started with POST /merchants/{id}/sync and polled by id with
+-- GET /merchants/{id}/sync/{job_id}. A job is only inserted once the merchant's sync
+-- lock is taken, so it starts out running; the sync marks it succeeded or failed.
+-- A job whose process died mid-sync stays running, like merchants.sync_started_at.
+
+CREATE TABLE sync_jobs (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	status              TEXT NOT NULL DEFAULT 'running', -- running|succeeded|failed
+	products            BIGINT, -- Records mirrored, once succeeded
+	orders              BIGINT,
+	checkouts           BIGINT,
+	error               TEXT,
+	created_by          UUID REFERENCES users(id) ON DELETE SET NULL,
+	started_at          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	finished_at         TIMESTAMPTZ
+);
+CREATE INDEX idx_sync_jobs_merchant ON sync_jobs(merchant_id);
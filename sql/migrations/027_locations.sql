@@ -0,0 +1,27 @@
+-- 027_locations.sql
+-- Shopify locations, mirrored by sync so inventory levels can name their location.
+-- Keyed by Shopify's id like inventory_levels.shopify_location_id. Levels don't
+-- reference this table, so a level may exist before its location has been synced.
+-- Locations Shopify no longer lists are deleted on the next sync.
+
+CREATE TABLE locations (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	shopify_location_id     BIGINT NOT NULL,
+	name                    TEXT,
+	active                  BOOLEAN NOT NULL DEFAULT TRUE,
+	address1                TEXT,
+	address2                TEXT,
+	city                    TEXT,
+	province                TEXT,
+	zip                     TEXT,
+	country_code            TEXT,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_locations_shopify ON locations(merchant_id, shopify_location_id);
+CREATE TRIGGER trg_locations_updated_at BEFORE UPDATE ON locations
+	FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Locations mirrored by a sync job, alongside products/orders/checkouts
+ALTER TABLE sync_jobs ADD COLUMN locations BIGINT;
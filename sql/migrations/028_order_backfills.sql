@@ -0,0 +1,23 @@
+-- 028_order_backfills.sql
+-- Order backfills started with POST /merchants/{id}/backfill. A backfill pulls the
+-- orders created in [created_from, created_to) one window of window_days at a time and
+-- moves synced_through forward after each window, so a backfill that failed or whose
+-- process died resumes from the last finished window instead of from the start.
+
+CREATE TABLE order_backfills (
+	id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id         UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	created_from        TIMESTAMPTZ NOT NULL,
+	created_to          TIMESTAMPTZ NOT NULL,
+	window_days         INTEGER NOT NULL,
+	synced_through      TIMESTAMPTZ, -- Orders created before this are mirrored
+	orders              BIGINT NOT NULL DEFAULT 0, -- Orders mirrored so far, across resumes
+	status              TEXT NOT NULL DEFAULT 'running', -- running|succeeded|failed
+	error               TEXT,
+	created_by          UUID REFERENCES users(id) ON DELETE SET NULL,
+	started_at          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	finished_at         TIMESTAMPTZ,
+	CHECK (created_from < created_to),
+	CHECK (window_days > 0)
+);
+CREATE INDEX idx_order_backfills_merchant ON order_backfills(merchant_id);
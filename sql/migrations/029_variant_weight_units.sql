@@ -0,0 +1,26 @@
+-- 029_variant_weight_units.sql
+-- variants.weight_unit was stored as Shopify sent it ("kg", "KG", "kilograms"). The sync
+-- now normalizes it to Shopify's short codes; this brings existing rows in line and
+-- keeps anything else out. Units that can't be recognized become NULL, keeping the weight.
+
+UPDATE variants
+SET weight_unit = CASE lower(btrim(weight_unit))
+	WHEN 'g' THEN 'g'
+	WHEN 'gram' THEN 'g'
+	WHEN 'grams' THEN 'g'
+	WHEN 'kg' THEN 'kg'
+	WHEN 'kgs' THEN 'kg'
+	WHEN 'kilogram' THEN 'kg'
+	WHEN 'kilograms' THEN 'kg'
+	WHEN 'oz' THEN 'oz'
+	WHEN 'ounce' THEN 'oz'
+	WHEN 'ounces' THEN 'oz'
+	WHEN 'lb' THEN 'lb'
+	WHEN 'lbs' THEN 'lb'
+	WHEN 'pound' THEN 'lb'
+	WHEN 'pounds' THEN 'lb'
+END
+WHERE weight_unit IS NOT NULL;
+
+ALTER TABLE variants
+	ADD CONSTRAINT variants_weight_unit_check CHECK (weight_unit IN ('g', 'kg', 'oz', 'lb'));
@@ -0,0 +1,7 @@
+-- 030_order_customer_email.sql
+-- The customer's email on each order, for support lookups with GET /orders/search.
+-- Emails are matched case-insensitively, hence the index on lower(customer_email).
+-- Orders synced before this have NULL until the next order sync or webhook.
+
+ALTER TABLE orders ADD COLUMN customer_email TEXT;
+CREATE INDEX idx_orders_customer_email ON orders(merchant_id, lower(customer_email));
@@ -1,19 +1,196 @@
 use anyhow;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, errors::ErrorKind, EncodingKey, Header};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, errors::ErrorKind, Algorithm, EncodingKey, Header};
 use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey};
 use rsa::traits::PublicKeyParts;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+use crate::args::Environment;
+use crate::auth::keys::{KeySet, KeySummary, SigningKey};
+use crate::auth::refresh::{self, RefreshError, RefreshStrategy, RefreshTokenOwner};
+
+/// Lifetime of every access token, counted from its not-before time
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+/// Lifetime of every refresh token, JWT or opaque
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Algorithm new tokens are signed with; every key in the set is RSA
+const SIGNING_ALGORITHM: Algorithm = Algorithm::RS256;
+
+/// `iss` of the tokens we mint when no `INSTANCE_NAME` is configured
+pub const DEFAULT_ISSUER: &str = "exchange_api";
+
+/// Where the signing keys in use came from, reported in the startup config summary
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    KeyStore,    // The persisted key set at KEY_STORE_PATH
+    Environment, // JWT_PRIVATE_KEY (and JWT_PUBLIC_KEY)
+    Generated,   // A fresh key pair for this process only
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Scope {
     Viewer,   // Can only look, no changes
     Manager,  // Can edit products/orders
     Admin,    // Full control, can add/remove users
+    Backoffice, // Can correct stock levels (writes back to Shopify)
+    Operator, // Platform-wide actions across all merchants; only for service clients
+    /// A deployment-defined scope such as `reports:read`, matched exactly.
+    /// Never implied by the built-in scopes, not even `Admin`.
+    Custom(String),
+}
+
+/// Built-in scopes with their `scope` string and `scope` array names
+const BUILT_IN_SCOPES: [(Scope, &str, &str); 5] = [
+    (Scope::Viewer, "viewer", "Viewer"),
+    (Scope::Manager, "manager", "Manager"),
+    (Scope::Admin, "admin", "Admin"),
+    (Scope::Backoffice, "backoffice", "Backoffice"),
+    (Scope::Operator, "operator", "Operator"),
+];
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Unknown scope: {0}")]
+pub struct ParseScopeError(pub String);
+
+impl Scope {
+    /// Name in the JWT `scope` array: the variant name for built-in scopes, e.g. "Viewer"
+    fn claim_name(&self) -> &str {
+        match self {
+            Scope::Custom(name) => name,
+            built_in => BUILT_IN_SCOPES
+                .iter()
+                .find(|(scope, _, _)| scope == built_in)
+                .map(|(_, _, claim)| *claim)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// A custom scope name must be an OAuth scope token (printable ASCII without
+    /// spaces, quotes or backslashes) that can't be mistaken for a built-in scope
+    fn is_valid_custom(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|b| matches!(b, 0x21 | 0x23..=0x5B | 0x5D..=0x7E))
+            && !BUILT_IN_SCOPES
+                .iter()
+                .any(|(_, string, _)| string.eq_ignore_ascii_case(name))
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Scope::Custom(name) => name.as_str(),
+            built_in => BUILT_IN_SCOPES
+                .iter()
+                .find(|(scope, _, _)| scope == built_in)
+                .map(|(_, string, _)| *string)
+                .unwrap_or_default(),
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = ParseScopeError;
+
+    /// Built-in scopes by their lowercase name; anything else that is a valid
+    /// custom scope name becomes `Scope::Custom`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let built_in = BUILT_IN_SCOPES.iter().find(|(_, string, _)| *string == s);
+        if let Some((scope, _, _)) = built_in {
+            return Ok(scope.clone());
+        }
+        if Scope::is_valid_custom(s) {
+            return Ok(Scope::Custom(s.to_string()));
+        }
+        Err(ParseScopeError(s.to_string()))
+    }
+}
+
+// Built-in scopes keep the variant names the derived impls used ("Viewer", ...);
+// custom scopes are their bare name, so existing tokens still verify.
+impl Serialize for Scope {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Scope::Custom(name) = self {
+            if !Scope::is_valid_custom(name) {
+                return Err(serde::ser::Error::custom(ParseScopeError(name.clone())));
+            }
+        }
+        serializer.serialize_str(self.claim_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let built_in = BUILT_IN_SCOPES.iter().find(|(_, _, claim)| *claim == name);
+        if let Some((scope, _, _)) = built_in {
+            return Ok(scope.clone());
+        }
+        if Scope::is_valid_custom(&name) {
+            return Ok(Scope::Custom(name));
+        }
+        Err(serde::de::Error::custom(ParseScopeError(name)))
+    }
+}
+
+/// Space-delimited OAuth `scope` string, e.g. "viewer manager"
+pub fn scopes_to_string(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(Scope::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a space-delimited OAuth `scope` string; names that are neither built-in nor
+/// valid custom scopes are an error
+pub fn parse_scopes(scopes: &str) -> Result<Vec<Scope>, ParseScopeError> {
+    scopes.split_whitespace().map(str::parse).collect()
+}
+
+/// Who a token is issued to; stored in the `sub` claim as a plain string
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Subject {
+    User(Uuid),      // A person who logged in
+    Service(String), // A client-credentials client, by client id
+}
+
+impl Subject {
+    /// Read a `sub` claim back; anything that isn't a UUID is a service identity
+    pub fn from_sub(sub: &str) -> Self {
+        match Uuid::parse_str(sub) {
+            Ok(user_id) => Subject::User(user_id),
+            Err(_) => Subject::Service(sub.to_string()),
+        }
+    }
+}
+
+impl From<Uuid> for Subject {
+    fn from(user_id: Uuid) -> Self {
+        Subject::User(user_id)
+    }
+}
+
+impl std::fmt::Display for Subject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Subject::User(user_id) => write!(f, "{}", user_id),
+            Subject::Service(client_id) => f.write_str(client_id),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -22,12 +199,76 @@ pub enum TokenType {
     Refresh,
 }
 
+impl TokenType {
+    /// JOSE `typ` header value; `at+jwt` is the RFC 9068 access token type
+    fn header_typ(&self) -> &'static str {
+        match self {
+            TokenType::Access => "at+jwt",
+            TokenType::Refresh => "refresh+jwt",
+        }
+    }
+
+    fn from_header_typ(typ: &str) -> Option<Self> {
+        match typ {
+            "at+jwt" => Some(TokenType::Access),
+            "refresh+jwt" => Some(TokenType::Refresh),
+            _ => None,
+        }
+    }
+}
+
+/// Where issued tokens say whether they are access or refresh tokens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenTypeClaim {
+    /// A payload claim with this name, `token_type` unless configured otherwise
+    Claim(String),
+    /// The JOSE `typ` header (`at+jwt` / `refresh+jwt`) instead of a payload claim
+    Header,
+}
+
+/// Claim layout for API gateways that expect OAuth-style tokens
+///
+/// Verification accepts both the configured layout and the default one, so
+/// tokens issued before a configuration change keep working until they expire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClaimsFormat {
+    pub token_type: TokenTypeClaim,
+    /// Emit `scope` as a space-delimited string, moving the array to `scopes`
+    pub scope_string: bool,
+}
+
+impl Default for ClaimsFormat {
+    fn default() -> Self {
+        Self {
+            token_type: TokenTypeClaim::Claim("token_type".to_string()),
+            scope_string: false,
+        }
+    }
+}
+
+impl ClaimsFormat {
+    /// `token_type_claim` names the payload claim, or is `header` for the JOSE `typ` header
+    pub fn new(token_type_claim: &str, scope_string: bool) -> Self {
+        let token_type = match token_type_claim {
+            "header" => TokenTypeClaim::Header,
+            name => TokenTypeClaim::Claim(name.to_string()),
+        };
+        Self {
+            token_type,
+            scope_string,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AccessTokenClaims {
     pub sub: String,
     pub email: String,
     pub exp: usize,
     pub iat: usize,
+    /// Optional not-before time; the token is rejected until this moment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
     pub iss: String,
     pub token_type: TokenType,
     pub scope: Vec<Scope>,
@@ -60,22 +301,161 @@ pub struct Jwks {
     pub keys: Vec<Jwk>,
 }
 
-#[derive(Clone)]
+/// The allowlist for verifying tokens signed with `configured`
+///
+/// HMAC algorithms are always dropped: our verification keys are public, so accepting
+/// HS* would let anyone forge a token using a public key as the shared secret.
+/// `none` has no `Algorithm` variant, so such tokens already fail to parse.
+fn verification_algorithms(configured: &[Algorithm]) -> Vec<Algorithm> {
+    configured
+        .iter()
+        .copied()
+        .filter(|alg| !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512))
+        .collect()
+}
+
 pub struct AuthService {
-    private_key: String,
-    public_key: String,
+    keys: RwLock<KeySet>,
+    /// Where the key set is persisted after a rotation; `None` keeps rotations in memory only
+    key_store_path: Option<PathBuf>,
+    leeway_secs: u64,
+    refresh_strategy: RefreshStrategy,
+    /// Tokens with an `iat` before this Unix time are rejected; 0 means no cutoff
+    min_iat: AtomicI64,
+    claims_format: ClaimsFormat,
+    /// Algorithms accepted on verification, derived from the signing algorithm
+    algorithms: Vec<Algorithm>,
+    /// `iss` put in every token, and the only one accepted on verification
+    issuer: String,
+    key_source: KeySource,
 }
 
 impl AuthService {
-    pub fn new(private_key: String, _jwt_expiration_hours: u64, public_key: String) -> Self {
-        AuthService {
+    pub fn new(
+        private_key: String,
+        _jwt_expiration_hours: u64,
+        public_key: String,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_key_set(KeySet::new(SigningKey {
+            kid: crate::misc::keypair::key_id(&public_key)?,
             private_key,
             public_key,
+            created_at: Utc::now(),
+        })))
+    }
+
+    fn from_key_set(keys: KeySet) -> Self {
+        AuthService {
+            keys: RwLock::new(keys),
+            key_store_path: None,
+            leeway_secs: 60,
+            refresh_strategy: RefreshStrategy::default(),
+            min_iat: AtomicI64::new(0),
+            claims_format: ClaimsFormat::default(),
+            algorithms: verification_algorithms(&[SIGNING_ALGORITHM]),
+            issuer: DEFAULT_ISSUER.to_string(),
+            key_source: KeySource::Generated,
         }
     }
 
+    /// Issue and accept tokens for `issuer` only, so instances sharing a key stay apart
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = issuer.into();
+        self
+    }
+
+    /// The `iss` this instance issues and accepts
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn with_key_source(mut self, key_source: KeySource) -> Self {
+        self.key_source = key_source;
+        self
+    }
+
+    pub fn key_source(&self) -> KeySource {
+        self.key_source
+    }
+
+    /// Persist the key set to `path` whenever keys are rotated
+    pub fn with_key_store(mut self, path: impl Into<PathBuf>) -> Self {
+        self.key_store_path = Some(path.into());
+        self
+    }
+
+    /// Override the clock-skew leeway (seconds) applied to `exp` and `nbf` checks
+    #[cfg(test)]
+    pub fn with_leeway(mut self, leeway_secs: u64) -> Self {
+        self.leeway_secs = leeway_secs;
+        self
+    }
+
+    pub fn with_refresh_strategy(mut self, refresh_strategy: RefreshStrategy) -> Self {
+        self.refresh_strategy = refresh_strategy;
+        self
+    }
+
+    pub fn with_claims_format(mut self, claims_format: ClaimsFormat) -> Self {
+        self.claims_format = claims_format;
+        self
+    }
+
+    /// Start with tokens issued before `min_iat` (Unix seconds) already invalidated
+    pub fn with_min_iat(self, min_iat: Option<i64>) -> Self {
+        self.min_iat.store(min_iat.unwrap_or(0), Ordering::Relaxed);
+        self
+    }
+
+    /// Reject every access and JWT refresh token issued before `at`
+    ///
+    /// Held in memory only: set `TOKEN_MIN_IAT` to keep the cutoff across restarts
+    /// and on every instance.
+    pub fn invalidate_before(&self, at: DateTime<Utc>) {
+        self.min_iat.fetch_max(at.timestamp(), Ordering::Relaxed);
+    }
+
+    /// The current "issued before" cutoff, if one has been set
+    pub fn min_iat(&self) -> Option<DateTime<Utc>> {
+        match self.min_iat.load(Ordering::Relaxed) {
+            0 => None,
+            secs => DateTime::from_timestamp(secs, 0),
+        }
+    }
+
+    fn check_iat(&self, iat: usize) -> Result<(), ErrorKind> {
+        if (iat as i64) < self.min_iat.load(Ordering::Relaxed) {
+            return Err(ErrorKind::InvalidToken);
+        }
+        Ok(())
+    }
+
     pub fn from_config(config: &crate::Args) -> anyhow::Result<Self> {
-        match &config.private_key {
+        // A persisted key set reflects rotations done at runtime, so it wins over
+        // keys supplied via the environment
+        if let Some(keys) = KeySet::load(Path::new(&config.key_store_path))? {
+            println!("🔑 Using JWT key set from {}", config.key_store_path);
+            if config.private_key.is_some() {
+                // Each replica rotates its own copy of the file, so they can drift apart
+                eprintln!(
+                    "⚠️  JWT_PRIVATE_KEY is set but ignored: the key set at {} overrides it. \
+                     Remove that file to use the environment keys, or unset JWT_PRIVATE_KEY",
+                    config.key_store_path
+                );
+            }
+            let service = Self::from_key_set(keys)
+                .with_key_source(KeySource::KeyStore)
+                .with_issuer(&config.instance_name)
+                .with_key_store(&config.key_store_path)
+                .with_refresh_strategy(config.refresh_strategy)
+                .with_min_iat(config.token_min_iat)
+                .with_claims_format(config.claims_format());
+            service.self_test()?;
+            Self::create_public_keys_json(&service.generate_jwks()?)?;
+            return Ok(service);
+        }
+
+        let service = match &config.private_key {
             Some(private_key) => {
                 println!("🔑 Using JWT private key from environment variables");
                 let private_key = private_key.replace("\\n", "\n");
@@ -89,23 +469,74 @@ impl AuthService {
                         Self::extract_public_key_from_private(&private_key)?
                     }
                 };
-                Self::create_public_keys_json(&public_key)?;
-                Ok(AuthService::new(
-                    private_key,
-                    config.jwt_expiration_hours,
-                    public_key,
-                ))
+                let service =
+                    AuthService::new(private_key, config.jwt_expiration_hours, public_key)?;
+                service.self_test()?;
+                Self::create_public_keys_json(&service.generate_jwks()?)?;
+                service.with_key_source(KeySource::Environment)
             }
-            _ => {
+            None if config.environment == Environment::Prod => anyhow::bail!(
+                "No JWT keys configured: set JWT_PRIVATE_KEY or provide a key set at {}. \
+                 ENVIRONMENT=prod never generates keys, since every restart would invalidate \
+                 all tokens and replicas would disagree on them",
+                config.key_store_path
+            ),
+            None => {
                 println!("🔑 JWT keys not provided via environment variables, generating new keys...");
                 let keys = crate::misc::keypair::generate_key_pair()?;
-                Ok(AuthService::new(
+                AuthService::new(
                     keys.private_key,
                     config.jwt_expiration_hours,
                     keys.public_key,
-                ))
+                )?
             }
-        }
+        };
+        Ok(service
+            .with_issuer(&config.instance_name)
+            .with_key_store(&config.key_store_path)
+            .with_refresh_strategy(config.refresh_strategy)
+            .with_min_iat(config.token_min_iat)
+            .with_claims_format(config.claims_format()))
+    }
+
+    /// Sign a throwaway token with the current key and verify it again
+    ///
+    /// Run at startup so a keypair that doesn't belong together (e.g. a hand-edited
+    /// key store) fails immediately instead of at the first login.
+    pub fn self_test(&self) -> anyhow::Result<()> {
+        const SELF_TEST_SUBJECT: &str = "startup-self-test";
+        let kid = self.signing_key().kid;
+
+        let token = self
+            .gen_access_token(
+                Subject::Service(SELF_TEST_SUBJECT.to_string()),
+                String::new(),
+                Vec::new(),
+            )
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "JWT self-test failed: could not sign with the private key (kid {}): {:?}",
+                    kid,
+                    e
+                )
+            })?;
+        // Not verify_access_token: a TOKEN_MIN_IAT in the future would reject even a good key
+        let claims: AccessTokenClaims = self.decode_claims(&token).map_err(|e| match e {
+            ErrorKind::InvalidSignature => anyhow::anyhow!(
+                "JWT self-test failed: the public key for kid {} does not match its private key",
+                kid
+            ),
+            e => anyhow::anyhow!(
+                "JWT self-test failed: a token signed with kid {} did not verify: {:?}",
+                kid,
+                e
+            ),
+        })?;
+        anyhow::ensure!(
+            claims.sub == SELF_TEST_SUBJECT && claims.token_type == TokenType::Access,
+            "JWT self-test failed: claims did not survive a sign/verify round trip"
+        );
+        Ok(())
     }
 
     fn extract_public_key_from_private(private_key_pem: &str) -> anyhow::Result<String> {
@@ -115,174 +546,735 @@ impl AuthService {
         Ok(public_key_pem)
     }
 
-    fn create_public_keys_json(public_key_pem: &str) -> anyhow::Result<()> {
-        let public_key = rsa::RsaPublicKey::from_public_key_pem(public_key_pem)?;
-        let n = URL_SAFE_NO_PAD.encode(&public_key.n().to_bytes_be());
-        let e = URL_SAFE_NO_PAD.encode(&public_key.e().to_bytes_be());
-        let jwks = serde_json::json!({
-            "keys": [{
-                "kty": "RSA",
-                "kid": "exchange_api_key_1",
-                "use": "sig",
-                "alg": "RS256",
-                "n": n,
-                "e": e
-            }]
-        });
-        std::fs::write("public_keys.json", serde_json::to_string_pretty(&jwks)?)?;
+    fn create_public_keys_json(jwks: &Jwks) -> anyhow::Result<()> {
+        std::fs::write("public_keys.json", serde_json::to_string_pretty(jwks)?)?;
         println!("📄 Public key saved to: public_keys.json");
         Ok(())
     }
 
+    /// Mint an access token for `subject`; pass a user's `Uuid` directly for the usual case
+    ///
+    /// Service subjects (client credentials) have no email, so pass an empty one.
     pub fn gen_access_token(
         &self,
-        user_id: Uuid,
+        subject: impl Into<Subject>,
+        email: String,
+        scopes: Vec<Scope>,
+    ) -> Result<String, ErrorKind> {
+        self.gen_access_token_with_nbf(subject, email, scopes, None)
+    }
+
+    /// Mint an access token that only becomes valid at `not_before`
+    ///
+    /// The 15 minute lifetime starts counting from `not_before` rather than now,
+    /// so a scheduled token is usable for its full window.
+    pub fn gen_access_token_with_nbf(
+        &self,
+        subject: impl Into<Subject>,
         email: String,
         scopes: Vec<Scope>,
+        not_before: Option<DateTime<Utc>>,
     ) -> Result<String, ErrorKind> {
         let now = Utc::now();
-        let expiration = now + Duration::minutes(15);
+        let expiration = not_before.unwrap_or(now) + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES);
         let claims = AccessTokenClaims {
-            sub: user_id.to_string(),
+            sub: subject.into().to_string(),
             email,
             exp: expiration.timestamp() as usize,
             iat: now.timestamp() as usize,
-            iss: "exchange_api".to_string(),
+            nbf: not_before.map(|nbf| nbf.timestamp() as usize),
+            iss: self.issuer.clone(),
             token_type: TokenType::Access,
             scope: scopes,
         };
-        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
-        header.kid = Some("exchange_api_key_1".to_string());
-        encode(
-            &header,
-            &claims,
-            &EncodingKey::from_rsa_pem(self.private_key.as_bytes()).map_err(|e| e.into_kind())?,
-        )
-        .map_err(|e| e.into_kind())
+        self.sign(&claims, TokenType::Access)
+            .map_err(|e| e.into_kind())
     }
 
     pub fn gen_refresh_token(
         &self,
-        user_id: Uuid,
+        subject: impl Into<Subject>,
         email: String,
     ) -> Result<String, jsonwebtoken::errors::Error> {
         let now = Utc::now();
-        let expiration = now + Duration::days(30);
+        let expiration = now + Duration::days(REFRESH_TOKEN_TTL_DAYS);
         let claims = RefreshTokenClaims {
-            sub: user_id.to_string(),
+            sub: subject.into().to_string(),
             email,
             exp: expiration.timestamp() as usize,
             iat: now.timestamp() as usize,
-            iss: "exchange_api".to_string(),
+            iss: self.issuer.clone(),
             token_type: TokenType::Refresh,
             jti: Uuid::new_v4().to_string(),
         };
-        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
-        header.kid = Some("exchange_api_key_1".to_string());
+        self.sign(&claims, TokenType::Refresh)
+    }
+
+    /// Sign `claims` with the current key, laid out per the configured `ClaimsFormat`
+    fn sign<T: Serialize>(
+        &self,
+        claims: &T,
+        token_type: TokenType,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut claims = serde_json::to_value(claims)?;
+        let mut header = Header::new(SIGNING_ALGORITHM);
+        if let Some(claims) = claims.as_object_mut() {
+            let marker = claims.remove("token_type");
+            match (&self.claims_format.token_type, marker) {
+                (TokenTypeClaim::Claim(name), Some(marker)) => {
+                    claims.insert(name.clone(), marker);
+                }
+                (TokenTypeClaim::Claim(_), None) => {}
+                (TokenTypeClaim::Header, _) => {
+                    header.typ = Some(token_type.header_typ().to_string());
+                }
+            }
+            if self.claims_format.scope_string {
+                if let Some(scopes) = claims.remove("scope") {
+                    let parsed: Vec<Scope> = serde_json::from_value(scopes.clone())?;
+                    claims.insert("scope".to_string(), scopes_to_string(&parsed).into());
+                    claims.insert("scopes".to_string(), scopes);
+                }
+            }
+        }
+
+        let signing_key = self.signing_key();
+        header.kid = Some(signing_key.kid);
         encode(
             &header,
             &claims,
-            &EncodingKey::from_rsa_pem(self.private_key.as_bytes())?,
+            &EncodingKey::from_rsa_pem(signing_key.private_key.as_bytes())?,
         )
     }
 
-    pub fn gen_token_pair(
-        &self,
-        user_id: Uuid,
-        email: String,
-        scopes: Vec<Scope>,
-    ) -> Result<(String, String), jsonwebtoken::errors::Error> {
-        let access_token = self.gen_access_token(user_id, email.clone(), scopes)?;
-        let refresh_token = self.gen_refresh_token(user_id, email)?;
-        Ok((access_token, refresh_token))
+    /// Verify `token` and map any `ClaimsFormat` layout back onto the claim structs
+    fn decode_claims<T: serde::de::DeserializeOwned>(&self, token: &str) -> Result<T, ErrorKind> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.into_kind())?;
+        let mut claims = jsonwebtoken::decode::<serde_json::Value>(
+            token,
+            &self.decoding_key(token)?,
+            &self.validation(),
+        )
+        .map_err(|e| e.into_kind())?
+        .claims;
+        let map = claims.as_object_mut().ok_or(ErrorKind::InvalidToken)?;
+
+        if !map.contains_key("token_type") {
+            let marker = match &self.claims_format.token_type {
+                TokenTypeClaim::Claim(name) => map.remove(name),
+                TokenTypeClaim::Header => None,
+            }
+            .or_else(|| {
+                let token_type = TokenType::from_header_typ(header.typ.as_deref()?)?;
+                serde_json::to_value(token_type).ok()
+            });
+            if let Some(marker) = marker {
+                map.insert("token_type".to_string(), marker);
+            }
+        }
+        if let Some(serde_json::Value::String(scope)) = map.get("scope") {
+            let scopes = parse_scopes(scope).map_err(|_| ErrorKind::InvalidToken)?;
+            let scopes = serde_json::to_value(scopes).map_err(|_| ErrorKind::InvalidToken)?;
+            map.insert("scope".to_string(), scopes);
+        }
+        map.remove("scopes");
+
+        serde_json::from_value(claims).map_err(|_| ErrorKind::InvalidToken)
     }
 
-    pub fn verify_token(
-        &self,
-        token: &str,
-    ) -> Result<AccessTokenClaims, jsonwebtoken::errors::Error> {
-        let access_claims = self.verify_access_token(token)?;
-        Ok(AccessTokenClaims {
-            sub: access_claims.sub,
-            email: access_claims.email,
-            exp: access_claims.exp,
-            iat: access_claims.iat,
-            iss: access_claims.iss,
-            token_type: access_claims.token_type,
-            scope: access_claims.scope,
-        })
-    }
-
-    pub fn has_admin_scope(&self, token: &str) -> Result<bool, jsonwebtoken::errors::Error> {
-        let claims = self.verify_token(token)?;
-        Ok(claims.scope.contains(&Scope::Admin))
-    }
-
-    pub fn has_scope(
-        &self,
-        token: &str,
-        required_scope: Scope,
-    ) -> Result<bool, jsonwebtoken::errors::Error> {
-        let claims = self.verify_token(token)?;
-        Ok(claims.scope.contains(&required_scope))
+    fn signing_key(&self) -> SigningKey {
+        self.keys
+            .read()
+            .expect("key set lock poisoned")
+            .current
+            .clone()
     }
 
-    pub fn verify_access_token(&self, token: &str) -> Result<AccessTokenClaims, ErrorKind> {
+    /// Select the verification key matching the token's `kid` header
+    fn decoding_key(&self, token: &str) -> Result<jsonwebtoken::DecodingKey, ErrorKind> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.into_kind())?;
+        // Checked before a key is chosen, so an HMAC token can never be verified
+        // with a public key as its secret
+        if !self.algorithms.contains(&header.alg) {
+            return Err(ErrorKind::InvalidAlgorithm);
+        }
+        let keys = self.keys.read().expect("key set lock poisoned");
+        let public_key = keys
+            .public_key_for(header.kid.as_deref())
+            .ok_or(ErrorKind::InvalidToken)?;
+        jsonwebtoken::DecodingKey::from_rsa_pem(public_key.as_bytes()).map_err(|e| e.into_kind())
+    }
+
+    fn validation(&self) -> jsonwebtoken::Validation {
         let mut validation = jsonwebtoken::Validation::default();
-        validation.algorithms = vec![jsonwebtoken::Algorithm::RS256];
-        let decoded = jsonwebtoken::decode::<AccessTokenClaims>(
-            token,
-            &jsonwebtoken::DecodingKey::from_rsa_pem(self.public_key.as_bytes())
-                .map_err(|e| e.into_kind())?,
-            &validation,
-        )
-        .map_err(|e| e.into_kind())?;
-        if decoded.claims.token_type != TokenType::Access {
+        validation.algorithms = self.algorithms.clone();
+        validation.set_issuer(&[&self.issuer]);
+        validation.validate_nbf = true;
+        validation.leeway = self.leeway_secs;
+        validation
+    }
+
+    pub fn verify_access_token(&self, token: &str) -> Result<AccessTokenClaims, ErrorKind> {
+        let claims: AccessTokenClaims = self.decode_claims(token)?;
+        if claims.token_type != TokenType::Access {
             return Err(ErrorKind::InvalidToken);
         }
-        Ok(decoded.claims)
+        self.check_iat(claims.iat)?;
+        Ok(claims)
     }
 
     pub fn verify_refresh_token(&self, token: &str) -> Result<RefreshTokenClaims, ErrorKind> {
-        let mut validation = jsonwebtoken::Validation::default();
-        validation.algorithms = vec![jsonwebtoken::Algorithm::RS256];
-        let decoded = jsonwebtoken::decode::<RefreshTokenClaims>(
-            token,
-            &jsonwebtoken::DecodingKey::from_rsa_pem(self.public_key.as_bytes())
-                .map_err(|e| e.into_kind())?,
-            &validation,
-        )
-        .map_err(|e| e.into_kind())?;
-        if decoded.claims.token_type != TokenType::Refresh {
+        let claims: RefreshTokenClaims = self.decode_claims(token)?;
+        if claims.token_type != TokenType::Refresh {
             return Err(ErrorKind::InvalidToken);
         }
-        Ok(decoded.claims)
+        self.check_iat(claims.iat)?;
+        Ok(claims)
     }
 
-    pub fn refresh_access_token(
+    /// Issue a refresh token using the configured `RefreshStrategy`
+    ///
+    /// `Jwt` signs a stateless token; `Opaque` returns a random token and stores
+    /// only its hash, so it can be revoked by marking the row.
+    pub async fn issue_refresh_token(
         &self,
+        db: &sqlx::PgPool,
+        user_id: Uuid,
+        email: String,
+    ) -> Result<String, RefreshError> {
+        match self.refresh_strategy {
+            RefreshStrategy::Jwt => self
+                .gen_refresh_token(user_id, email)
+                .map_err(|e| e.into_kind().into()),
+            RefreshStrategy::Opaque => {
+                let token = refresh::generate_opaque_token();
+                let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+                refresh::store_opaque_token(db, user_id, &token, expires_at).await?;
+                Ok(token)
+            }
+        }
+    }
+
+    /// Resolve a refresh token issued by `issue_refresh_token` to its owner
+    pub async fn refresh_token_owner(
+        &self,
+        db: &sqlx::PgPool,
         refresh_token: &str,
-        scopes: Vec<Scope>,
-    ) -> Result<String, ErrorKind> {
-        let refresh_claims = self.verify_refresh_token(refresh_token)?;
-        let user_id = Uuid::parse_str(&refresh_claims.sub).map_err(|_| ErrorKind::InvalidToken)?;
-        self.gen_access_token(user_id, refresh_claims.email, scopes)
+    ) -> Result<RefreshTokenOwner, RefreshError> {
+        match self.refresh_strategy {
+            RefreshStrategy::Jwt => {
+                let claims = self.verify_refresh_token(refresh_token)?;
+                Ok(RefreshTokenOwner {
+                    subject: Subject::from_sub(&claims.sub),
+                    email: claims.email,
+                    issued_at: DateTime::from_timestamp(claims.iat as i64, 0)
+                        .ok_or(ErrorKind::InvalidToken)?,
+                })
+            }
+            RefreshStrategy::Opaque => {
+                let (user_id, email, issued_at) =
+                    refresh::lookup_opaque_token(db, refresh_token).await?;
+                Ok(RefreshTokenOwner {
+                    subject: Subject::User(user_id),
+                    email,
+                    issued_at,
+                })
+            }
+        }
     }
 
-    pub fn generate_jwks(&self) -> anyhow::Result<Jwks> {
-        let public_key = rsa::RsaPublicKey::from_public_key_pem(&self.public_key)?;
-        let n = URL_SAFE_NO_PAD.encode(&public_key.n().to_bytes_be());
-        let e = URL_SAFE_NO_PAD.encode(&public_key.e().to_bytes_be());
-        let jwk = Jwk {
-            alg: "RS256".to_string(),
-            e,
-            kid: "exchange_api_key_1".to_string(),
-            kty: "RSA".to_string(),
-            n,
-            r#use: "sig".to_string(),
+    /// Generate a new signing key and retire the current one to the verification set
+    ///
+    /// Tokens signed with the previous key keep verifying (matched by `kid`) until
+    /// they expire. Returns the new `kid`.
+    pub fn rotate_keys(&self) -> anyhow::Result<String> {
+        let keys = crate::misc::keypair::generate_rsa_key_pair()?;
+        let next = SigningKey {
+            kid: crate::misc::keypair::key_id(&keys.public_key)?,
+            private_key: keys.private_key,
+            public_key: keys.public_key,
+            created_at: Utc::now(),
         };
-        let jwks = Jwks { keys: vec![jwk] };
-        Ok(jwks)
+        let kid = next.kid.clone();
+
+        {
+            let mut key_set = self.keys.write().expect("key set lock poisoned");
+            key_set.rotate(next);
+            if let Some(path) = &self.key_store_path {
+                key_set.save(path)?;
+            }
+        }
+        if self.key_store_path.is_some() {
+            Self::create_public_keys_json(&self.generate_jwks()?)?;
+        }
+
+        println!("🔑 Rotated JWT signing key, new kid: {}", kid);
+        Ok(kid)
+    }
+
+    /// kid, algorithm and age of every signing and verification key, current first
+    pub fn list_keys(&self) -> Vec<KeySummary> {
+        self.keys
+            .read()
+            .expect("key set lock poisoned")
+            .summaries()
+    }
+
+    pub fn generate_jwks(&self) -> anyhow::Result<Jwks> {
+        let key_set = self.keys.read().expect("key set lock poisoned");
+        let keys = key_set
+            .verification_keys()
+            .into_iter()
+            .map(|(kid, public_key_pem)| {
+                let public_key = rsa::RsaPublicKey::from_public_key_pem(public_key_pem)?;
+                Ok(Jwk {
+                    alg: "RS256".to_string(),
+                    e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+                    kid: kid.to_string(),
+                    kty: "RSA".to_string(),
+                    n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                    r#use: "sig".to_string(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Jwks { keys })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs8::EncodePrivateKey;
+
+    fn test_key_pair() -> (String, String) {
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let private_pem = private_key
+            .to_pkcs8_pem(rsa::pkcs8::LineEnding::LF)
+            .unwrap()
+            .to_string();
+        let public_pem = AuthService::extract_public_key_from_private(&private_pem).unwrap();
+        (private_pem, public_pem)
+    }
+
+    fn test_service() -> AuthService {
+        let (private_pem, public_pem) = test_key_pair();
+        AuthService::new(private_pem, 24, public_pem).unwrap()
+    }
+
+    #[test]
+    fn tokens_from_another_instance_are_rejected() {
+        // Even with the same signing key, only the issuer tells the instances apart
+        let (private_pem, public_pem) = test_key_pair();
+        let staging = AuthService::new(private_pem.clone(), 24, public_pem.clone())
+            .unwrap()
+            .with_issuer("staging");
+        let prod = AuthService::new(private_pem, 24, public_pem)
+            .unwrap()
+            .with_issuer("prod");
+        let user_id = Uuid::new_v4();
+        let email = "admin@test-shop.com".to_string();
+        let access_token = staging
+            .gen_access_token(user_id, email.clone(), vec![Scope::Admin])
+            .unwrap();
+        let refresh_token = staging.gen_refresh_token(user_id, email).unwrap();
+
+        assert_eq!(
+            staging.verify_access_token(&access_token).unwrap().iss,
+            "staging"
+        );
+        assert_eq!(
+            prod.verify_access_token(&access_token).unwrap_err(),
+            ErrorKind::InvalidIssuer
+        );
+        assert_eq!(
+            prod.verify_refresh_token(&refresh_token).unwrap_err(),
+            ErrorKind::InvalidIssuer
+        );
+    }
+
+    #[test]
+    fn token_with_future_nbf_is_rejected_until_valid() {
+        let service = test_service().with_leeway(0);
+        let not_before = Utc::now() + Duration::seconds(2);
+        let token = service
+            .gen_access_token_with_nbf(
+                Uuid::new_v4(),
+                "viewer@test-shop.com".to_string(),
+                vec![Scope::Viewer],
+                Some(not_before),
+            )
+            .unwrap();
+
+        assert_eq!(
+            service.verify_access_token(&token).unwrap_err(),
+            ErrorKind::ImmatureSignature
+        );
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+        let claims = service.verify_access_token(&token).unwrap();
+        assert_eq!(claims.nbf, Some(not_before.timestamp() as usize));
+    }
+
+    #[test]
+    fn hmac_token_keyed_with_the_public_key_is_rejected() {
+        let service = test_service();
+        let token = service
+            .gen_access_token(
+                Uuid::new_v4(),
+                "admin@test-shop.com".to_string(),
+                vec![Scope::Admin],
+            )
+            .unwrap();
+        let payload = URL_SAFE_NO_PAD
+            .decode(token.split('.').nth(1).unwrap())
+            .unwrap();
+        let claims: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+
+        // Same claims and kid, but HS256 with the (public) verification key as the secret
+        let signing_key = service.signing_key();
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(signing_key.kid);
+        let forged = encode(
+            &header,
+            &claims,
+            &EncodingKey::from_secret(signing_key.public_key.as_bytes()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            service.verify_access_token(&forged).unwrap_err(),
+            ErrorKind::InvalidAlgorithm
+        );
+        assert!(service.verify_access_token(&token).is_ok());
+    }
+
+    #[test]
+    fn unsigned_token_is_rejected() {
+        let service = test_service();
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(br#"{"sub":"x","token_type":"access"}"#);
+
+        assert!(service
+            .verify_access_token(&format!("{}.{}.", header, payload))
+            .is_err());
+    }
+
+    #[test]
+    fn hmac_algorithms_are_never_accepted() {
+        assert_eq!(
+            verification_algorithms(&[Algorithm::HS256, Algorithm::RS256, Algorithm::HS512]),
+            vec![Algorithm::RS256]
+        );
+    }
+
+    #[test]
+    fn signing_kid_matches_jwks_kid() {
+        let service = test_service();
+        let token = service
+            .gen_access_token(
+                Uuid::new_v4(),
+                "viewer@test-shop.com".to_string(),
+                vec![Scope::Viewer],
+            )
+            .unwrap();
+        let refresh_token = service
+            .gen_refresh_token(Uuid::new_v4(), "viewer@test-shop.com".to_string())
+            .unwrap();
+
+        let jwks = service.generate_jwks().unwrap();
+        assert_eq!(jwks.keys.len(), 1);
+        let jwks_kid = Some(jwks.keys[0].kid.clone());
+        assert_eq!(jsonwebtoken::decode_header(&token).unwrap().kid, jwks_kid);
+        assert_eq!(
+            jsonwebtoken::decode_header(&refresh_token).unwrap().kid,
+            jwks_kid
+        );
+    }
+
+    #[test]
+    fn rotated_key_signs_new_tokens_and_old_tokens_still_verify() {
+        let service = test_service();
+        let old_token = service
+            .gen_access_token(
+                Uuid::new_v4(),
+                "admin@test-shop.com".to_string(),
+                vec![Scope::Admin],
+            )
+            .unwrap();
+        let old_kid = jsonwebtoken::decode_header(&old_token)
+            .unwrap()
+            .kid
+            .unwrap();
+
+        let new_kid = service.rotate_keys().unwrap();
+        assert_ne!(new_kid, old_kid);
+
+        let new_token = service
+            .gen_access_token(
+                Uuid::new_v4(),
+                "admin@test-shop.com".to_string(),
+                vec![Scope::Admin],
+            )
+            .unwrap();
+        let header = jsonwebtoken::decode_header(&new_token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some(new_kid.as_str()));
+
+        assert!(service.verify_access_token(&old_token).is_ok());
+        assert!(service.verify_access_token(&new_token).is_ok());
+
+        let kids: Vec<String> = service
+            .generate_jwks()
+            .unwrap()
+            .keys
+            .into_iter()
+            .map(|k| k.kid)
+            .collect();
+        assert_eq!(kids, vec![new_kid.clone(), old_kid.clone()]);
+
+        let listed: Vec<(String, bool, bool)> = service
+            .list_keys()
+            .into_iter()
+            .map(|k| (k.kid, k.current, k.retired_at.is_some()))
+            .collect();
+        assert_eq!(listed, vec![(new_kid, true, false), (old_kid, false, true)]);
+    }
+
+    #[test]
+    fn scopes_round_trip_through_oauth_strings() {
+        let scopes = vec![
+            Scope::Viewer,
+            Scope::Manager,
+            Scope::Admin,
+            Scope::Backoffice,
+        ];
+        let encoded = scopes_to_string(&scopes);
+        assert_eq!(encoded, "viewer manager admin backoffice");
+        assert_eq!(parse_scopes(&encoded).unwrap(), scopes);
+
+        assert_eq!(parse_scopes("").unwrap(), vec![]);
+        // Unknown names are custom scopes now; a miscased built-in is still rejected
+        assert_eq!(
+            parse_scopes("viewer Admin"),
+            Err(ParseScopeError("Admin".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn service_subjects_survive_refresh() {
+        let service = test_service();
+        let subject = Subject::Service("inventory-worker".to_string());
+        let refresh_token = service
+            .gen_refresh_token(subject.clone(), String::new())
+            .unwrap();
+
+        // JWT refresh tokens are checked without touching the database
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let owner = service
+            .refresh_token_owner(&db, &refresh_token)
+            .await
+            .unwrap();
+        let token = service
+            .gen_access_token(owner.subject, owner.email, vec![Scope::Viewer])
+            .unwrap();
+        let claims = service.verify_access_token(&token).unwrap();
+        assert_eq!(claims.sub, "inventory-worker");
+        assert_eq!(Subject::from_sub(&claims.sub), subject);
+
+        let user_id = Uuid::new_v4();
+        assert_eq!(
+            Subject::from_sub(&user_id.to_string()),
+            Subject::User(user_id)
+        );
+    }
+
+    #[test]
+    fn self_test_catches_a_public_key_from_another_pair() {
+        assert!(test_service().self_test().is_ok());
+
+        let ours = crate::misc::keypair::generate_rsa_key_pair().unwrap();
+        let theirs = crate::misc::keypair::generate_rsa_key_pair().unwrap();
+        let mismatched = AuthService::new(ours.private_key, 24, theirs.public_key).unwrap();
+        let error = mismatched.self_test().unwrap_err().to_string();
+        assert!(error.contains("does not match its private key"));
+
+        // A cutoff in the future doesn't make a good key look broken
+        let cutoff = (Utc::now() + Duration::hours(1)).timestamp();
+        let service = test_service().with_min_iat(Some(cutoff));
+        assert!(service.self_test().is_ok());
+    }
+
+    fn payload(token: &str) -> serde_json::Value {
+        use base64::Engine;
+        let payload = token.split('.').nth(1).unwrap();
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(payload)
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn custom_scopes_round_trip_without_changing_built_in_names() {
+        let reports = Scope::Custom("reports:read".to_string());
+        assert_eq!(
+            serde_json::to_value([Scope::Admin, reports.clone()]).unwrap(),
+            serde_json::json!(["Admin", "reports:read"])
+        );
+        assert_eq!(
+            parse_scopes("viewer reports:read").unwrap(),
+            vec![Scope::Viewer, reports.clone()]
+        );
+        assert_eq!(
+            scopes_to_string(&[Scope::Viewer, reports.clone()]),
+            "viewer reports:read"
+        );
+
+        // Nothing can pass itself off as a built-in scope
+        for name in ["Admin", "ADMIN", "a b", "", "quote\"d"] {
+            assert!(name.parse::<Scope>().is_err(), "{:?}", name);
+        }
+        assert!(serde_json::to_value(Scope::Custom("Admin".to_string())).is_err());
+        assert_eq!(
+            serde_json::from_value::<Scope>(serde_json::json!("Admin")).unwrap(),
+            Scope::Admin
+        );
+        assert!(serde_json::from_value::<Scope>(serde_json::json!("admin")).is_err());
+
+        for format in [ClaimsFormat::default(), ClaimsFormat::new("token_type", true)] {
+            let service = test_service().with_claims_format(format);
+            let token = service
+                .gen_access_token(
+                    Uuid::new_v4(),
+                    String::new(),
+                    vec![Scope::Viewer, reports.clone()],
+                )
+                .unwrap();
+            let scopes = service.verify_access_token(&token).unwrap().scope;
+            assert_eq!(scopes, vec![Scope::Viewer, reports.clone()]);
+            assert!(!scopes.contains(&Scope::Custom("reports:write".to_string())));
+        }
+    }
+
+    #[test]
+    fn configured_claims_format_round_trips() {
+        let scopes = vec![Scope::Viewer, Scope::Backoffice];
+        for format in [
+            ClaimsFormat::new("https://shop.example/token_type", true),
+            ClaimsFormat::new("header", false),
+        ] {
+            let service = test_service().with_claims_format(format.clone());
+            let access_token = service
+                .gen_access_token(Uuid::new_v4(), String::new(), scopes.clone())
+                .unwrap();
+            let refresh_token = service
+                .gen_refresh_token(Uuid::new_v4(), String::new())
+                .unwrap();
+
+            let claims = payload(&access_token);
+            assert!(claims.get("token_type").is_none());
+            match &format.token_type {
+                TokenTypeClaim::Claim(name) => assert_eq!(claims[name.as_str()], "Access"),
+                TokenTypeClaim::Header => assert_eq!(
+                    jsonwebtoken::decode_header(&access_token).unwrap().typ,
+                    Some("at+jwt".to_string())
+                ),
+            }
+            if format.scope_string {
+                assert_eq!(claims["scope"], "viewer backoffice");
+                assert_eq!(
+                    claims["scopes"],
+                    serde_json::json!(["Viewer", "Backoffice"])
+                );
+            }
+
+            assert_eq!(
+                service.verify_access_token(&access_token).unwrap().scope,
+                scopes
+            );
+            assert!(service.verify_refresh_token(&refresh_token).is_ok());
+            // Still typed: one kind of token can't stand in for the other
+            assert!(service.verify_access_token(&refresh_token).is_err());
+            assert!(service.verify_refresh_token(&access_token).is_err());
+        }
+    }
+
+    #[test]
+    fn default_layout_tokens_verify_after_format_change() {
+        let service = test_service();
+        let token = service
+            .gen_access_token(Uuid::new_v4(), String::new(), vec![Scope::Admin])
+            .unwrap();
+
+        let service = service.with_claims_format(ClaimsFormat::new("header", true));
+        let claims = service.verify_access_token(&token).unwrap();
+        assert_eq!(claims.scope, vec![Scope::Admin]);
+    }
+
+    #[test]
+    fn token_without_nbf_verifies_immediately() {
+        let service = test_service().with_leeway(0);
+        let token = service
+            .gen_access_token(
+                Uuid::new_v4(),
+                "viewer@test-shop.com".to_string(),
+                vec![Scope::Viewer],
+            )
+            .unwrap();
+
+        let claims = service.verify_access_token(&token).unwrap();
+        assert_eq!(claims.nbf, None);
+    }
+
+    #[test]
+    fn tokens_issued_before_min_iat_are_rejected() {
+        let service = test_service();
+        let user_id = Uuid::new_v4();
+        let email = "viewer@test-shop.com".to_string();
+        let token = service
+            .gen_access_token(user_id, email.clone(), vec![Scope::Viewer])
+            .unwrap();
+        let refresh_token = service.gen_refresh_token(user_id, email).unwrap();
+
+        service.invalidate_before(Utc::now() - Duration::minutes(5));
+        assert!(service.verify_access_token(&token).is_ok());
+
+        let cutoff = Utc::now() + Duration::minutes(5);
+        service.invalidate_before(cutoff);
+        assert_eq!(
+            service.min_iat(),
+            DateTime::from_timestamp(cutoff.timestamp(), 0)
+        );
+        assert_eq!(
+            service.verify_access_token(&token).unwrap_err(),
+            ErrorKind::InvalidToken
+        );
+        assert_eq!(
+            service.verify_refresh_token(&refresh_token).unwrap_err(),
+            ErrorKind::InvalidToken
+        );
+
+        // An earlier cutoff never re-validates tokens
+        service.invalidate_before(Utc::now() - Duration::minutes(5));
+        assert!(service.verify_access_token(&token).is_err());
+    }
+
+    #[test]
+    fn prod_refuses_to_start_without_keys() {
+        // Not the dev path: generating keys writes keys.json into the working directory
+        let missing_store =
+            std::env::temp_dir().join(format!("no-key-set-{}.json", Uuid::new_v4()));
+        let config = crate::Args {
+            environment: Environment::Prod,
+            key_store_path: missing_store.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        let err = AuthService::from_config(&config).err().unwrap();
+        assert!(
+            err.to_string().starts_with("No JWT keys configured"),
+            "{}",
+            err
+        );
+    }
+}
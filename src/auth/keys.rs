@@ -0,0 +1,193 @@
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Retired keys stay in the verification set for as long as the longest-lived
+/// token they could have signed (refresh tokens, 30 days)
+const RETIRED_KEY_RETENTION_DAYS: i64 = 30;
+
+/// The key currently used to sign new tokens
+///
+/// Only serialized to write the key store; `Debug` leaves the private key out.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SigningKey {
+    pub kid: String,
+    pub private_key: String,
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("kid", &self.kid)
+            .field("private_key", &"<redacted>")
+            .field("public_key", &self.public_key)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+/// Public half of a rotated-out key, kept so tokens it signed still verify until expiry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RetiredKey {
+    pub kid: String,
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+    pub retired_at: DateTime<Utc>,
+}
+
+/// What an operator may see about a key: never any key material
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeySummary {
+    pub kid: String,
+    pub alg: &'static str,
+    pub created_at: DateTime<Utc>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub current: bool,
+}
+
+/// Current signing key plus previous verification keys, looked up by `kid`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeySet {
+    pub current: SigningKey,
+    pub previous: Vec<RetiredKey>,
+}
+
+impl KeySet {
+    pub fn new(current: SigningKey) -> Self {
+        Self {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Public key PEM for a token's `kid`; tokens without a `kid` use the current key
+    pub fn public_key_for(&self, kid: Option<&str>) -> Option<&str> {
+        match kid {
+            None => Some(&self.current.public_key),
+            Some(kid) if kid == self.current.kid => Some(&self.current.public_key),
+            Some(kid) => self
+                .previous
+                .iter()
+                .find(|key| key.kid == kid)
+                .map(|key| key.public_key.as_str()),
+        }
+    }
+
+    /// All `(kid, public key PEM)` pairs that tokens may be verified with, current first
+    pub fn verification_keys(&self) -> Vec<(&str, &str)> {
+        std::iter::once((self.current.kid.as_str(), self.current.public_key.as_str()))
+            .chain(
+                self.previous
+                    .iter()
+                    .map(|key| (key.kid.as_str(), key.public_key.as_str())),
+            )
+            .collect()
+    }
+
+    /// Every key in the set, current first, then retired keys newest first
+    pub fn summaries(&self) -> Vec<KeySummary> {
+        // Keys are always RSA and sign with RS256, as advertised in the JWKS
+        std::iter::once(KeySummary {
+            kid: self.current.kid.clone(),
+            alg: "RS256",
+            created_at: self.current.created_at,
+            retired_at: None,
+            current: true,
+        })
+        .chain(self.previous.iter().map(|key| KeySummary {
+            kid: key.kid.clone(),
+            alg: "RS256",
+            created_at: key.created_at,
+            retired_at: Some(key.retired_at),
+            current: false,
+        }))
+        .collect()
+    }
+
+    /// Promote `next` to the signing key, retiring the current one to the verification set
+    pub fn rotate(&mut self, next: SigningKey) {
+        let now = Utc::now();
+        let retired = std::mem::replace(&mut self.current, next);
+        self.previous.insert(
+            0,
+            RetiredKey {
+                kid: retired.kid,
+                public_key: retired.public_key,
+                created_at: retired.created_at,
+                retired_at: now,
+            },
+        );
+        self.previous
+            .retain(|key| key.retired_at + Duration::days(RETIRED_KEY_RETENTION_DAYS) > now);
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    /// Write the key set, readable by its owner only since it holds the private key
+    ///
+    /// Written to a temporary file that is then renamed over `path`, so a crash
+    /// mid-write never leaves a truncated key set behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = Path::new(&tmp_path);
+
+        // A leftover from an interrupted save may have other permissions; start afresh
+        let _ = std::fs::remove_file(tmp_path);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(tmp_path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_key_sets_are_private_and_load_back() -> anyhow::Result<()> {
+        let keys = crate::misc::keypair::generate_rsa_key_pair()?;
+        let key_set = KeySet::new(SigningKey {
+            kid: crate::misc::keypair::key_id(&keys.public_key)?,
+            private_key: keys.private_key,
+            public_key: keys.public_key,
+            created_at: Utc::now(),
+        });
+        let path = std::env::temp_dir().join(format!("key_set-{}.json", uuid::Uuid::new_v4()));
+
+        key_set.save(&path)?;
+        // Saving again replaces the file in place
+        key_set.save(&path)?;
+        let loaded = KeySet::load(&path)?;
+        #[cfg(unix)]
+        let mode =
+            std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path)?.permissions());
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(
+            loaded.map(|keys| keys.current.kid),
+            Some(key_set.current.kid)
+        );
+        #[cfg(unix)]
+        assert_eq!(mode & 0o777, 0o600);
+        Ok(())
+    }
+}
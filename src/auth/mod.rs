@@ -1 +1,3 @@
 pub mod jkws;
+pub mod keys;
+pub mod refresh;
@@ -0,0 +1,152 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::errors::ErrorKind;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::jkws::Subject;
+
+/// How refresh tokens are issued and checked
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum RefreshStrategy {
+    /// Stateless signed JWTs; valid until they expire
+    #[default]
+    Jwt,
+    /// Random tokens whose hash is stored in `refresh_tokens`; revocable at any time
+    Opaque,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefreshError {
+    #[error("Invalid refresh token")]
+    Invalid,
+    #[error("Token error: {0:?}")]
+    Token(ErrorKind),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<ErrorKind> for RefreshError {
+    fn from(kind: ErrorKind) -> Self {
+        RefreshError::Token(kind)
+    }
+}
+
+/// Who a refresh token belongs to, and when it was issued
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshTokenOwner {
+    pub subject: Subject,
+    pub email: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// 256 random bits, base64url-encoded
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Store the hash of a newly issued opaque token
+pub async fn store_opaque_token(
+    db: &PgPool,
+    user_id: Uuid,
+    token: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_token(token))
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Resolve an opaque token to its `(user_id, email, created_at)`, if unexpired, unrevoked and the user is active
+pub async fn lookup_opaque_token(
+    db: &PgPool,
+    token: &str,
+) -> Result<(Uuid, String, DateTime<Utc>), RefreshError> {
+    sqlx::query_as::<_, (Uuid, String, DateTime<Utc>)>(
+        r#"
+        SELECT u.id, u.email, rt.created_at
+        FROM refresh_tokens rt
+        JOIN users u ON u.id = rt.user_id
+        WHERE rt.token_hash = $1
+          AND rt.revoked_at IS NULL
+          AND rt.expires_at > NOW()
+          AND u.is_active
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await?
+    .ok_or(RefreshError::Invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jkws::AuthService;
+    use crate::test_support::{insert_merchant, test_db};
+
+    #[tokio::test]
+    async fn opaque_tokens_are_hashed_and_revocable() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+
+        let merchant_id = insert_merchant(&db).await?;
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (merchant_id, email) VALUES ($1, 'viewer@test-shop.com') RETURNING id",
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await?;
+
+        let keys = crate::misc::keypair::generate_rsa_key_pair()?;
+        let auth_service = AuthService::new(keys.private_key, 24, keys.public_key)?
+            .with_refresh_strategy(RefreshStrategy::Opaque);
+
+        let token = auth_service
+            .issue_refresh_token(&db, user_id, "viewer@test-shop.com".to_string())
+            .await?;
+        let stored: String =
+            sqlx::query_scalar("SELECT token_hash FROM refresh_tokens WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&db)
+                .await?;
+        let owner = auth_service.refresh_token_owner(&db, &token).await;
+
+        sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&db)
+            .await?;
+        let after_revoke = auth_service.refresh_token_owner(&db, &token).await;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_ne!(stored, token);
+        assert_eq!(stored, hash_token(&token));
+        assert_eq!(owner?.subject, Subject::User(user_id));
+        assert!(matches!(after_revoke, Err(RefreshError::Invalid)));
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use axum::{
+    extract::Path,
+    http::{HeaderName, StatusCode},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::jkws::{parse_scopes, scopes_to_string, AccessTokenClaims, Scope, TokenType};
+use crate::auth::refresh::{generate_opaque_token, hash_token};
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError, JsonBody};
+
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+pub(crate) const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Every key starts with this, so leaked keys are easy to recognise (e.g. by secret scanners)
+const KEY_PREFIX: &str = "ak_";
+
+/// How much of a key is kept in clear to tell keys apart: the prefix plus 8 characters
+const KEY_PREFIX_LEN: usize = KEY_PREFIX.len() + 8;
+
+const API_KEY_COLUMNS: &str = r#"
+    id, merchant_id, key_prefix, scopes, description, last_used_at, revoked_at, created_at
+"#;
+
+pub fn api_keys_router() -> Router {
+    Router::new()
+        .route(
+            "/merchants/:id/api-keys",
+            get(list_api_keys).post(create_api_key),
+        )
+        .route("/merchants/:id/api-keys/:key_id", delete(revoke_api_key))
+}
+
+/// The key a request authenticated with, and the merchant it is bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKeyCaller {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+}
+
+/// Resolve an `X-Api-Key` value to its merchant and scopes
+///
+/// Unknown and revoked keys, and keys of deleted merchants, are the same 401. The
+/// key's scopes go into claims that were never signed; their `sub` is
+/// `api_key:<id>`, a service subject, so handlers that need a user reject key
+/// callers as they do client-credentials tokens.
+pub(crate) async fn authenticate(
+    ctx: &ApiContext,
+    key: &str,
+) -> Result<AuthenticatedUser, AppError> {
+    let (id, merchant_id, scopes): (Uuid, Uuid, String) = sqlx::query_as(
+        r#"
+        SELECT k.id, k.merchant_id, k.scopes
+        FROM api_keys k
+        JOIN merchants m ON m.id = k.merchant_id
+        WHERE k.key_hash = $1 AND k.revoked_at IS NULL AND m.deleted_at IS NULL
+        "#,
+    )
+    .bind(hash_token(key))
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::Unauthorized)?;
+
+    let mut scope = parse_scopes(&scopes).map_err(|e| {
+        eprintln!("API key {} has invalid scopes configured: {}", id, e);
+        AppError::InternalServerError
+    })?;
+    // Keys created before `admin` and `operator` were refused keep their other scopes only
+    scope.retain(|s| *s != Scope::Admin && *s != Scope::Operator);
+
+    // Off the request path: the response shouldn't wait on a write
+    tokio::spawn(touch(ctx.db.clone(), id));
+
+    let now = Utc::now().timestamp() as usize;
+    Ok(AuthenticatedUser {
+        claims: AccessTokenClaims {
+            sub: format!("api_key:{}", id),
+            email: String::new(),
+            exp: now,
+            iat: now,
+            nbf: None,
+            iss: ctx.auth_service.issuer().to_string(),
+            token_type: TokenType::Access,
+            scope,
+        },
+        api_key: Some(ApiKeyCaller { id, merchant_id }),
+    })
+}
+
+/// Record that key `id` was just used, at most once a minute so busy keys don't write per request
+async fn touch(db: PgPool, id: Uuid) {
+    let result = sqlx::query(
+        r#"
+        UPDATE api_keys SET last_used_at = NOW()
+        WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+        "#,
+    )
+    .bind(id)
+    .execute(&db)
+    .await;
+    if let Err(e) = result {
+        eprintln!("Could not update last_used_at of API key {}: {}", id, e);
+    }
+}
+
+// Create an API key for the merchant (ADMIN ONLY, own merchant only)
+// The plaintext key is in this response and nowhere else; only its hash is stored.
+async fn create_api_key(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(merchant_id): Path<Uuid>,
+    JsonBody(req): JsonBody<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), AppError> {
+    user.require_scope(Scope::Admin)?;
+    // Keys are created by people, not by other keys or services
+    let created_by = user.user_id()?;
+    ensure_own_merchant(&ctx, &user, merchant_id).await?;
+
+    let scopes = parse_scopes(&req.scopes).map_err(|e| AppError::Validation(e.to_string()))?;
+    if scopes.is_empty() {
+        return Err(AppError::Validation(
+            "scopes must name at least one scope".to_string(),
+        ));
+    }
+    // A key never expires, so it must not manage the merchant or act on the platform
+    if scopes.contains(&Scope::Admin) || scopes.contains(&Scope::Operator) {
+        return Err(AppError::Validation(
+            "API keys can't have the admin or operator scope".to_string(),
+        ));
+    }
+
+    let key = format!("{}{}", KEY_PREFIX, generate_opaque_token());
+    let info = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        r#"
+        INSERT INTO api_keys (merchant_id, key_hash, key_prefix, scopes, description, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING {}
+        "#,
+        API_KEY_COLUMNS
+    ))
+    .bind(merchant_id)
+    .bind(hash_token(&key))
+    .bind(&key[..KEY_PREFIX_LEN])
+    .bind(scopes_to_string(&scopes))
+    .bind(req.description)
+    .bind(created_by)
+    .fetch_one(&ctx.db)
+    .await?;
+
+    eprintln!(
+        "Created API key: id={}, merchant_id={}, scope={}, created_by={}",
+        info.id, merchant_id, info.scopes, created_by
+    );
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { key, info })))
+}
+
+// List the merchant's API keys, revoked ones included (ADMIN ONLY, own merchant only)
+// Keys unused for a long time show up by `last_used_at`.
+async fn list_api_keys(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(merchant_id): Path<Uuid>,
+) -> AppResult<Vec<ApiKeyInfo>> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, merchant_id).await?;
+
+    let keys = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        "SELECT {} FROM api_keys WHERE merchant_id = $1 ORDER BY created_at DESC, id DESC",
+        API_KEY_COLUMNS
+    ))
+    .bind(merchant_id)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(keys))
+}
+
+// Revoke an API key; requests with it fail with 401 from then on (ADMIN ONLY, own merchant only)
+async fn revoke_api_key(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path((merchant_id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, merchant_id).await?;
+
+    // Revoking twice is fine; the first revocation time is kept
+    let revoked = sqlx::query(
+        r#"
+        UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND merchant_id = $2
+        "#,
+    )
+    .bind(key_id)
+    .bind(merchant_id)
+    .execute(&ctx.db)
+    .await?
+    .rows_affected();
+    if revoked == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    eprintln!(
+        "Revoked API key: id={}, merchant_id={}",
+        key_id, merchant_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, insert_user, send, send_with_headers, test_db};
+
+    #[tokio::test]
+    async fn api_keys_authenticate_until_revoked() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let mut merchant_ids = Vec::new();
+        for _ in 0..2 {
+            let merchant_id = insert_merchant(&db).await?;
+            merchant_ids.push(merchant_id);
+        }
+        let (merchant_id, other_merchant_id) = (merchant_ids[0], merchant_ids[1]);
+        let (_, email) = insert_user(&db, merchant_id, "admin").await?;
+        let app = crate::http::test_router(db.clone());
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let bearer = format!(
+            "Bearer {}",
+            session["data"]["access_token"].as_str().unwrap()
+        );
+        let admin = &[("Authorization", bearer.as_str())];
+
+        let keys_uri = format!("/api/v1/merchants/{}/api-keys", merchant_id);
+        let body = serde_json::json!({ "scopes": "viewer backoffice", "description": "ERP" });
+        let (status, created) = send_with_headers(&app, "POST", &keys_uri, admin, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let key = created["key"].as_str().unwrap().to_string();
+        assert!(key.starts_with(created["key_prefix"].as_str().unwrap()));
+        assert_eq!(created["scopes"], "viewer backoffice");
+        let with_key = &[("X-Api-Key", key.as_str())];
+
+        // The key acts for its own merchant only
+        let tags_uri = |merchant_id: Uuid| format!("/api/v1/tags?merchant_id={}", merchant_id);
+        let (status, _) =
+            send_with_headers(&app, "GET", &tags_uri(merchant_id), with_key, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) =
+            send_with_headers(&app, "GET", &tags_uri(other_merchant_id), with_key, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // It has no admin scope, so can't manage keys; listings never show the key
+        let (status, _) = send_with_headers(&app, "GET", &keys_uri, with_key, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, listed) = send_with_headers(&app, "GET", &keys_uri, admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed.as_array().map(Vec::len), Some(1));
+        assert!(listed[0].get("key").is_none());
+
+        // last_used_at is written in the background
+        let mut last_used_at = None;
+        for _ in 0..50 {
+            last_used_at = sqlx::query_scalar("SELECT last_used_at FROM api_keys WHERE id = $1")
+                .bind(Uuid::parse_str(created["id"].as_str().unwrap())?)
+                .fetch_one(&db)
+                .await?;
+            if last_used_at.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let _: chrono::DateTime<Utc> = last_used_at.expect("last_used_at was never set");
+
+        let body = serde_json::json!({ "scopes": "viewer admin" });
+        let (status, _) = send_with_headers(&app, "POST", &keys_uri, admin, Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let key_uri = format!("{}/{}", keys_uri, created["id"].as_str().unwrap());
+        let (status, _) = send_with_headers(&app, "DELETE", &key_uri, admin, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) =
+            send_with_headers(&app, "GET", &tags_uri(merchant_id), with_key, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let unknown = &[("X-Api-Key", "ak_unknown")];
+        let (status, _) =
+            send_with_headers(&app, "GET", &tags_uri(merchant_id), unknown, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Keys stop working once their merchant is soft-deleted
+        let body = serde_json::json!({ "scopes": "viewer" });
+        let (_, created) = send_with_headers(&app, "POST", &keys_uri, admin, Some(body)).await;
+        let with_key = &[("X-Api-Key", created["key"].as_str().unwrap())];
+        sqlx::query("UPDATE merchants SET deleted_at = NOW() WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        let (status, _) =
+            send_with_headers(&app, "GET", &tags_uri(merchant_id), with_key, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        sqlx::query("DELETE FROM merchants WHERE id = ANY($1)")
+            .bind(&merchant_ids)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use axum::{routing::post, Json, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::Value;
+
+use crate::auth::jkws::Scope;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::types::{AppError, DecodeTokenRequest, DecodedToken};
+use crate::http::JsonBody;
+
+pub fn decode_router() -> Router {
+    Router::new().route("/auth/decode", post(decode_token))
+}
+
+// Show a token's header and claims WITHOUT checking its signature (ADMIN ONLY)
+// Support tooling only: the response is always marked `"verified": false`.
+async fn decode_token(
+    user: AuthenticatedUser,
+    JsonBody(request): JsonBody<DecodeTokenRequest>,
+) -> Result<Json<DecodedToken>, AppError> {
+    user.require_scope(Scope::Admin)?;
+    eprintln!(
+        "Decoding token without verification: requested by {}",
+        user.claims.sub
+    );
+
+    let (header, claims) = decode_unverified(&request.token)?;
+    Ok(Json(DecodedToken {
+        verified: false,
+        header,
+        claims,
+    }))
+}
+
+/// Base64url-decode the header and payload segments of a JWT, ignoring the signature
+fn decode_unverified(token: &str) -> Result<(Value, Value), AppError> {
+    let mut segments = token.split('.');
+    let (Some(header), Some(payload), Some(_signature), None) = (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) else {
+        return Err(AppError::Validation(
+            "Token must have three dot-separated segments".to_string(),
+        ));
+    };
+
+    let decode_segment = |segment: &str, name: &str| -> Result<Value, AppError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(segment)
+            .map_err(|_| AppError::Validation(format!("Token {} is not valid base64url", name)))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|_| AppError::Validation(format!("Token {} is not valid JSON", name)))
+    };
+
+    Ok((
+        decode_segment(header, "header")?,
+        decode_segment(payload, "payload")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_claims_even_with_a_bad_signature() {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(br#"{"sub":"abc","scope":["Admin"]}"#);
+        let token = format!("{}.{}.not-a-real-signature", header, payload);
+
+        let (header, claims) = decode_unverified(&token).unwrap();
+        assert_eq!(header["alg"], "RS256");
+        assert_eq!(claims["sub"], "abc");
+
+        assert!(decode_unverified("only.two").is_err());
+    }
+}
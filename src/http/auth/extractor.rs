@@ -0,0 +1,261 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{
+        header::{AUTHORIZATION, COOKIE},
+        request::Parts,
+        HeaderMap,
+    },
+};
+use jsonwebtoken::errors::ErrorKind;
+use uuid::Uuid;
+
+use crate::auth::jkws::{AccessTokenClaims, Scope, Subject};
+use crate::http::auth::api_keys::{self, ApiKeyCaller, X_API_KEY};
+use crate::http::{ApiContext, AppError};
+
+/// Verified caller identity, extracted from the `Authorization: Bearer <token>` header
+///
+/// Without an Authorization header, an `X-Api-Key` header is tried next, then the
+/// `ACCESS_TOKEN_COOKIE` cookie that browsers send. Handlers that take this
+/// extractor reject requests without a valid credential with 401 before any
+/// handler code runs. Token failures carry an RFC 6750 `WWW-Authenticate: Bearer`
+/// challenge: none for a missing token, `invalid_token` for a bad or expired one.
+pub struct AuthenticatedUser {
+    pub claims: AccessTokenClaims,
+    /// Set when the caller used an API key, which is bound to one merchant
+    pub api_key: Option<ApiKeyCaller>,
+}
+
+impl AuthenticatedUser {
+    /// Fail with 403 `insufficient_scope`, naming `scope`, unless the token carries it
+    pub fn require_scope(&self, scope: Scope) -> Result<(), AppError> {
+        if self.claims.scope.contains(&scope) {
+            Ok(())
+        } else {
+            Err(AppError::InsufficientScope(scope))
+        }
+    }
+
+    pub fn subject(&self) -> Subject {
+        Subject::from_sub(&self.claims.sub)
+    }
+
+    /// The caller's user id; service tokens have none and are rejected with 401
+    pub fn user_id(&self) -> Result<Uuid, AppError> {
+        match self.subject() {
+            Subject::User(user_id) => Ok(user_id),
+            Subject::Service(_) => Err(AppError::Unauthorized),
+        }
+    }
+}
+
+/// Claims of the request's access token, verified earlier in the request (by the
+/// rate limiter) so the extractor doesn't verify the same token again
+#[derive(Clone)]
+pub(crate) struct VerifiedAccessToken(pub AccessTokenClaims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ctx = parts
+            .extensions
+            .get::<ApiContext>()
+            .ok_or(AppError::InternalServerError)?;
+
+        if !parts.headers.contains_key(AUTHORIZATION) {
+            if let Some(key) = parts.headers.get(X_API_KEY) {
+                let key = key.to_str().map_err(|_| AppError::Unauthorized)?;
+                return api_keys::authenticate(ctx, key).await;
+            }
+        }
+
+        if let Some(VerifiedAccessToken(claims)) = parts.extensions.get() {
+            return Ok(Self {
+                claims: claims.clone(),
+                api_key: None,
+            });
+        }
+
+        // A present but unusable Authorization header is a bad token, not a missing one
+        let token = access_token(&parts.headers, &ctx.config.access_token_cookie).ok_or(
+            if parts.headers.contains_key(AUTHORIZATION) {
+                AppError::InvalidToken
+            } else {
+                AppError::MissingToken
+            },
+        )?;
+
+        let claims = ctx
+            .auth_service
+            .verify_access_token(token)
+            .map_err(|e| match e {
+                ErrorKind::ExpiredSignature => AppError::ExpiredToken,
+                _ => AppError::InvalidToken,
+            })?;
+
+        Ok(Self {
+            claims,
+            api_key: None,
+        })
+    }
+}
+
+/// The bearer token from `Authorization`, or from cookie `cookie_name` when that header is absent
+///
+/// A present but malformed Authorization header never falls back to the cookie.
+pub(crate) fn access_token<'a>(headers: &'a HeaderMap, cookie_name: &str) -> Option<&'a str> {
+    match headers.get(AUTHORIZATION) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer ")),
+        None => cookie(headers, cookie_name),
+    }
+}
+
+/// Value of cookie `name` across all `Cookie` headers
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header::WWW_AUTHENTICATE, Request, StatusCode},
+        routing::get,
+        Extension, Router,
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn cookie_is_only_used_without_an_authorization_header() {
+        let from_cookie = headers(&[
+            ("cookie", "theme=dark"),
+            ("cookie", "access_token_old=x; access_token=a.b.c"),
+        ]);
+        assert_eq!(access_token(&from_cookie, "access_token"), Some("a.b.c"));
+        assert_eq!(access_token(&from_cookie, "session"), None);
+
+        let both = headers(&[
+            ("authorization", "Bearer d.e.f"),
+            ("cookie", "access_token=a.b.c"),
+        ]);
+        assert_eq!(access_token(&both, "access_token"), Some("d.e.f"));
+
+        // A broken header is an error, not a reason to try the cookie
+        let malformed = headers(&[
+            ("authorization", "Basic Zm9v"),
+            ("cookie", "access_token=a.b.c"),
+        ]);
+        assert_eq!(access_token(&malformed, "access_token"), None);
+    }
+
+    #[tokio::test]
+    async fn token_and_scope_failures_carry_a_bearer_challenge() {
+        // Bearer tokens are checked without touching the database
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let ctx = crate::http::test_context(db.clone(), db);
+        let auth = ctx.auth_service.clone();
+        let app = Router::new()
+            .route(
+                "/admin-only",
+                get(|user: AuthenticatedUser| async move { user.require_scope(Scope::Admin) }),
+            )
+            .layer(Extension(ctx));
+        let token = |scope: Scope, not_before: Option<chrono::DateTime<Utc>>| {
+            let header = format!(
+                "Bearer {}",
+                auth.gen_access_token_with_nbf(
+                    Uuid::new_v4(),
+                    String::new(),
+                    vec![scope],
+                    not_before
+                )
+                .unwrap()
+            );
+            Some(header)
+        };
+
+        let an_hour_ago = Some(Utc::now() - Duration::hours(1));
+        let cases = [
+            (None, StatusCode::UNAUTHORIZED, Some("Bearer")),
+            (
+                Some("Basic Zm9vOmJhcg==".to_string()),
+                StatusCode::UNAUTHORIZED,
+                Some(
+                    r#"Bearer error="invalid_token", error_description="The access token is invalid""#,
+                ),
+            ),
+            (
+                Some("Bearer not.a.jwt".to_string()),
+                StatusCode::UNAUTHORIZED,
+                Some(
+                    r#"Bearer error="invalid_token", error_description="The access token is invalid""#,
+                ),
+            ),
+            (
+                token(Scope::Admin, an_hour_ago),
+                StatusCode::UNAUTHORIZED,
+                Some(
+                    r#"Bearer error="invalid_token", error_description="The access token expired""#,
+                ),
+            ),
+            (
+                token(Scope::Viewer, None),
+                StatusCode::FORBIDDEN,
+                Some(r#"Bearer error="insufficient_scope", scope="admin""#),
+            ),
+            (token(Scope::Admin, None), StatusCode::OK, None),
+        ];
+        for (authorization, status, challenge) in cases {
+            let mut request = Request::builder().uri("/admin-only");
+            if let Some(authorization) = &authorization {
+                request = request.header(AUTHORIZATION, authorization);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{:?}", authorization);
+            assert_eq!(
+                response
+                    .headers()
+                    .get(WWW_AUTHENTICATE)
+                    .map(|value| value.to_str().unwrap()),
+                challenge,
+                "{:?}",
+                authorization
+            );
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{routing::post, Extension, Json, Router};
+use chrono::Utc;
+
+use crate::auth::jkws::Scope;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError};
+
+pub fn invalidate_router() -> Router {
+    Router::new().route("/auth/invalidate-before", post(invalidate_before))
+}
+
+// Invalidate every token issued before now, forcing all users to log in again (OPERATOR ONLY)
+// Affects every merchant, so a merchant's admin can't call it.
+// The cutoff lives in memory; set TOKEN_MIN_IAT to the returned time to keep it after a restart.
+async fn invalidate_before(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+) -> Result<Json<ApiResponse<InvalidateTokensResponse>>, AppError> {
+    user.require_scope(Scope::Operator)?;
+
+    let now = Utc::now();
+    ctx.auth_service.invalidate_before(now);
+    let min_iat = ctx.auth_service.min_iat().unwrap_or(now);
+
+    // Opaque refresh tokens aren't JWTs, so revoke their rows instead
+    let revoked_refresh_tokens =
+        sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE revoked_at IS NULL")
+            .execute(&ctx.db)
+            .await?
+            .rows_affected();
+
+    eprintln!(
+        "Invalidated tokens issued before {}: requested by {}, {} refresh tokens revoked",
+        min_iat, user.claims.sub, revoked_refresh_tokens
+    );
+
+    Ok(Json(ApiResponse::success_with_message(
+        InvalidateTokensResponse {
+            min_iat,
+            revoked_refresh_tokens,
+        },
+        "All previously issued tokens are now invalid".to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jkws::Subject;
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn merchant_admins_cannot_invalidate_every_token() {
+        // Refused before the database is touched; a successful call would revoke
+        // every refresh token in the shared test database
+        let db = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let ctx = crate::http::test_context(db.clone(), db);
+        let app = invalidate_router().layer(Extension(ctx.clone()));
+        let token = ctx
+            .auth_service
+            .gen_access_token(
+                Subject::Service("merchant-admin".to_string()),
+                String::new(),
+                vec![Scope::Viewer, Scope::Manager, Scope::Admin],
+            )
+            .unwrap();
+
+        let request = Request::post("/auth/invalidate-before")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(ctx.auth_service.min_iat().is_none());
+    }
+}
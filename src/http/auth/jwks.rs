@@ -1,6 +1,24 @@
-use axum::{extract::Extension, routing::get, Json, Router};
+use axum::{
+    extract::Extension,
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use sha2::{Digest, Sha256};
 
-use crate::{auth::jkws::Jwks, http::types::AppError};
+use crate::{
+    auth::jkws::{Jwks, ACCESS_TOKEN_TTL_MINUTES},
+    http::types::AppError,
+};
+
+/// How long clients may cache the JWKS: a third of the shortest token lifetime, so a
+/// verifier holding a stale set refetches well before tokens from a new key expire
+const JWKS_MAX_AGE_SECS: i64 = ACCESS_TOKEN_TTL_MINUTES * 60 / 3;
+const _: () = assert!(JWKS_MAX_AGE_SECS < ACCESS_TOKEN_TTL_MINUTES * 60);
 
 /* #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Jwks {
@@ -21,14 +39,125 @@ pub fn jwks_router() -> Router {
     Router::new().route("/jwks", get(get_jwks))
 }
 
+// Cacheable for JWKS_MAX_AGE_SECS; the ETag changes whenever a key is rotated in or out,
+// so `If-None-Match` revalidation gets a 304 only while the key set is unchanged
 async fn get_jwks(
     Extension(context): Extension<crate::http::ApiContext>,
-) -> Result<Json<Jwks>, AppError> {
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     // Generate JWKS from the AuthService's public key
     let jwks = context
         .auth_service
         .generate_jwks()
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
-    Ok(Json(jwks))
+    let etag = jwks_etag(&jwks);
+    let cache_headers = [
+        (
+            CACHE_CONTROL,
+            HeaderValue::from_str(&format!("public, max-age={}", JWKS_MAX_AGE_SECS))
+                .map_err(|_| AppError::InternalServerError)?,
+        ),
+        (
+            ETAG,
+            HeaderValue::from_str(&etag).map_err(|_| AppError::InternalServerError)?,
+        ),
+    ];
+
+    if etag_matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((cache_headers, Json(jwks)).into_response())
+}
+
+/// Strong ETag over the set of `kid`s; a kid is a thumbprint of its key, so the same
+/// kids always mean the same keys
+fn jwks_etag(jwks: &Jwks) -> String {
+    let mut kids: Vec<&str> = jwks.keys.iter().map(|key| key.kid.as_str()).collect();
+    kids.sort_unstable();
+    let digest = Sha256::digest(kids.join(",").as_bytes());
+    format!("\"{:x}\"", digest)
+}
+
+/// Whether `If-None-Match` names `etag` (weak or strong) or is `*`
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    #[test]
+    fn etag_follows_the_set_of_kids() {
+        let service = {
+            let keys = crate::misc::keypair::generate_rsa_key_pair().unwrap();
+            crate::auth::jkws::AuthService::new(keys.private_key, 24, keys.public_key).unwrap()
+        };
+        let before = service.generate_jwks().unwrap();
+        assert_eq!(jwks_etag(&before), jwks_etag(&before.clone()));
+
+        service.rotate_keys().unwrap();
+        let mut after = service.generate_jwks().unwrap();
+        assert_ne!(jwks_etag(&after), jwks_etag(&before));
+
+        // Order of the keys doesn't matter, only which ones are served
+        let etag = jwks_etag(&after);
+        after.keys.reverse();
+        assert_eq!(jwks_etag(&after), etag);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, "\"stale\"".parse().unwrap());
+        assert!(!etag_matches(&headers, &etag));
+        headers.insert(
+            IF_NONE_MATCH,
+            format!("\"stale\", W/{}", etag).parse().unwrap(),
+        );
+        assert!(etag_matches(&headers, &etag));
+        headers.insert(IF_NONE_MATCH, "*".parse().unwrap());
+        assert!(etag_matches(&headers, &etag));
+    }
+
+    #[tokio::test]
+    async fn unchanged_jwks_revalidates_with_304() {
+        // JWKS never touches the database
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let app = crate::http::test_router(db);
+        let get = |if_none_match: Option<&str>| {
+            let mut request = Request::builder().uri("/api/v1/jwks");
+            if let Some(etag) = if_none_match {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CACHE_CONTROL],
+            format!("public, max-age={}", JWKS_MAX_AGE_SECS)
+        );
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+
+        let response = app.clone().oneshot(get(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let response = app.oneshot(get(Some("\"stale\""))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,130 @@
+use axum::{
+    extract::Query,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+
+use crate::auth::jkws::Scope;
+use crate::auth::keys::KeySummary;
+use crate::http::{
+    auth::AuthenticatedUser, types::*, ApiContext, AppError, PageLimits, Pagination,
+};
+
+/// Key sets stay small (one signing key plus 30 days of retired ones)
+const KEY_PAGE_LIMITS: PageLimits = PageLimits {
+    default_limit: 50,
+    max_limit: 100,
+};
+
+pub fn keys_router() -> Router {
+    Router::new()
+        .route("/auth/keys", get(list_keys))
+        .route("/auth/keys/rotate", post(rotate_keys))
+}
+
+// List signing and verification keys (OPERATOR ONLY)
+// Shows which key signs new tokens and when older ones were retired; no key material.
+async fn list_keys(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListKeysParams>,
+) -> AppResult<ListResponse<SigningKeyInfo>> {
+    user.require_scope(Scope::Operator)?;
+
+    let Pagination { limit, offset } =
+        Pagination::new(params.limit, params.offset, KEY_PAGE_LIMITS);
+    Ok(Json(key_page(ctx.auth_service.list_keys(), limit, offset)))
+}
+
+fn key_page(keys: Vec<KeySummary>, limit: i32, offset: i32) -> ListResponse<SigningKeyInfo> {
+    let total = keys.len() as i64;
+    let items = keys
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|key| SigningKeyInfo {
+            kid: key.kid,
+            alg: key.alg.to_string(),
+            created_at: key.created_at,
+            retired_at: key.retired_at,
+            current: key.current,
+        })
+        .collect();
+    ListResponse {
+        items,
+        total,
+        limit,
+        offset,
+    }
+}
+
+// Rotate the JWT signing key for every merchant (OPERATOR ONLY)
+// The previous public key stays in the JWKS so tokens it signed keep verifying.
+async fn rotate_keys(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+) -> Result<Json<ApiResponse<RotateKeysResponse>>, AppError> {
+    user.require_scope(Scope::Operator)?;
+    eprintln!("Rotating JWT signing key: requested by {}", user.claims.sub);
+
+    // RSA key generation is CPU-bound, keep it off the async workers
+    let auth_service = ctx.auth_service.clone();
+    let kid = tokio::task::spawn_blocking(move || auth_service.rotate_keys())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let verification_kids = ctx
+        .auth_service
+        .generate_jwks()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .keys
+        .into_iter()
+        .map(|k| k.kid)
+        .collect();
+
+    Ok(Json(ApiResponse::success_with_message(
+        RotateKeysResponse {
+            kid,
+            verification_kids,
+        },
+        "Signing key rotated".to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jkws::AuthService;
+    use crate::misc::keypair::generate_rsa_key_pair;
+
+    #[test]
+    fn key_listing_pages_without_leaking_private_keys() {
+        let keys = generate_rsa_key_pair().unwrap();
+        let service = AuthService::new(keys.private_key.clone(), 24, keys.public_key).unwrap();
+        service.rotate_keys().unwrap();
+        service.rotate_keys().unwrap();
+
+        let page = key_page(service.list_keys(), 2, 0);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.items.len(), 2);
+        assert!(page.items[0].current);
+        assert!(!page.items[1].current);
+        let rest = key_page(service.list_keys(), 2, 2);
+        assert_eq!(rest.items.len(), 1);
+
+        // Not the original key, nor the ones rotation generated, in any form
+        let json = serde_json::to_string(&page).unwrap() + &serde_json::to_string(&rest).unwrap();
+        let debug = format!("{:?}", service.list_keys());
+        let private_body: String = keys
+            .private_key
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        for output in [&json, &debug] {
+            assert!(!output.contains("PRIVATE"), "{}", output);
+            assert!(!output.contains(&private_body[..64]));
+        }
+        assert!(!json.contains("public_key"));
+    }
+}
@@ -1,36 +1,99 @@
-use axum::{extract::Extension, routing::post, Json, Router};
-use sha2::{Digest, Sha256};
+use axum::{
+    extract::{Extension, Query},
+    http::{header::SET_COOKIE, HeaderMap, HeaderValue},
+    routing::post,
+    Json, Router,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::auth::jkws::Scope;
-use crate::http::types::{ApiResponse, AppError, LoginRequest, LoginResponseData, User, UserInfo};
-use crate::http::ApiContext;
+use crate::auth::jkws::{
+    parse_scopes, Scope, Subject, ACCESS_TOKEN_TTL_MINUTES, REFRESH_TOKEN_TTL_DAYS,
+};
+use crate::auth::refresh::RefreshError;
+use crate::http::auth::cookie;
+use crate::http::types::{
+    ApiResponse, AppError, LoginParams, LoginRequest, LoginResponseData, RefreshRequest,
+    RefreshResponseData, User, UserInfo,
+};
+use crate::http::{v1, ApiContext, JsonBody};
+use crate::misc::password_hash::{hash_password, verify_password, PasswordHashParams};
 use crate::misc::validator;
 
 pub fn login_router() -> Router {
-    Router::new().route("/login", post(handle_login))
+    Router::new()
+        .route("/login", post(handle_login))
+        .route("/refresh", post(handle_refresh))
 }
 
 // Helper function to determine scopes based on user role
 fn determine_user_scopes(role: &str) -> Vec<Scope> {
     match role {
-        "admin" => vec![Scope::Viewer, Scope::Manager, Scope::Admin],
+        "admin" => vec![
+            Scope::Viewer,
+            Scope::Manager,
+            Scope::Admin,
+            Scope::Backoffice,
+        ],
+        "backoffice" => vec![Scope::Viewer, Scope::Backoffice],
         "manager" => vec![Scope::Viewer, Scope::Manager],
         "viewer" => vec![Scope::Viewer],
         _ => vec![Scope::Viewer], // Default to viewer (read-only)
     }
 }
 
+// Scopes for a login token: the role's scopes, narrowed to the requested ones if any.
+// Requested scopes the role doesn't grant are dropped, so a request can never widen them.
+fn login_scopes(role: &str, requested: Option<&[Scope]>) -> Result<Vec<Scope>, AppError> {
+    let entitled = determine_user_scopes(role);
+    let Some(requested) = requested else {
+        return Ok(entitled);
+    };
+    let scopes: Vec<Scope> = entitled
+        .into_iter()
+        .filter(|scope| requested.contains(scope))
+        .collect();
+    if scopes.is_empty() {
+        return Err(AppError::Forbidden);
+    }
+    Ok(scopes)
+}
+
+// Store a fresh hash of a password that just verified
+async fn rehash_password(
+    db: &PgPool,
+    user_id: Uuid,
+    password: &str,
+    params: &PasswordHashParams,
+) -> Result<(), AppError> {
+    let password_hash = hash_password(password, params)?;
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
 // Login handler
+// With ?cookie=true the access token is also set as an HttpOnly cookie for browser clients.
 async fn handle_login(
     Extension(context): Extension<ApiContext>,
-    Json(login_req): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<LoginResponseData>>, AppError> {
+    Query(params): Query<LoginParams>,
+    JsonBody(login_req): JsonBody<LoginRequest>,
+) -> Result<(HeaderMap, Json<ApiResponse<LoginResponseData>>), AppError> {
     // Validate email format
     validator::validate_email(&login_req.email)?;
+    let requested_scopes = login_req
+        .scope
+        .as_deref()
+        .map(parse_scopes)
+        .transpose()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
 
     // Query the database for the user
     let user = sqlx::query_as::<_, User>(
-        "SELECT id, merchant_id, email, password_hash, display_name, role, is_active 
+        "SELECT id, email, password_hash, display_name, role, is_active 
          FROM users WHERE email = $1",
     )
     .bind(&login_req.email)
@@ -46,25 +109,55 @@ async fn handle_login(
 
     // Verify password
     let password_hash = user.password_hash.ok_or(AppError::InvalidCredentials)?;
-    
-    // Hash the provided password
-    let mut hasher = Sha256::new();
-    hasher.update(login_req.password.as_bytes());
-    let provided_hash = format!("{:x}", hasher.finalize());
-
-    if password_hash != provided_hash {
+    if !verify_password(&login_req.password, &password_hash) {
         println!("Password mismatch for user: {}", user.email);
         return Err(AppError::InvalidCredentials);
     }
 
-    // Determine scopes based on user's role
-    let scopes = determine_user_scopes(&user.role);
+    // Upgrade legacy SHA-256 hashes and hashes made with an older Argon2 cost
+    // while we have the plaintext; a failed upgrade doesn't block the login
+    let hash_params = context.config.password_hash_params();
+    if hash_params.needs_rehash(&password_hash) {
+        if let Err(e) =
+            rehash_password(&context.db, user.id, &login_req.password, &hash_params).await
+        {
+            eprintln!("Failed to re-hash password for user {}: {}", user.id, e);
+        }
+    }
 
-    // Generate JWT token pair (access + refresh)
-    let (access_token, refresh_token) = context
+    // Scopes come from the user's stored role, never from the caller
+    let scopes = login_scopes(&user.role, requested_scopes.as_deref())?;
+
+    // Generate token pair (access JWT + refresh token per the configured strategy)
+    let access_token = context
         .auth_service
-        .gen_token_pair(user.id, user.email.clone(), scopes)
+        .gen_access_token(user.id, user.email.clone(), scopes)
         .map_err(|_| AppError::InternalServerError)?;
+    let refresh_token = context
+        .auth_service
+        .issue_refresh_token(&context.db, user.id, user.email.clone())
+        .await
+        .map_err(|e| match e {
+            RefreshError::Database(e) => e.into(),
+            _ => AppError::InternalServerError,
+        })?;
+
+    let mut headers = HeaderMap::new();
+    if params.cookie.unwrap_or(false) {
+        headers.append(
+            SET_COOKIE,
+            access_token_cookie(&context.config.access_token_cookie, &access_token)?,
+        );
+    }
+    let refresh_token = if params.refresh_cookie.unwrap_or(false) {
+        headers.append(
+            SET_COOKIE,
+            refresh_token_cookie(&context.config.refresh_token_cookie, &refresh_token)?,
+        );
+        None
+    } else {
+        Some(refresh_token)
+    };
 
     let response_data = LoginResponseData {
         access_token,
@@ -77,8 +170,243 @@ async fn handle_login(
         },
     };
 
-    Ok(Json(ApiResponse::success_with_message(
-        response_data,
-        "Login successful".to_string(),
-    )))
+    Ok((
+        headers,
+        Json(ApiResponse::success_with_message(
+            response_data,
+            "Login successful".to_string(),
+        )),
+    ))
+}
+
+// Set-Cookie value for the access token; it expires together with the token
+fn access_token_cookie(name: &str, access_token: &str) -> Result<HeaderValue, AppError> {
+    HeaderValue::from_str(&format!(
+        "{}={}; Path=/; Max-Age={}; Secure; HttpOnly; SameSite=Lax",
+        name,
+        access_token,
+        ACCESS_TOKEN_TTL_MINUTES * 60
+    ))
+    .map_err(|_| AppError::InternalServerError)
+}
+
+// Set-Cookie value for the refresh token. Page scripts can't read it, and the browser
+// only sends it to the refresh endpoint, never cross-site. It expires with the token.
+fn refresh_token_cookie(name: &str, refresh_token: &str) -> Result<HeaderValue, AppError> {
+    HeaderValue::from_str(&format!(
+        "{}={}; Path={}/refresh; Max-Age={}; Secure; HttpOnly; SameSite=Strict",
+        name,
+        refresh_token,
+        v1::PREFIX,
+        REFRESH_TOKEN_TTL_DAYS * 24 * 60 * 60
+    ))
+    .map_err(|_| AppError::InternalServerError)
+}
+
+// Refresh handler: exchange a refresh token for a new access token
+// The token comes from the body, or from the refresh token cookie when the body has none.
+async fn handle_refresh(
+    Extension(context): Extension<ApiContext>,
+    headers: HeaderMap,
+    body: Option<JsonBody<RefreshRequest>>,
+) -> Result<Json<ApiResponse<RefreshResponseData>>, AppError> {
+    let refresh_token = body
+        .and_then(|JsonBody(refresh_req)| refresh_req.refresh_token)
+        .or_else(|| cookie(&headers, &context.config.refresh_token_cookie).map(str::to_string))
+        .ok_or(AppError::Unauthorized)?;
+
+    let owner = context
+        .auth_service
+        .refresh_token_owner(&context.db, &refresh_token)
+        .await
+        .map_err(|e| match e {
+            RefreshError::Database(e) => e.into(),
+            _ => AppError::Unauthorized,
+        })?;
+    // Service clients re-run the client-credentials grant instead of refreshing
+    let Subject::User(user_id) = owner.subject else {
+        return Err(AppError::Unauthorized);
+    };
+
+    // Scopes come from the user's current role, not from the old token.
+    // Tokens issued before the user's last "log out everywhere" no longer count.
+    let role: String = sqlx::query_scalar(
+        r#"
+        SELECT role FROM users
+        WHERE id = $1 AND is_active = true
+          AND (tokens_valid_after IS NULL OR tokens_valid_after <= $2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(owner.issued_at)
+    .fetch_optional(&context.db)
+    .await?
+    .ok_or(AppError::Unauthorized)?;
+
+    let access_token = context
+        .auth_service
+        .gen_access_token(user_id, owner.email, determine_user_scopes(&role))
+        .map_err(|_| AppError::InternalServerError)?;
+
+    Ok(Json(ApiResponse::success(RefreshResponseData {
+        access_token,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, insert_user, test_db};
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[test]
+    fn login_scopes_never_exceed_the_role() {
+        // No request: everything the role grants
+        assert_eq!(
+            login_scopes("manager", None).unwrap(),
+            vec![Scope::Viewer, Scope::Manager]
+        );
+
+        // Asking for more than the role grants only yields the overlap
+        let requested = [Scope::Viewer, Scope::Admin];
+        assert_eq!(
+            login_scopes("viewer", Some(&requested)).unwrap(),
+            vec![Scope::Viewer]
+        );
+        assert_eq!(
+            login_scopes("admin", Some(&[Scope::Viewer])).unwrap(),
+            vec![Scope::Viewer]
+        );
+
+        // Unknown roles are viewers, whatever they ask for
+        assert_eq!(
+            login_scopes("superuser", Some(&requested)).unwrap(),
+            vec![Scope::Viewer]
+        );
+
+        // Nothing in common is refused rather than issued with no scopes
+        assert!(matches!(
+            login_scopes("viewer", Some(&[Scope::Admin])),
+            Err(AppError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn access_token_cookie_is_http_only_and_expires_with_the_token() {
+        let cookie = access_token_cookie("access_token", "a.b.c").unwrap();
+        assert_eq!(
+            cookie.to_str().unwrap(),
+            "access_token=a.b.c; Path=/; Max-Age=900; Secure; HttpOnly; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn refresh_token_cookie_is_only_sent_to_the_refresh_endpoint() {
+        let cookie = refresh_token_cookie("refresh_token", "opaque").unwrap();
+        assert_eq!(
+            cookie.to_str().unwrap(),
+            "refresh_token=opaque; Path=/api/v1/refresh; Max-Age=2592000; Secure; HttpOnly; SameSite=Strict"
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_token_cookie_round_trips_through_refresh() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let email = format!("refresh-cookie-{}@test-shop.com", Uuid::new_v4());
+        sqlx::query("INSERT INTO users (merchant_id, email, password_hash) VALUES ($1, $2, $3)")
+            .bind(merchant_id)
+            .bind(&email)
+            .bind(hash_password("password", &PasswordHashParams::default())?)
+            .execute(&db)
+            .await?;
+        let app = crate::http::test_router(db.clone());
+
+        let body = serde_json::json!({ "email": email, "password": "password" });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/login?refresh_cookie=true")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let set_cookie = response.headers()[SET_COOKIE].to_str()?.to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let login: serde_json::Value = serde_json::from_slice(&bytes)?;
+        // The refresh token is only in the cookie, never in the body
+        assert!(login["data"].get("refresh_token").is_none());
+        let token_cookie = set_cookie.split(';').next().unwrap();
+        assert!(token_cookie.starts_with("refresh_token="));
+
+        let refresh = |cookie: Option<&str>| {
+            let mut request = Request::builder().method("POST").uri("/api/v1/refresh");
+            if let Some(cookie) = cookie {
+                request = request.header("Cookie", cookie);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let response = app.clone().oneshot(refresh(Some(token_cookie))).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(refresh(None)).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn login_upgrades_legacy_password_hashes() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let (user_id, email) = insert_user(&db, merchant_id, "viewer").await?;
+        let app = crate::http::test_router(db.clone());
+
+        let login = |password: &str| {
+            let body = serde_json::json!({ "email": email, "password": password });
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/login")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let stored_hash = || {
+            sqlx::query_scalar::<_, String>("SELECT password_hash FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_one(&db)
+        };
+
+        // A wrong password leaves the legacy hash alone
+        let response = app.clone().oneshot(login("wrong")).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!stored_hash().await?.starts_with("$argon2"));
+
+        let response = app.clone().oneshot(login("password")).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let upgraded = stored_hash().await?;
+        assert!(!PasswordHashParams::default().needs_rehash(&upgraded));
+
+        // The upgraded hash keeps working and isn't re-hashed again
+        let response = app.clone().oneshot(login("password")).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(stored_hash().await?, upgraded);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
 }
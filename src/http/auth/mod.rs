@@ -1,11 +1,31 @@
+mod api_keys;
+mod decode;
+mod extractor;
+mod invalidate;
 mod jwks;
+mod keys;
 mod login;
+mod sessions;
+mod token;
+mod token_info;
 mod users;
+mod verify;
 
 use axum::Router;
 
+pub use extractor::AuthenticatedUser;
+pub(crate) use extractor::{access_token, cookie, VerifiedAccessToken};
+
 pub fn auth_router() -> Router {
     Router::new()
+        .merge(api_keys::api_keys_router())
+        .merge(decode::decode_router())
+        .merge(invalidate::invalidate_router())
         .merge(jwks::jwks_router())
+        .merge(keys::keys_router())
         .merge(login::login_router())
+        .merge(sessions::sessions_router())
+        .merge(token::token_router())
+        .merge(token_info::token_info_router())
+        .merge(verify::verify_router())
 }
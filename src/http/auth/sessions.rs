@@ -0,0 +1,192 @@
+use axum::{routing::delete, Extension, Json, Router};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError};
+
+pub fn sessions_router() -> Router {
+    Router::new().route("/auth/sessions", delete(revoke_sessions))
+}
+
+// "Log out everywhere": revoke every refresh token the caller holds
+// Access tokens already handed out stay valid until they expire (ACCESS_TOKEN_TTL_MINUTES).
+async fn revoke_sessions(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+) -> Result<Json<ApiResponse<RevokeSessionsResponse>>, AppError> {
+    let user_id = user.user_id()?;
+
+    let mut tx = ctx.db.begin().await?;
+    let revoked = revoke_user_sessions(&mut tx, user_id).await?;
+    tx.commit().await?;
+
+    eprintln!(
+        "Revoked sessions: user_id={}, revoked_sessions={}",
+        user_id, revoked.revoked_sessions
+    );
+
+    Ok(Json(ApiResponse::success_with_message(
+        revoked,
+        "All sessions have been logged out".to_string(),
+    )))
+}
+
+/// Reject the user's outstanding refresh tokens
+///
+/// JWT refresh tokens are stateless, so they're cut off by `tokens_valid_after`
+/// (checked on refresh); opaque ones are also revoked row by row so they can be counted.
+/// The cutoff is truncated to the second like a JWT `iat`, so logging in again
+/// within the same second still gives a usable token.
+async fn revoke_user_sessions(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+) -> Result<RevokeSessionsResponse, AppError> {
+    let tokens_valid_after: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
+        r#"
+        UPDATE users SET tokens_valid_after = date_trunc('second', NOW())
+        WHERE id = $1
+        RETURNING tokens_valid_after
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::Unauthorized)?;
+
+    let revoked_sessions = sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    Ok(RevokeSessionsResponse {
+        tokens_valid_after,
+        revoked_sessions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, insert_user, send, test_db, test_db_with};
+    use axum::http::StatusCode;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn logging_out_everywhere_rejects_earlier_refresh_tokens() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let (_, email) = insert_user(&db, merchant_id, "viewer").await?;
+        let app = crate::http::test_router(db.clone());
+
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, laptop) = send(&app, "POST", "/api/v1/login", None, Some(login.clone())).await;
+        let (_, phone) = send(&app, "POST", "/api/v1/login", None, Some(login.clone())).await;
+        let refresh = |session: &serde_json::Value| {
+            let refresh_token = &session["data"]["refresh_token"];
+            serde_json::json!({ "refresh_token": refresh_token })
+        };
+
+        let (status, _) = send(&app, "POST", "/api/v1/refresh", None, Some(refresh(&phone))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // The cutoff has one-second resolution, like the tokens' `iat`
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let (status, body) = send(
+            &app,
+            "DELETE",
+            "/api/v1/auth/sessions",
+            laptop["data"]["access_token"].as_str(),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // Default JWT refresh tokens have no rows to count
+        assert_eq!(body["data"]["revoked_sessions"], 0);
+
+        for session in [&laptop, &phone] {
+            let (status, _) = send(
+                &app,
+                "POST",
+                "/api/v1/refresh",
+                None,
+                Some(refresh(session)),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        let (status, _) = send(&app, "DELETE", "/api/v1/auth/sessions", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Logging in again straight away, even within the same second, works
+        let (_, tablet) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/v1/refresh",
+            None,
+            Some(refresh(&tablet)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_live_opaque_tokens_count_as_revoked_sessions() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (merchant_id, email) VALUES ($1, 'viewer@test-shop.com') RETURNING id",
+        )
+        .bind(merchant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (user_id, token_hash, expires_at, revoked_at)
+            VALUES
+                ($1, 'live-1', NOW() + INTERVAL '1 day', NULL),
+                ($1, 'live-2', NOW() + INTERVAL '1 day', NULL),
+                ($1, 'expired', NOW() - INTERVAL '1 day', NULL),
+                ($1, 'revoked', NOW() + INTERVAL '1 day', NOW())
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let revoked = revoke_user_sessions(&mut tx, user_id).await?;
+        assert_eq!(revoked.revoked_sessions, 2);
+        assert_eq!(
+            revoke_user_sessions(&mut tx, user_id)
+                .await?
+                .revoked_sessions,
+            0
+        );
+        assert!(matches!(
+            revoke_user_sessions(&mut tx, Uuid::new_v4()).await,
+            Err(AppError::Unauthorized)
+        ));
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
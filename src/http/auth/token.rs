@@ -0,0 +1,130 @@
+use axum::{
+    extract::{rejection::FormRejection, Extension, Form},
+    routing::post,
+    Json, Router,
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+
+use crate::auth::jkws::{parse_scopes, scopes_to_string, Scope, Subject, ACCESS_TOKEN_TTL_MINUTES};
+use crate::http::types::{AppError, ClientCredentialsRequest, ClientTokenResponse};
+use crate::http::{ApiContext, AppResult};
+
+pub fn token_router() -> Router {
+    Router::new().route("/auth/token", post(issue_client_token))
+}
+
+// OAuth client-credentials grant for service-to-service calls
+// Issues a short-lived access token with the client's scopes; there is no refresh token.
+async fn issue_client_token(
+    Extension(ctx): Extension<ApiContext>,
+    form: Result<Form<ClientCredentialsRequest>, FormRejection>,
+) -> AppResult<ClientTokenResponse> {
+    let Form(req) = form.map_err(|e| AppError::Validation(e.body_text()))?;
+    if req.grant_type != "client_credentials" {
+        return Err(AppError::Validation(format!(
+            "Unsupported grant_type '{}': expected 'client_credentials'",
+            req.grant_type
+        )));
+    }
+
+    let mut conn = ctx.db.acquire().await?;
+    let scopes = authenticate_client(&mut conn, &req.client_id, &req.client_secret).await?;
+    let scope = scopes_to_string(&scopes);
+
+    // Service tokens carry no email
+    let access_token = ctx
+        .auth_service
+        .gen_access_token(
+            Subject::Service(req.client_id.clone()),
+            String::new(),
+            scopes,
+        )
+        .map_err(|_| AppError::InternalServerError)?;
+
+    eprintln!(
+        "Issued client token: client_id={}, scope={}",
+        req.client_id, scope
+    );
+
+    Ok(Json(ClientTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: ACCESS_TOKEN_TTL_MINUTES * 60,
+        scope,
+    }))
+}
+
+/// Scopes of the active client matching `client_id`/`client_secret`
+///
+/// Unknown clients, wrong secrets and disabled clients are all the same 401.
+async fn authenticate_client(
+    conn: &mut PgConnection,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<Vec<Scope>, AppError> {
+    let mut hasher = Sha256::new();
+    hasher.update(client_secret.as_bytes());
+    let secret_hash = format!("{:x}", hasher.finalize());
+
+    let scopes: String = sqlx::query_scalar(
+        r#"
+        SELECT scopes FROM clients
+        WHERE client_id = $1 AND client_secret_hash = $2 AND is_active = true
+        "#,
+    )
+    .bind(client_id)
+    .bind(&secret_hash)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::Unauthorized)?;
+
+    parse_scopes(&scopes).map_err(|e| {
+        eprintln!("Client {} has invalid scopes configured: {}", client_id, e);
+        AppError::InternalServerError
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db_with;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn only_active_clients_with_the_right_secret_authenticate() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO clients (client_id, client_secret_hash, scopes, is_active)
+            VALUES
+                ('test-worker', encode(sha256('s3cret'::bytea), 'hex'), 'viewer backoffice', true),
+                ('test-retired', encode(sha256('s3cret'::bytea), 'hex'), 'viewer', false)
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let scopes = authenticate_client(&mut tx, "test-worker", "s3cret").await?;
+        assert_eq!(scopes, vec![Scope::Viewer, Scope::Backoffice]);
+
+        for (client_id, secret) in [
+            ("test-worker", "wrong"),
+            ("test-retired", "s3cret"),
+            ("test-unknown", "s3cret"),
+        ] {
+            assert!(matches!(
+                authenticate_client(&mut tx, client_id, secret).await,
+                Err(AppError::Unauthorized)
+            ));
+        }
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+
+use crate::auth::jkws::AccessTokenClaims;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::types::{AppError, TokenInfo};
+
+pub fn token_info_router() -> Router {
+    Router::new().route("/auth/token-info", get(get_token_info))
+}
+
+// When the caller's access token expires, so UIs can warn before the session ends
+async fn get_token_info(user: AuthenticatedUser) -> Result<Json<TokenInfo>, AppError> {
+    if user.api_key.is_some() {
+        return Err(AppError::Validation(
+            "API keys don't expire; they last until revoked".to_string(),
+        ));
+    }
+    Ok(Json(token_info(&user.claims, Utc::now())?))
+}
+
+/// Remaining lifetime of a verified token as of `now`
+///
+/// Verification allows some clock-skew leeway past `exp`; a token in that window has
+/// no lifetime left to report, so it is rejected here like any other expired token.
+fn token_info(claims: &AccessTokenClaims, now: DateTime<Utc>) -> Result<TokenInfo, AppError> {
+    let expires_at =
+        DateTime::from_timestamp(claims.exp as i64, 0).ok_or(AppError::Unauthorized)?;
+    let expires_in_seconds = (expires_at - now).num_seconds();
+    if expires_in_seconds <= 0 {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok(TokenInfo {
+        expires_at,
+        expires_in_seconds,
+        scopes: claims.scope.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jkws::{Scope, TokenType};
+    use chrono::Duration;
+
+    fn claims(exp: DateTime<Utc>) -> AccessTokenClaims {
+        AccessTokenClaims {
+            sub: "user".to_string(),
+            email: "viewer@test-shop.com".to_string(),
+            exp: exp.timestamp() as usize,
+            iat: (exp - Duration::minutes(15)).timestamp() as usize,
+            nbf: None,
+            iss: "exchange_api".to_string(),
+            token_type: TokenType::Access,
+            scope: vec![Scope::Viewer],
+        }
+    }
+
+    #[test]
+    fn reports_the_remaining_lifetime() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let info = token_info(&claims(now + Duration::seconds(120)), now).unwrap();
+
+        assert_eq!(info.expires_at, now + Duration::seconds(120));
+        assert_eq!(info.expires_in_seconds, 120);
+        assert_eq!(info.scopes, vec![Scope::Viewer]);
+    }
+
+    #[test]
+    fn expired_tokens_are_unauthorized_even_within_the_leeway() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for exp in [now, now - Duration::seconds(30)] {
+            assert!(matches!(
+                token_info(&claims(exp), now),
+                Err(AppError::Unauthorized)
+            ));
+        }
+    }
+}
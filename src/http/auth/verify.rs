@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use axum::{extract::Extension, routing::post, Json, Router};
+
+use crate::auth::jkws::AuthService;
+use crate::http::types::{AppError, TokenVerification, VerifyBatchRequest};
+use crate::http::{ApiContext, JsonBody};
+
+/// Upper bound on tokens per batch so one request can't monopolise the blocking pool
+const MAX_BATCH_SIZE: usize = 100;
+
+pub fn verify_router() -> Router {
+    Router::new().route("/auth/verify-batch", post(verify_batch))
+}
+
+// Verify many access tokens in one call (for the edge gateway)
+// Results are returned in the same order as the submitted tokens.
+async fn verify_batch(
+    Extension(context): Extension<ApiContext>,
+    JsonBody(request): JsonBody<VerifyBatchRequest>,
+) -> Result<Json<Vec<TokenVerification>>, AppError> {
+    if request.tokens.len() > MAX_BATCH_SIZE {
+        return Err(AppError::Validation(format!(
+            "At most {} tokens can be verified per batch",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    eprintln!("Verifying token batch: count={}", request.tokens.len());
+    let results = verify_tokens(context.auth_service.clone(), request.tokens).await;
+    Ok(Json(results))
+}
+
+/// Verify each token on the blocking pool so RSA work runs in parallel
+async fn verify_tokens(
+    auth_service: Arc<AuthService>,
+    tokens: Vec<String>,
+) -> Vec<TokenVerification> {
+    let verifications = tokens.into_iter().map(|token| {
+        let auth_service = auth_service.clone();
+        tokio::task::spawn_blocking(move || match auth_service.verify_access_token(&token) {
+            Ok(claims) => TokenVerification {
+                valid: true,
+                sub: Some(claims.sub),
+                scope: Some(claims.scope),
+                error: None,
+            },
+            Err(kind) => TokenVerification {
+                valid: false,
+                sub: None,
+                scope: None,
+                error: Some(format!("{:?}", kind)),
+            },
+        })
+    });
+
+    futures::future::join_all(verifications)
+        .await
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|e| TokenVerification {
+                valid: false,
+                sub: None,
+                scope: None,
+                error: Some(e.to_string()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jkws::Scope;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn batch_reports_each_token_in_order() {
+        let keys = crate::misc::keypair::generate_rsa_key_pair().unwrap();
+        let auth_service =
+            Arc::new(AuthService::new(keys.private_key, 24, keys.public_key).unwrap());
+        let user_id = Uuid::new_v4();
+        let token = auth_service
+            .gen_access_token(
+                user_id,
+                "viewer@test-shop.com".to_string(),
+                vec![Scope::Viewer],
+            )
+            .unwrap();
+
+        let results = verify_tokens(auth_service, vec![token, "not-a-jwt".to_string()]).await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].valid);
+        assert_eq!(results[0].sub, Some(user_id.to_string()));
+        assert_eq!(results[0].scope, Some(vec![Scope::Viewer]));
+        assert!(!results[1].valid);
+        assert!(results[1].error.is_some());
+    }
+}
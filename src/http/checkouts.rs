@@ -0,0 +1,164 @@
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::{
+    auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, Filters, PagedResource,
+    Pagination, ReadPool,
+};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use sqlx::PgConnection;
+
+pub fn checkouts_router() -> Router {
+    Router::new().route("/checkouts", get(list_checkouts))
+}
+
+// Abandoned checkouts, newest first, with the order each one converted into (if any)
+async fn list_checkouts(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ListCheckoutsParams>,
+) -> AppResult<ListResponse<Checkout>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+    eprintln!(
+        "Listing checkouts: merchant_id={}, converted={:?}, limit={:?}, offset={:?}",
+        params.merchant_id, params.converted, params.limit, params.offset
+    );
+
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Checkouts),
+    );
+
+    let mut conn = read_db.acquire().await?;
+    let (checkouts, total) = find_checkouts(&mut conn, &params, limit, offset).await?;
+
+    Ok(Json(ListResponse {
+        items: checkouts,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+/// One page of checkouts matching the `converted` filter, plus the total match count
+///
+/// The order is looked up through `orders.shopify_checkout_id`, so a checkout
+/// shows as converted as soon as its order is synced, whichever synced first.
+async fn find_checkouts(
+    conn: &mut PgConnection,
+    params: &ListCheckoutsParams,
+    limit: i32,
+    offset: i32,
+) -> Result<(Vec<Checkout>, i64), AppError> {
+    const FROM: &str = r#"
+        FROM checkouts c
+        LEFT JOIN LATERAL (
+            SELECT o.id
+            FROM orders o
+            WHERE o.merchant_id = c.merchant_id
+                AND o.shopify_checkout_id = c.shopify_checkout_id
+                AND o.deleted_at IS NULL
+            ORDER BY o.id
+            LIMIT 1
+        ) o ON TRUE
+    "#;
+    let filters = Filters::new()
+        .and("c.merchant_id = {}", [params.merchant_id.into()])
+        .and_opt("(o.id IS NOT NULL) = {}", params.converted);
+
+    let total: i64 = filters
+        .count(FROM)
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let checkouts = filters
+        .page(
+            &format!(
+                r#"
+        SELECT
+            c.id,
+            c.shopify_checkout_id,
+            c.email,
+            c.abandoned_checkout_url,
+            c.currency,
+            c.total_price,
+            c.shopify_created_at,
+            c.completed_at,
+            o.id AS order_id
+        {}
+        "#,
+                FROM
+            ),
+            "c.shopify_created_at DESC, c.shopify_checkout_id DESC",
+            "c.id DESC",
+            limit,
+            offset,
+        )
+        .build_query_as::<Checkout>()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok((checkouts, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, test_db_with};
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn find_checkouts_links_converted_orders() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO checkouts (merchant_id, shopify_checkout_id, shopify_created_at)
+            VALUES ($1, 1, NOW() - INTERVAL '2 days'), ($1, 2, NOW() - INTERVAL '1 day')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await?;
+        let order_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO orders (merchant_id, shopify_order_id, shopify_checkout_id)
+            VALUES ($1, 10, 1)
+            RETURNING id
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let params = |converted| ListCheckoutsParams {
+            merchant_id,
+            converted,
+            limit: None,
+            offset: None,
+        };
+        let cases = [
+            (None, vec![(2, None), (1, Some(order_id))]),
+            (Some(true), vec![(1, Some(order_id))]),
+            (Some(false), vec![(2, None)]),
+        ];
+        for (converted, expected) in cases {
+            let (checkouts, total) = find_checkouts(&mut tx, &params(converted), 50, 0).await?;
+            let found: Vec<(i64, Option<i64>)> = checkouts
+                .iter()
+                .map(|c| (c.shopify_checkout_id, c.order_id))
+                .collect();
+            assert_eq!(found, expected, "{:?}", converted);
+            assert_eq!(total, expected.len() as i64);
+        }
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
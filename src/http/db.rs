@@ -0,0 +1,247 @@
+use std::ops::{Deref, DerefMut};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderName},
+};
+use sqlx::{pool::PoolConnection, postgres::PgConnectOptions, PgConnection, PgPool, Postgres};
+
+use crate::http::{ApiContext, AppError};
+
+/// SQLSTATE `query_canceled`, which is what a statement timeout raises
+pub(crate) const QUERY_CANCELED: &str = "57014";
+
+/// Request header that sends `ReadPool` reads to the primary instead of the replica
+pub const X_READ_PRIMARY: HeaderName = HeaderName::from_static("x-read-primary");
+
+/// `options` with a server-side `statement_timeout` of `timeout_ms`; 0 leaves it unset
+///
+/// Set as a startup parameter, so it holds for every statement on every pooled
+/// connection, in or out of a transaction, without a `SET` per acquire. A runaway
+/// query is cancelled by Postgres and surfaces as a `503 Query timeout`.
+pub fn with_statement_timeout(options: PgConnectOptions, timeout_ms: u64) -> PgConnectOptions {
+    if timeout_ms == 0 {
+        return options;
+    }
+    options.options([("statement_timeout", timeout_ms.to_string())])
+}
+
+/// One pooled connection for the whole request
+///
+/// Handlers that run several queries take this instead of querying `ctx.db`
+/// directly, which acquires a connection per query. The connection goes back
+/// to the pool when the handler returns. `conn.begin()` starts a transaction on it.
+pub struct DbConn(pub PoolConnection<Postgres>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for DbConn
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ctx = parts
+            .extensions
+            .get::<ApiContext>()
+            .ok_or(AppError::InternalServerError)?;
+
+        Ok(Self(ctx.db.acquire().await?))
+    }
+}
+
+impl Deref for DbConn {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.0
+    }
+}
+
+impl DerefMut for DbConn {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        &mut self.0
+    }
+}
+
+/// The pool read-only handlers query: the read replica, or the primary without one
+///
+/// Falls back to the primary when no `READ_REPLICA_URL` is configured. The replica
+/// may lag behind, so a read straight after a write can miss it; clients that need
+/// to see their own write send `X-Read-Primary: true` to read from the primary.
+/// Never write through this pool.
+pub struct ReadPool(pub PgPool);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ReadPool
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ctx = parts
+            .extensions
+            .get::<ApiContext>()
+            .ok_or(AppError::InternalServerError)?;
+
+        if wants_primary(parts)? {
+            Ok(Self(ctx.db.clone()))
+        } else {
+            Ok(Self(ctx.read_db.clone()))
+        }
+    }
+}
+
+fn wants_primary(parts: &Parts) -> Result<bool, AppError> {
+    match parts.headers.get(X_READ_PRIMARY) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().to_ascii_lowercase().parse().ok())
+            .ok_or_else(|| {
+                AppError::Validation("X-Read-Primary must be 'true' or 'false'".to_string())
+            }),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::database_url;
+    use axum::{
+        body::Body,
+        http::{header::RETRY_AFTER, Request, StatusCode},
+        response::IntoResponse,
+    };
+    use sqlx::postgres::PgPoolOptions;
+    use std::str::FromStr;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn slow_statements_are_cancelled() -> anyhow::Result<()> {
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        let options = PgConnectOptions::from_str(&database_url)?;
+        let db = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(with_statement_timeout(options, 100))
+            .await?;
+
+        let timeout: String = sqlx::query_scalar("SHOW statement_timeout")
+            .fetch_one(&db)
+            .await?;
+        assert_eq!(timeout, "100ms");
+
+        let err = sqlx::query("SELECT pg_sleep(5)")
+            .execute(&db)
+            .await
+            .unwrap_err();
+        let response = AppError::from(err).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Inside a transaction too, and the connection is still usable afterwards
+        let mut tx = db.begin().await?;
+        let err = sqlx::query("SELECT pg_sleep(5)")
+            .execute(&mut *tx)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_database_error().and_then(|e| e.code()).as_deref(),
+            Some(QUERY_CANCELED)
+        );
+        tx.rollback().await?;
+        let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&db).await?;
+        assert_eq!(one, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exhausted_pool_answers_503_with_retry_after() -> anyhow::Result<()> {
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        let db = PgPoolOptions::new()
+            .min_connections(1)
+            .max_connections(1)
+            .acquire_timeout(std::time::Duration::from_millis(200))
+            .connect(&database_url)
+            .await?;
+        let app = crate::http::test_router(db.clone());
+
+        // Hold the only connection so the login query can't get one
+        let held = db.acquire().await?;
+        let response = app
+            .oneshot(
+                Request::post("/api/v1/login")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"email":"someone@example.com","password":"password"}"#,
+                    ))?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "2");
+        drop(held);
+
+        let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&db).await?;
+        assert_eq!(one, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_pool_uses_the_replica_unless_primary_is_asked_for() -> anyhow::Result<()> {
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        // Both pools reach the same database; application_name tells them apart
+        let options = PgConnectOptions::from_str(&database_url)?;
+        let primary = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.clone().application_name("primary"))
+            .await?;
+        let replica = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.application_name("replica"))
+            .await?;
+        let app = axum::Router::new()
+            .route(
+                "/",
+                axum::routing::get(|ReadPool(db): ReadPool| async move {
+                    sqlx::query_scalar::<_, String>("SELECT current_setting('application_name')")
+                        .fetch_one(&db)
+                        .await
+                        .unwrap()
+                }),
+            )
+            .layer(axum::Extension(crate::http::test_context(primary, replica)));
+
+        for (header, expected) in [
+            (None, "replica"),
+            (Some("false"), "replica"),
+            (Some("true"), "primary"),
+        ] {
+            let mut request = Request::get("/");
+            if let Some(value) = header {
+                request = request.header(X_READ_PRIMARY, value);
+            }
+            let response = app.clone().oneshot(request.body(Body::empty())?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            assert_eq!(body, expected, "{:?}", header);
+        }
+
+        let request = Request::get("/")
+            .header(X_READ_PRIMARY, "please")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Optional `{data, meta}` envelope around successful responses.
+//!
+//! Clients opt in per request with `X-Response-Envelope: true` or `?envelope=true`;
+//! without either, bodies are returned as-is. Errors keep their `{error, message}`
+//! shape (or problem details, see `problem`) either way so error handling doesn't
+//! depend on the toggle.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Query, Request},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderName, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::http::AppError;
+
+const X_RESPONSE_ENVELOPE: HeaderName = HeaderName::from_static("x-response-envelope");
+pub(crate) const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+#[derive(Deserialize)]
+struct EnvelopeParams {
+    envelope: Option<bool>,
+}
+
+/// Wrap successful JSON responses in `{data, meta}` when the client asks for it
+///
+/// `meta.request_id` echoes the caller's `X-Request-Id`, or is a fresh UUID.
+pub async fn response_envelope(request: Request, next: Next) -> Response {
+    let enveloped = match wants_envelope(&request) {
+        Ok(enveloped) => enveloped,
+        Err(e) => return e.into_response(),
+    };
+    if !enveloped {
+        return next.run(request).await;
+    }
+
+    let request_id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let response = next.run(request).await;
+    if !response.status().is_success() || !is_json(response.headers()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Ok(data) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    let body = json!({
+        "data": data,
+        "meta": { "request_id": request_id },
+    });
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+/// The query parameter wins over the header when both are given
+fn wants_envelope(request: &Request) -> Result<bool, AppError> {
+    let invalid = || AppError::Validation("envelope must be 'true' or 'false'".to_string());
+
+    let Query(params) =
+        Query::<EnvelopeParams>::try_from_uri(request.uri()).map_err(|_| invalid())?;
+    if let Some(envelope) = params.envelope {
+        return Ok(envelope);
+    }
+    match request.headers().get(X_RESPONSE_ENVELOPE) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().to_ascii_lowercase().parse().ok())
+            .ok_or_else(invalid),
+        None => Ok(false),
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/thing", get(|| async { Json(json!({ "id": 1 })) }))
+            .route(
+                "/missing",
+                get(|| async { AppError::NotFound.into_response() }),
+            )
+            .route("/empty", get(|| async { StatusCode::NO_CONTENT }))
+            .layer(middleware::from_fn(response_envelope))
+    }
+
+    async fn send(uri: &str, headers: &[(&str, &str)]) -> (StatusCode, Value) {
+        let mut request = axum::http::Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn successful_json_is_wrapped_only_on_request() {
+        let (_, body) = send("/thing", &[]).await;
+        assert_eq!(body, json!({ "id": 1 }));
+
+        let (_, body) = send("/thing", &[("X-Response-Envelope", "true")]).await;
+        assert_eq!(body["data"], json!({ "id": 1 }));
+        assert!(body["meta"]["request_id"].is_string());
+
+        let (_, body) = send("/thing?envelope=true", &[("X-Request-Id", "abc-123")]).await;
+        assert_eq!(body["data"], json!({ "id": 1 }));
+        assert_eq!(body["meta"]["request_id"], "abc-123");
+
+        // The query parameter overrides the header
+        let (_, body) = send("/thing?envelope=false", &[("X-Response-Envelope", "true")]).await;
+        assert_eq!(body, json!({ "id": 1 }));
+    }
+
+    #[tokio::test]
+    async fn errors_and_empty_bodies_keep_their_shape() {
+        let (status, body) = send("/missing?envelope=true", &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Resource not found");
+        assert!(body.get("data").is_none());
+
+        let (status, body) = send("/empty?envelope=true", &[]).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(body, Value::Null);
+
+        let (status, body) = send("/thing", &[("X-Response-Envelope", "yes")]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Validation error");
+    }
+}
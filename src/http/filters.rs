@@ -0,0 +1,210 @@
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+/// A value bound by a list filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bind<'a> {
+    Uuid(Uuid),
+    Text(&'a str),
+    Bool(bool),
+    BigInt(i64),
+}
+
+impl From<Uuid> for Bind<'_> {
+    fn from(value: Uuid) -> Self {
+        Bind::Uuid(value)
+    }
+}
+
+impl<'a> From<&'a str> for Bind<'a> {
+    fn from(value: &'a str) -> Self {
+        Bind::Text(value)
+    }
+}
+
+impl From<bool> for Bind<'_> {
+    fn from(value: bool) -> Self {
+        Bind::Bool(value)
+    }
+}
+
+impl From<i64> for Bind<'_> {
+    fn from(value: i64) -> Self {
+        Bind::BigInt(value)
+    }
+}
+
+/// The `WHERE` clause of a list query, with the values each condition binds
+///
+/// A condition is an SQL fragment with `{}` wherever a value goes, e.g.
+/// `"financial_status = {}"`. Placeholders are only numbered when a query is
+/// built, so adding a filter is one call and `$n` can't drift from the bindings.
+/// The same filters build both the `COUNT(*)` and the page query.
+#[derive(Debug, Default)]
+pub struct Filters<'a> {
+    conditions: Vec<(&'static str, Vec<Bind<'a>>)>,
+}
+
+impl<'a> Filters<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a condition that always applies, binding `binds` to its `{}`s in order
+    pub fn and(mut self, sql: &'static str, binds: impl IntoIterator<Item = Bind<'a>>) -> Self {
+        let binds: Vec<_> = binds.into_iter().collect();
+        debug_assert_eq!(
+            sql.matches("{}").count(),
+            binds.len(),
+            "placeholders in {:?}",
+            sql
+        );
+        self.conditions.push((sql, binds));
+        self
+    }
+
+    /// Add a single-value condition only when the filter was given
+    pub fn and_opt<T: Into<Bind<'a>>>(self, sql: &'static str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.and(sql, [value.into()]),
+            None => self,
+        }
+    }
+
+    /// `SELECT COUNT(*) {from} WHERE ...`
+    pub fn count(&self, from: &str) -> QueryBuilder<'a, Postgres> {
+        self.query(&format!("SELECT COUNT(*) {}", from))
+    }
+
+    /// `{select} WHERE ... ORDER BY {order_by}, {tiebreaker} LIMIT .. OFFSET ..`
+    ///
+    /// `tiebreaker` must be unique (the primary key), so rows that sort equal by
+    /// `order_by` still come back in the same order on every call. Without it an
+    /// offset page can repeat rows from the previous page and skip others.
+    pub fn page(
+        &self,
+        select: &str,
+        order_by: &str,
+        tiebreaker: &str,
+        limit: i32,
+        offset: i32,
+    ) -> QueryBuilder<'a, Postgres> {
+        let mut builder = self.query(select);
+        builder.push(" ORDER BY ").push(order_by);
+        builder.push(", ").push(tiebreaker);
+        builder.push(" LIMIT ").push_bind(limit);
+        builder.push(" OFFSET ").push_bind(offset);
+        builder
+    }
+
+    /// `{prefix} WHERE ...`, for statements other than a paged `SELECT`
+    pub fn query(&self, prefix: &str) -> QueryBuilder<'a, Postgres> {
+        let mut builder = QueryBuilder::new(prefix);
+        for (i, (sql, binds)) in self.conditions.iter().enumerate() {
+            builder.push(if i == 0 { " WHERE " } else { " AND " });
+            let mut binds = binds.iter();
+            let mut parts = sql.split("{}");
+            if let Some(first) = parts.next() {
+                builder.push(first);
+            }
+            for part in parts {
+                match binds.next() {
+                    Some(Bind::Uuid(value)) => builder.push_bind(*value),
+                    Some(Bind::Text(value)) => builder.push_bind(*value),
+                    Some(Bind::Bool(value)) => builder.push_bind(*value),
+                    Some(Bind::BigInt(value)) => builder.push_bind(*value),
+                    None => &mut builder,
+                };
+                builder.push(part);
+            }
+        }
+        builder
+    }
+
+    /// Every bound value, in placeholder order
+    #[cfg(test)]
+    pub fn binds(&self) -> Vec<Bind<'a>> {
+        self.conditions
+            .iter()
+            .flat_map(|(_, binds)| binds.iter().copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders(financial_status: Option<&str>, cancelled: Option<bool>) -> Filters<'_> {
+        Filters::new()
+            .and("merchant_id = {}", [Uuid::nil().into()])
+            .and("deleted_at IS NULL", [])
+            .and_opt("financial_status = {}", financial_status)
+            .and_opt("(cancelled_at IS NOT NULL) = {}", cancelled)
+    }
+
+    #[test]
+    fn placeholders_are_numbered_in_bind_order() {
+        let cases = [
+            (
+                orders(None, None),
+                " WHERE merchant_id = $1 AND deleted_at IS NULL",
+                vec![Bind::Uuid(Uuid::nil())],
+            ),
+            (
+                orders(Some("paid"), None),
+                " WHERE merchant_id = $1 AND deleted_at IS NULL AND financial_status = $2",
+                vec![Bind::Uuid(Uuid::nil()), Bind::Text("paid")],
+            ),
+            (
+                orders(None, Some(true)),
+                " WHERE merchant_id = $1 AND deleted_at IS NULL AND (cancelled_at IS NOT NULL) = $2",
+                vec![Bind::Uuid(Uuid::nil()), Bind::Bool(true)],
+            ),
+            (
+                orders(Some("paid"), Some(false)),
+                " WHERE merchant_id = $1 AND deleted_at IS NULL AND financial_status = $2 AND (cancelled_at IS NOT NULL) = $3",
+                vec![Bind::Uuid(Uuid::nil()), Bind::Text("paid"), Bind::Bool(false)],
+            ),
+        ];
+        for (filters, where_clause, binds) in cases {
+            assert_eq!(
+                filters.count("FROM orders").sql(),
+                format!("SELECT COUNT(*) FROM orders{}", where_clause)
+            );
+            assert_eq!(filters.binds(), binds);
+        }
+    }
+
+    #[test]
+    fn page_binds_limit_and_offset_after_the_filters() {
+        let filters = orders(Some("paid"), None).and("lower(name) = lower({})", ["#1001".into()]);
+        assert_eq!(
+            filters
+                .page(
+                    "SELECT id FROM orders",
+                    "created_at DESC",
+                    "id DESC",
+                    50,
+                    100
+                )
+                .sql(),
+            "SELECT id FROM orders WHERE merchant_id = $1 AND deleted_at IS NULL \
+             AND financial_status = $2 AND lower(name) = lower($3) \
+             ORDER BY created_at DESC, id DESC LIMIT $4 OFFSET $5"
+        );
+        assert_eq!(filters.binds().len(), 3);
+    }
+
+    #[test]
+    fn a_condition_can_bind_several_values() {
+        let filters = Filters::new().and(
+            "price BETWEEN {} AND {}",
+            [Bind::BigInt(1), Bind::BigInt(10)],
+        );
+        assert_eq!(
+            filters.query("SELECT id FROM variants").sql(),
+            "SELECT id FROM variants WHERE price BETWEEN $1 AND $2"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::auth::jkws::Scope;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult};
+use axum::{routing::get, Extension, Json, Router};
+use sqlx::migrate::Migrator;
+use sqlx::PgConnection;
+
+/// The migrations this binary was built with
+static EMBEDDED_MIGRATIONS: Migrator = sqlx::migrate!("./sql/migrations");
+
+pub fn health_router() -> Router {
+    Router::new().route("/health/detailed", get(detailed_health))
+}
+
+// Applied vs embedded schema version (ADMIN ONLY, it reveals internal versions)
+// Catches a new binary running against a database nobody has migrated yet.
+async fn detailed_health(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+) -> AppResult<DetailedHealth> {
+    user.require_scope(Scope::Admin)?;
+
+    let mut conn = ctx.db.acquire().await?;
+    let health = migration_status(&mut conn, &EMBEDDED_MIGRATIONS).await?;
+
+    if health.schema_outdated {
+        eprintln!(
+            "Schema outdated: applied={:?}, pending={:?}",
+            health.applied_migration_version, health.pending_migrations
+        );
+    }
+
+    Ok(Json(health))
+}
+
+/// Compare the migrations recorded in `_sqlx_migrations` with the ones `migrator` knows about
+async fn migration_status(
+    conn: &mut PgConnection,
+    migrator: &Migrator,
+) -> Result<DetailedHealth, AppError> {
+    // A database that was never migrated has no bookkeeping table at all
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&mut *conn)
+            .await
+            .or_else(|e| match &e {
+                sqlx::Error::Database(db) if db.code().as_deref() == Some("42P01") => {
+                    Ok(Vec::new())
+                }
+                _ => Err(e),
+            })?;
+
+    let embedded: Vec<i64> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| m.version)
+        .collect();
+    let pending_migrations: Vec<i64> = embedded
+        .iter()
+        .copied()
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    Ok(DetailedHealth {
+        applied_migration_version: applied.iter().copied().max(),
+        latest_migration_version: embedded.iter().copied().max(),
+        schema_outdated: !pending_migrations.is_empty(),
+        pending_migrations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::database_url;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn unapplied_embedded_migration_marks_schema_outdated() -> anyhow::Result<()> {
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        let db = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url)
+            .await?;
+        EMBEDDED_MIGRATIONS.run(&db).await?;
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let health = migration_status(&mut tx, &EMBEDDED_MIGRATIONS).await?;
+        assert!(!health.schema_outdated);
+        assert_eq!(
+            health.applied_migration_version,
+            health.latest_migration_version
+        );
+
+        // Pretend the newest migration hasn't been run against this database yet
+        let latest = health.latest_migration_version.unwrap();
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+            .bind(latest)
+            .execute(&mut *tx)
+            .await?;
+        let health = migration_status(&mut tx, &EMBEDDED_MIGRATIONS).await?;
+        assert!(health.schema_outdated);
+        assert_eq!(health.pending_migrations, vec![latest]);
+        assert!(health.applied_migration_version < Some(latest));
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
@@ -1,48 +1,71 @@
-use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::auth::jkws::Scope;
+use crate::http::changes::{ChangeFeed, ChangesResponse, IdType};
+use crate::http::merchants::{ensure_own_merchant, shopify_client_for};
+use crate::http::prefer;
+use crate::http::{
+    auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, Filters, JsonBody,
+    PagedResource, Pagination, ReadPool,
+};
+use crate::shopify::ShopifyClient;
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
-    routing::get,
+    middleware,
+    routing::{get, post},
     Extension, Json, Router,
 };
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Most levels accepted by one `POST /inventory-levels/bulk`
+const MAX_BULK_LEVELS: usize = 1000;
 
 pub fn inventory_router() -> Router {
     Router::new()
         .route("/inventory", get(list_items).post(create_item))
+        .route("/inventory/changes", get(item_changes))
         .route(
             "/inventory/:id",
             get(get_item).put(update_item).delete(delete_item),
         )
+        // Prefer: return=minimal on create/update; added before /adjust, which isn't covered
+        .route_layer(middleware::from_fn(prefer::return_preference))
+        .route("/inventory/:id/adjust", post(adjust_item))
+        .route("/inventory-levels/bulk", post(bulk_upsert_levels))
 }
 
 async fn list_items(
     Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
     Query(params): Query<ListInventoryItemsParams>,
-) -> AppResult<InventoryItemListResponse> {
+) -> AppResult<ListResponse<InventoryItem>> {
     eprintln!(
         "Listing inventory items: merchant_id={}, limit={:?}, offset={:?}",
         params.merchant_id, params.limit, params.offset
     );
 
-    let limit = params.limit.unwrap_or(50).min(100);
-    let offset = params.offset.unwrap_or(0);
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Inventory),
+    );
+
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and("deleted_at IS NULL", []);
 
     // Get total count
-    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
-        r#"
-        SELECT COUNT(*) as count
-        FROM inventory_items 
-        WHERE merchant_id = $1
-        "#,
-    )
-    .bind(params.merchant_id)
-    .fetch_one(&ctx.db)
-    .await?
-    .unwrap_or(0);
+    let total: i64 = filters
+        .count("FROM inventory_items")
+        .build_query_scalar()
+        .fetch_one(&read_db)
+        .await?;
 
     // Get inventory items
-    let items = sqlx::query_as::<_, InventoryItem>(
-        r#"
+    let items = filters
+        .page(
+            r#"
         SELECT 
             id,
             merchant_id,
@@ -51,20 +74,19 @@ async fn list_items(
             created_at,
             updated_at
         FROM inventory_items
-        WHERE merchant_id = $1
-        ORDER BY updated_at DESC
-        LIMIT $2 OFFSET $3
         "#,
-    )
-    .bind(params.merchant_id)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(&ctx.db)
-    .await?;
+            "updated_at DESC",
+            "id DESC",
+            limit,
+            offset,
+        )
+        .build_query_as::<InventoryItem>()
+        .fetch_all(&read_db)
+        .await?;
 
     eprintln!("Found {} inventory items (total: {})", items.len(), total);
 
-    Ok(Json(InventoryItemListResponse {
+    Ok(Json(ListResponse {
         items,
         total,
         limit,
@@ -72,8 +94,35 @@ async fn list_items(
     }))
 }
 
-async fn get_item(
+/// Inventory items changed since `since` or `cursor`, deleted ones included; see `http::changes`
+async fn item_changes(
+    user: AuthenticatedUser,
     Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ChangesParams>,
+) -> AppResult<ChangesResponse<InventoryItem>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+
+    let feed = ChangeFeed {
+        table: "inventory_items",
+        columns: "id, merchant_id, shopify_inventory_item_id, shopify_variant_id, \
+            created_at, updated_at",
+        id_type: IdType::Uuid,
+    };
+    let mut conn = read_db.acquire().await?;
+    let changes = feed
+        .page(
+            &mut conn,
+            &params,
+            ctx.config.page_limits(PagedResource::Inventory),
+            ctx.config.change_feed_lag(),
+        )
+        .await?;
+    Ok(Json(changes))
+}
+
+async fn get_item(
+    ReadPool(read_db): ReadPool,
     Path(id): Path<uuid::Uuid>,
 ) -> AppResult<InventoryItem> {
     eprintln!("Getting inventory item: id={}", id);
@@ -88,11 +137,11 @@ async fn get_item(
             created_at,
             updated_at
         FROM inventory_items
-        WHERE id = $1
+        WHERE id = $1 AND deleted_at IS NULL
         "#,
     )
     .bind(id)
-    .fetch_optional(&ctx.db)
+    .fetch_optional(&read_db)
     .await?
     .ok_or(AppError::NotFound)?;
 
@@ -101,7 +150,7 @@ async fn get_item(
 
 async fn create_item(
     Extension(ctx): Extension<ApiContext>,
-    Json(payload): Json<CreateInventoryItemRequest>,
+    JsonBody(payload): JsonBody<CreateInventoryItemRequest>,
 ) -> AppResult<InventoryItem> {
     eprintln!(
         "Creating inventory item: merchant_id={}, shopify_inventory_item_id={}, shopify_variant_id={:?}",
@@ -123,7 +172,7 @@ async fn create_item(
     eprintln!("Inventory item existence check: {:?}", existing);
 
     if existing.is_some() {
-        return Err(AppError::Validation(
+        return Err(AppError::Conflict(
             "Inventory item already exists".to_string(),
         ));
     }
@@ -152,7 +201,7 @@ async fn create_item(
 async fn update_item(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<uuid::Uuid>,
-    Json(payload): Json<UpdateInventoryItemRequest>,
+    JsonBody(payload): JsonBody<UpdateInventoryItemRequest>,
 ) -> AppResult<InventoryItem> {
     eprintln!(
         "Updating inventory item: id={}, shopify_variant_id={:?}",
@@ -163,9 +212,8 @@ async fn update_item(
         r#"
         UPDATE inventory_items 
         SET 
-            shopify_variant_id = COALESCE($2, shopify_variant_id),
-            updated_at = NOW()
-        WHERE id = $1
+            shopify_variant_id = COALESCE($2, shopify_variant_id)
+        WHERE id = $1 AND deleted_at IS NULL
         RETURNING id, merchant_id, shopify_inventory_item_id, shopify_variant_id,
                   created_at, updated_at
         "#,
@@ -180,6 +228,235 @@ async fn update_item(
     Ok(Json(item))
 }
 
+// Adjust stock at a location and write it back to Shopify (BACKOFFICE ONLY)
+async fn adjust_item(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<uuid::Uuid>,
+    JsonBody(payload): JsonBody<AdjustInventoryRequest>,
+) -> AppResult<InventoryLevel> {
+    user.require_scope(Scope::Backoffice)?;
+    eprintln!(
+        "Adjusting inventory item: id={}, location_id={}, delta={}",
+        id, payload.location_id, payload.delta
+    );
+
+    let item = sqlx::query_as::<_, InventoryItem>(
+        r#"
+        SELECT 
+            id,
+            merchant_id,
+            shopify_inventory_item_id,
+            shopify_variant_id,
+            created_at,
+            updated_at
+        FROM inventory_items
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    ensure_own_merchant(&ctx, &user, item.merchant_id).await?;
+    let client = shopify_client_for(&ctx, item.merchant_id).await?;
+
+    let level = apply_adjustment(&ctx.db, &client, &item, payload.location_id, payload.delta).await?;
+
+    eprintln!(
+        "Inventory adjusted successfully: id={}, available={}",
+        id, level.available
+    );
+    Ok(Json(level))
+}
+
+/// Apply `delta` to the local level, then to Shopify, in one transaction
+///
+/// The local row stays locked while Shopify is called, and is rolled back if
+/// Shopify rejects the adjustment so the two never drift apart.
+async fn apply_adjustment(
+    db: &PgPool,
+    client: &ShopifyClient,
+    item: &InventoryItem,
+    location_id: i64,
+    delta: i32,
+) -> Result<InventoryLevel, AppError> {
+    let mut tx = db.begin().await?;
+
+    let level = sqlx::query_as::<_, InventoryLevel>(
+        r#"
+        INSERT INTO inventory_levels (
+            merchant_id, inventory_item_id, shopify_location_id, available
+        )
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (inventory_item_id, shopify_location_id) DO UPDATE
+        SET available = inventory_levels.available + EXCLUDED.available
+        RETURNING id, merchant_id, inventory_item_id, shopify_location_id, available,
+                  created_at, updated_at
+        "#,
+    )
+    .bind(item.merchant_id)
+    .bind(item.id)
+    .bind(location_id)
+    .bind(delta)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Dropping `tx` on error rolls the local change back
+    let shopify_level = client
+        .adjust_inventory(item.shopify_inventory_item_id.into(), location_id, delta)
+        .await?;
+
+    // Shopify's count is authoritative, e.g. if it changed there since our last sync
+    let level = match shopify_level.available {
+        Some(available) if available != level.available => {
+            sqlx::query_as::<_, InventoryLevel>(
+                r#"
+                UPDATE inventory_levels
+                SET available = $2
+                WHERE id = $1
+                RETURNING id, merchant_id, inventory_item_id, shopify_location_id, available,
+                          created_at, updated_at
+                "#,
+            )
+            .bind(level.id)
+            .bind(available)
+            .fetch_one(&mut *tx)
+            .await?
+        }
+        _ => level,
+    };
+    let location_name = sqlx::query_scalar(
+        "SELECT name FROM locations WHERE merchant_id = $1 AND shopify_location_id = $2",
+    )
+    .bind(level.merchant_id)
+    .bind(level.shopify_location_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten();
+
+    tx.commit().await?;
+    Ok(InventoryLevel {
+        location_name,
+        ..level
+    })
+}
+
+// Set many stock levels at once, e.g. after pulling them from Shopify (BACKOFFICE ONLY)
+// Local only: nothing is written back to Shopify.
+async fn bulk_upsert_levels(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    JsonBody(payload): JsonBody<BulkUpsertLevelsRequest>,
+) -> AppResult<BulkUpsertLevelsResponse> {
+    user.require_scope(Scope::Backoffice)?;
+    ensure_own_merchant(&ctx, &user, payload.merchant_id).await?;
+    validate_levels(&payload.levels)?;
+    eprintln!(
+        "Bulk upserting inventory levels: merchant_id={}, count={}",
+        payload.merchant_id,
+        payload.levels.len()
+    );
+
+    let mut tx = ctx.db.begin().await?;
+    let counts = upsert_levels(&mut tx, payload.merchant_id, &payload.levels).await?;
+    tx.commit().await?;
+
+    eprintln!(
+        "Inventory levels upserted: inserted={}, updated={}, unchanged={}",
+        counts.inserted, counts.updated, counts.unchanged
+    );
+    Ok(Json(counts))
+}
+
+/// Batch size, quantities, and no item/location pair twice (one row can't be upserted twice)
+fn validate_levels(levels: &[LevelUpsert]) -> Result<(), AppError> {
+    if levels.is_empty() || levels.len() > MAX_BULK_LEVELS {
+        return Err(AppError::Validation(format!(
+            "levels must hold between 1 and {} entries",
+            MAX_BULK_LEVELS
+        )));
+    }
+    let mut seen = HashSet::new();
+    for (i, level) in levels.iter().enumerate() {
+        if level.available < 0 {
+            return Err(AppError::Validation(format!(
+                "levels[{}].available must not be negative",
+                i
+            )));
+        }
+        if !seen.insert((level.inventory_item_id, level.location_id)) {
+            return Err(AppError::Validation(format!(
+                "levels[{}] repeats inventory item {} at location {}",
+                i, level.inventory_item_id, level.location_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Upsert every level in one statement; fails without writing if any item isn't the merchant's
+async fn upsert_levels(
+    conn: &mut PgConnection,
+    merchant_id: Uuid,
+    levels: &[LevelUpsert],
+) -> Result<BulkUpsertLevelsResponse, AppError> {
+    let item_ids: Vec<Uuid> = levels.iter().map(|l| l.inventory_item_id).collect();
+    let location_ids: Vec<i64> = levels.iter().map(|l| l.location_id).collect();
+    let available: Vec<i32> = levels.iter().map(|l| l.available).collect();
+
+    let unknown: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT l.item_id
+        FROM UNNEST($2::uuid[]) AS l(item_id)
+        LEFT JOIN inventory_items i
+            ON i.id = l.item_id AND i.merchant_id = $1 AND i.deleted_at IS NULL
+        WHERE i.id IS NULL
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&item_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    if !unknown.is_empty() {
+        let unknown: Vec<String> = unknown.iter().map(Uuid::to_string).collect();
+        return Err(AppError::Validation(format!(
+            "Unknown inventory items: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    // A row only comes back when it was inserted or its quantity changed
+    let written: Vec<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO inventory_levels (
+            merchant_id, inventory_item_id, shopify_location_id, available
+        )
+        SELECT $1, l.item_id, l.location_id, l.available
+        FROM UNNEST($2::uuid[], $3::bigint[], $4::int[]) AS l(item_id, location_id, available)
+        ON CONFLICT (inventory_item_id, shopify_location_id) DO UPDATE
+        SET available = EXCLUDED.available
+        WHERE inventory_levels.available <> EXCLUDED.available
+        RETURNING xmax = 0
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&item_ids)
+    .bind(&location_ids)
+    .bind(&available)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let inserted = written.iter().filter(|&&inserted| inserted).count() as u64;
+    let updated = written.len() as u64 - inserted;
+    Ok(BulkUpsertLevelsResponse {
+        inserted,
+        updated,
+        unchanged: levels.len() as u64 - inserted - updated,
+    })
+}
+
 async fn delete_item(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<uuid::Uuid>,
@@ -203,3 +480,234 @@ async fn delete_item(
     eprintln!("Inventory item deleted successfully: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, test_db};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn failed_shopify_adjustment_rolls_back_local_level() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+
+        let merchant_id = insert_merchant(&db).await?;
+        let item = sqlx::query_as::<_, InventoryItem>(
+            r#"
+            INSERT INTO inventory_items (merchant_id, shopify_inventory_item_id)
+            VALUES ($1, 808950810)
+            RETURNING id, merchant_id, shopify_inventory_item_id, shopify_variant_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await?;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/admin/api/2024-10/inventory_levels/adjust.json"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+        let result = apply_adjustment(&db, &client, &item, 655441491, 5).await;
+        assert!(matches!(result, Err(AppError::Shopify(_))));
+
+        let levels: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM inventory_levels WHERE inventory_item_id = $1",
+        )
+        .bind(item.id)
+        .fetch_one(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_eq!(levels, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adjusted_levels_carry_the_location_name() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+
+        let merchant_id = insert_merchant(&db).await?;
+        let item = sqlx::query_as::<_, InventoryItem>(
+            r#"
+            INSERT INTO inventory_items (merchant_id, shopify_inventory_item_id)
+            VALUES ($1, 808950810)
+            RETURNING id, merchant_id, shopify_inventory_item_id, shopify_variant_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await?;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/admin/api/2024-10/inventory_levels/adjust.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "inventory_level": {
+                    "inventory_item_id": 808950810,
+                    "location_id": 655441491,
+                    "available": 5,
+                }
+            })))
+            .mount(&server)
+            .await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+
+        // Not synced yet, so there is no name to show
+        let level = apply_adjustment(&db, &client, &item, 655441491, 5).await?;
+        assert_eq!(level.location_name, None);
+
+        sqlx::query(
+            r#"
+            INSERT INTO locations (merchant_id, shopify_location_id, name)
+            VALUES ($1, 655441491, 'Warehouse')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let level = apply_adjustment(&db, &client, &item, 655441491, 0).await;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        let level = level?;
+        assert_eq!(level.location_name.as_deref(), Some("Warehouse"));
+        assert_eq!(level.available, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_levels_are_validated_before_touching_the_database() {
+        let level = |location_id: i64, available: i32| LevelUpsert {
+            inventory_item_id: Uuid::nil(),
+            location_id,
+            available,
+        };
+
+        assert!(validate_levels(&[level(1, 0), level(2, 5)]).is_ok());
+        for (levels, message) in [
+            (vec![], "levels must hold between 1 and 1000 entries"),
+            (
+                vec![level(1, 3), level(2, -1)],
+                "levels[1].available must not be negative",
+            ),
+            (
+                vec![level(1, 3), level(1, 4)],
+                "levels[1] repeats inventory item",
+            ),
+        ] {
+            match validate_levels(&levels) {
+                Err(AppError::Validation(m)) => assert!(m.starts_with(message), "{}", m),
+                other => panic!("expected a validation error, got {:?}", other.err()),
+            }
+        }
+        let too_many: Vec<_> = (0..=MAX_BULK_LEVELS as i64).map(|i| level(i, 1)).collect();
+        assert!(validate_levels(&too_many).is_err());
+    }
+
+    #[tokio::test]
+    async fn bulk_upsert_counts_inserted_updated_and_unchanged_levels() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        let item_ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            INSERT INTO inventory_items (merchant_id, shopify_inventory_item_id)
+            SELECT $1, UNNEST(ARRAY[1, 2]::bigint[])
+            RETURNING id
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let level = |item: usize, location_id: i64, available: i32| LevelUpsert {
+            inventory_item_id: item_ids[item],
+            location_id,
+            available,
+        };
+
+        let counts =
+            upsert_levels(&mut tx, merchant_id, &[level(0, 10, 5), level(1, 10, 7)]).await?;
+        assert_eq!(
+            counts,
+            BulkUpsertLevelsResponse {
+                inserted: 2,
+                updated: 0,
+                unchanged: 0
+            }
+        );
+
+        let counts = upsert_levels(
+            &mut tx,
+            merchant_id,
+            &[level(0, 10, 9), level(1, 10, 7), level(1, 20, 0)],
+        )
+        .await?;
+        assert_eq!(
+            counts,
+            BulkUpsertLevelsResponse {
+                inserted: 1,
+                updated: 1,
+                unchanged: 1
+            }
+        );
+        let available: i32 = sqlx::query_scalar(
+            "SELECT available FROM inventory_levels WHERE inventory_item_id = $1",
+        )
+        .bind(item_ids[0])
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(available, 9);
+
+        // Another merchant's (or a made-up) item rejects the whole batch
+        let stranger = LevelUpsert {
+            inventory_item_id: Uuid::new_v4(),
+            location_id: 10,
+            available: 1,
+        };
+        let result = upsert_levels(&mut tx, merchant_id, &[level(0, 10, 1), stranger]).await;
+        assert!(
+            matches!(result, Err(AppError::Validation(m)) if m.starts_with("Unknown inventory items"))
+        );
+        let available: i32 = sqlx::query_scalar(
+            "SELECT available FROM inventory_levels WHERE inventory_item_id = $1",
+        )
+        .bind(item_ids[0])
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(available, 9);
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
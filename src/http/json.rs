@@ -0,0 +1,214 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Query, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_path_to_error::Error as PathError;
+
+use crate::http::AppError;
+
+/// `Json` extractor for request bodies that rejects with our `AppError` shape
+///
+/// Axum's own `Json` answers bad bodies with a plain-text 422 containing the raw serde
+/// error; this turns them into a 400 validation error naming the offending field.
+///
+/// Unknown fields are ignored, as serde does by default. With `?strict=true` on the
+/// request they are a 400 listing every one of them instead, so a typo like `titel`
+/// doesn't silently drop the value.
+pub struct JsonBody<T>(pub T);
+
+#[derive(Deserialize)]
+struct StrictParam {
+    #[serde(default)]
+    strict: bool,
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let strict = Query::<StrictParam>::try_from_uri(req.uri())
+            .map_err(|_| AppError::Validation("`strict` must be true or false".to_string()))?
+            .strict;
+        if !strict {
+            let Json(value) = Json::<T>::from_request(req, state).await?;
+            return Ok(JsonBody(value));
+        }
+
+        let Json(body) = Json::<serde_json::Value>::from_request(req, state).await?;
+        from_value_strict(body).map(JsonBody)
+    }
+}
+
+/// Deserialize like `Json` does, but fail on fields `T` doesn't have
+fn from_value_strict<T: DeserializeOwned>(body: serde_json::Value) -> Result<T, AppError> {
+    let mut unknown = Vec::new();
+    let value = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+        body,
+        &mut |path: serde_ignored::Path| unknown.push(format!("`{}`", path)),
+    ))
+    .map_err(|err| {
+        let path = err.path().to_string();
+        match path.as_str() {
+            "." => AppError::Validation(format!("Invalid request body: {}", err.inner())),
+            _ => AppError::Validation(format!("Invalid value for `{}`: {}", path, err.inner())),
+        }
+    })?;
+    if !unknown.is_empty() {
+        return Err(AppError::Validation(format!(
+            "Unknown field(s): {}",
+            unknown.join(", ")
+        )));
+    }
+    Ok(value)
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return AppError::PayloadTooLarge;
+        }
+        let message = match &rejection {
+            JsonRejection::JsonDataError(err) => match path_error(err) {
+                Some((path, reason)) if path != "." => {
+                    format!("Invalid value for `{}`: {}", path, reason)
+                }
+                Some((_, reason)) => format!("Invalid request body: {}", reason),
+                None => "Invalid request body".to_string(),
+            },
+            JsonRejection::JsonSyntaxError(err) => match path_error(err) {
+                Some((_, reason)) => format!("Malformed JSON body: {}", reason),
+                None => "Malformed JSON body".to_string(),
+            },
+            JsonRejection::MissingJsonContentType(_) => {
+                "Expected a `Content-Type: application/json` body".to_string()
+            }
+            _ => "Could not read request body".to_string(),
+        };
+        AppError::Validation(message)
+    }
+}
+
+// Axum deserializes through serde_path_to_error, which sits underneath the rejection
+fn path_error(err: &(dyn std::error::Error + 'static)) -> Option<(String, String)> {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<PathError<serde_json::Error>>() {
+            return Some((err.path().to_string(), err.inner().to_string()));
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, response::IntoResponse, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Payload {
+        shopify_product_id: i64,
+        #[serde(default)]
+        variants: Vec<Variant>,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Variant {
+        sku: String,
+    }
+
+    async fn rejection_for(body: &str) -> (u16, serde_json::Value) {
+        send("/", body).await
+    }
+
+    async fn send(uri: &str, body: &str) -> (u16, serde_json::Value) {
+        let app = Router::new().route("/", post(|JsonBody(_): JsonBody<Payload>| async {}));
+        let response = app
+            .oneshot(
+                Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .into_response();
+        let status = response.status().as_u16();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn type_errors_name_the_offending_field() {
+        let (status, body) = rejection_for(r#"{"shopify_product_id": "abc"}"#).await;
+
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "Validation error");
+        let message = body["message"].as_str().unwrap();
+        assert!(
+            message.starts_with("Invalid value for `shopify_product_id`: invalid type: string"),
+            "{}",
+            message
+        );
+    }
+
+    #[tokio::test]
+    async fn syntax_errors_are_reported_as_malformed() {
+        let (status, body) = rejection_for(r#"{"shopify_product_id": "#).await;
+
+        assert_eq!(status, 400);
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Malformed JSON body"));
+    }
+
+    #[tokio::test]
+    async fn unknown_fields_are_only_rejected_in_strict_mode() {
+        let body =
+            r#"{"shopify_product_id": 1, "titel": "Tee", "variants": [{"sku": "A", "prise": 5}]}"#;
+
+        let (status, _) = send("/", body).await;
+        assert_eq!(status, 200);
+        let (status, _) = send("/?strict=false", body).await;
+        assert_eq!(status, 200);
+
+        let (status, body) = send("/?strict=true", body).await;
+        assert_eq!(status, 400);
+        assert_eq!(
+            body["message"],
+            "Unknown field(s): `titel`, `variants.0.prise`"
+        );
+
+        let (status, _) = send("/?strict=true", r#"{"shopify_product_id": 1}"#).await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn strict_mode_keeps_the_usual_errors() {
+        let (status, body) = send("/?strict=true", r#"{"shopify_product_id": "abc"}"#).await;
+        assert_eq!(status, 400);
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid value for `shopify_product_id`: invalid type: string"));
+
+        let (status, body) = send("/?strict=yes", r#"{"shopify_product_id": 1}"#).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["message"], "`strict` must be true or false");
+    }
+}
@@ -0,0 +1,95 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::http::ClientIp;
+use crate::misc::redact;
+
+/// Matches axum's default request body limit so logging never accepts more than a handler would
+const MAX_REQUEST_BODY: usize = 2 * 1024 * 1024;
+
+/// Logged bodies are truncated to keep log lines readable
+const MAX_LOGGED_CHARS: usize = 4096;
+
+/// Debug middleware that logs request and response bodies with sensitive fields redacted
+///
+/// Enabled with `--log-bodies`. Bodies are buffered in memory, so this is meant for
+/// local debugging rather than production traffic.
+pub async fn log_bodies(request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_REQUEST_BODY).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let client = parts
+        .extensions
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    eprintln!(
+        "{}",
+        format_log_line(
+            &format!("--> {} {} client={}", parts.method, parts.uri, client),
+            &parts.headers,
+            &bytes
+        )
+    );
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    eprintln!(
+        "{}",
+        format_log_line(&format!("<-- {}", parts.status), &parts.headers, &bytes)
+    );
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn format_log_line(prefix: &str, headers: &HeaderMap, body: &[u8]) -> String {
+    let mut body = redact::redact_body(body);
+    if body.len() > MAX_LOGGED_CHARS {
+        let cut = (0..=MAX_LOGGED_CHARS)
+            .rev()
+            .find(|i| body.is_char_boundary(*i))
+            .unwrap_or(0);
+        body.truncate(cut);
+        body.push_str("...");
+    }
+    format!(
+        "{} headers=[{}] body={}",
+        prefix,
+        redact::redact_headers(headers),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logged_login_request_masks_password() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer a.b.c".parse().unwrap());
+        let line = format_log_line(
+            "--> POST /api/v1/login",
+            &headers,
+            br#"{"email":"admin@test-shop.com","password":"admin123"}"#,
+        );
+        assert!(line.contains(r#""password":"***""#));
+        assert!(line.contains("authorization: ***"));
+        assert!(!line.contains("admin123"));
+        assert!(!line.contains("a.b.c"));
+    }
+}
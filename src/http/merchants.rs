@@ -0,0 +1,909 @@
+use crate::auth::jkws::Scope;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError, JsonBody};
+use crate::misc::retry::retry_uncommitted;
+use crate::shopify::sync::{self, SyncCounts, SyncError, SyncLock};
+use crate::shopify::{parse_tags, ShopInfo, ShopifyClient, ShopifyErrorType};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub fn merchants_router() -> Router {
+    Router::new()
+        .route("/merchants/:id", delete(delete_merchant))
+        .route("/merchants/:id/shop", get(get_shop))
+        .route("/merchants/:id/reinstall", post(reinstall))
+        .route("/merchants/:id/sync", post(start_sync))
+        .route("/merchants/:id/sync/:job_id", get(get_sync_job))
+        .route("/merchants/:id/sync-status", get(get_sync_status))
+        .route("/merchants/:id/backfill", post(start_backfill))
+        .route("/merchants/:id/backfill/:backfill_id", get(get_backfill))
+        .route(
+            "/merchants/:id/ingest-filter",
+            get(get_ingest_filter).put(update_ingest_filter),
+        )
+}
+
+// Callers can only act on the merchant they belong to
+pub(crate) async fn ensure_own_merchant(
+    ctx: &ApiContext,
+    user: &AuthenticatedUser,
+    merchant_id: Uuid,
+) -> Result<(), AppError> {
+    let caller_merchant_id: Option<Uuid> = match user.api_key {
+        Some(key) => Some(key.merchant_id),
+        None => {
+            sqlx::query_scalar("SELECT merchant_id FROM users WHERE id = $1")
+                .bind(user.user_id()?)
+                .fetch_optional(&ctx.db)
+                .await?
+        }
+    };
+    if caller_merchant_id != Some(merchant_id) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+// Get live store info from Shopify (name, domain, plan, currency, timezone)
+// Cached briefly per merchant since it rarely changes.
+async fn get_shop(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<ShopInfo> {
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    if let Some(shop) = ctx.shop_cache.get(id) {
+        return Ok(Json(shop));
+    }
+
+    eprintln!("Fetching shop info from Shopify: merchant_id={}", id);
+    let shop = shopify_client_for(&ctx, id).await?.get_shop().await?;
+
+    ctx.shop_cache.insert(id, shop.clone());
+    Ok(Json(shop))
+}
+
+// Build a Shopify client from the merchant's active install (404 without a stored token,
+// 409 once the app was uninstalled). Clients for the same merchant share one circuit
+// breaker, so an outage seen by one request pauses Shopify calls for the others too.
+pub(crate) async fn shopify_client_for(
+    ctx: &ApiContext,
+    merchant_id: Uuid,
+) -> Result<ShopifyClient, AppError> {
+    let install: Option<(String, Option<String>, String)> = sqlx::query_as(
+        r#"
+        SELECT m.shop_domain, si.access_token, si.status
+        FROM merchants m
+        JOIN shopify_installs si ON si.merchant_id = m.id
+        WHERE m.id = $1 AND m.deleted_at IS NULL
+        ORDER BY si.installed_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(&ctx.db)
+    .await?;
+
+    // The token of an uninstalled app would only ever get 401s from Shopify
+    let (shop_domain, access_token) = match install {
+        Some((_, _, status)) if status != "active" => {
+            return Err(ShopifyErrorType::Uninstalled.into())
+        }
+        Some((shop_domain, Some(access_token), _)) => (shop_domain, access_token),
+        _ => return Err(AppError::NotFound),
+    };
+
+    let store_name = shop_domain.trim_end_matches(".myshopify.com").to_string();
+    Ok(ShopifyClient::new(
+        store_name,
+        access_token,
+        ctx.config.shopify_api_version.clone(),
+    )
+    .with_circuit_breaker(ctx.shopify_breakers.get(merchant_id))
+    .with_max_retries(ctx.config.shopify_max_retries))
+}
+
+/// Mark the merchant's installs uninstalled after Shopify's `app/uninstalled` webhook
+///
+/// The dead token is dropped; from here on `shopify_client_for` refuses to build a
+/// client, so nothing keeps calling Shopify until a reinstall. A sync or backfill
+/// already running stops at its next product, order or checkout. Returns how many
+/// installs were active.
+pub(crate) async fn mark_uninstalled(db: &PgPool, merchant_id: Uuid) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE shopify_installs
+        SET status = 'uninstalled', uninstalled_at = NOW(), access_token = NULL
+        WHERE merchant_id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(merchant_id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Re-activate a merchant after the app is installed again (ADMIN ONLY, own merchant only)
+// Records the new install's token; earlier installs are kept as history.
+async fn reinstall(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    JsonBody(payload): JsonBody<ReinstallRequest>,
+) -> Result<StatusCode, AppError> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+    if payload.access_token.trim().is_empty() {
+        return Err(AppError::Validation("access_token is required".to_string()));
+    }
+    eprintln!("Reinstalling merchant: id={}", id);
+
+    let mut tx = ctx.db.begin().await?;
+    // Any install still marked active is superseded by the new one
+    sqlx::query(
+        r#"
+        UPDATE shopify_installs
+        SET status = 'uninstalled', uninstalled_at = NOW(), access_token = NULL
+        WHERE merchant_id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO shopify_installs (merchant_id, access_scopes, installed_at, status, access_token)
+        VALUES ($1, $2, NOW(), 'active', $3)
+        "#,
+    )
+    .bind(id)
+    .bind(&payload.access_scopes)
+    .bind(&payload.access_token)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+const SYNC_JOB_COLUMNS: &str = r#"
+    id,
+    merchant_id,
+    status,
+    locations,
+    products,
+    orders,
+    checkouts,
+    error,
+    started_at,
+    finished_at
+"#;
+
+// Start a full sync in the background and return its job to poll (ADMIN ONLY, own merchant only)
+// 409 while another sync of the merchant runs, on this replica or another; a second
+// trigger is refused rather than queued.
+async fn start_sync(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, Json<SyncJob>), AppError> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    let client = shopify_client_for(&ctx, id).await?;
+    let lock = SyncLock::try_acquire(&ctx.db, id)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Sync already running".to_string()))?;
+
+    let job = sqlx::query_as::<_, SyncJob>(&format!(
+        "INSERT INTO sync_jobs (merchant_id, created_by) VALUES ($1, $2) RETURNING {}",
+        SYNC_JOB_COLUMNS
+    ))
+    .bind(id)
+    .bind(user.user_id().ok())
+    .fetch_one(&ctx.db)
+    .await?;
+
+    eprintln!("Starting sync: merchant_id={}, job_id={}", id, job.id);
+    let db = ctx.db.clone();
+    let job_id = job.id;
+    tokio::spawn(async move {
+        // A failure is also logged and recorded on the merchant by the sync itself
+        let result = sync::sync_merchant_locked(lock, &client, &db, id).await;
+        if let Ok(counts) = &result {
+            eprintln!("Sync finished: merchant_id={}, {:?}", id, counts);
+        }
+        if let Err(e) = finish_sync_job(&db, job_id, &result).await {
+            eprintln!("Could not record sync job {}: {}", job_id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Mark a sync job succeeded, with what it mirrored, or failed, with why
+async fn finish_sync_job(
+    db: &PgPool,
+    job_id: Uuid,
+    result: &Result<SyncCounts, SyncError>,
+) -> Result<(), sqlx::Error> {
+    let (status, counts, error) = match result {
+        Ok(counts) => ("succeeded", Some(counts), None),
+        Err(e) => ("failed", None, Some(e.to_string())),
+    };
+    sqlx::query(
+        r#"
+        UPDATE sync_jobs
+        SET status = $2, locations = $3, products = $4, orders = $5, checkouts = $6, error = $7,
+            finished_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(status)
+    .bind(counts.map(|c| c.locations as i64))
+    .bind(counts.map(|c| c.products as i64))
+    .bind(counts.map(|c| c.orders as i64))
+    .bind(counts.map(|c| c.checkouts as i64))
+    .bind(error)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+// Poll a sync job started with POST /merchants/{id}/sync (ADMIN ONLY, own merchant only)
+async fn get_sync_job(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path((id, job_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<SyncJob> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    let job = sqlx::query_as::<_, SyncJob>(&format!(
+        "SELECT {} FROM sync_jobs WHERE id = $1 AND merchant_id = $2",
+        SYNC_JOB_COLUMNS
+    ))
+    .bind(job_id)
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(job))
+}
+
+/// Days of orders per backfill window when the request doesn't say
+const DEFAULT_BACKFILL_WINDOW_DAYS: i32 = 30;
+
+const ORDER_BACKFILL_COLUMNS: &str = r#"
+    id,
+    merchant_id,
+    created_from AS "from",
+    created_to AS "to",
+    window_days,
+    synced_through,
+    orders,
+    status,
+    error,
+    started_at,
+    finished_at
+"#;
+
+// Backfill the orders created in [from, to) in the background, one window of orders at a
+// time, and return the backfill to poll (ADMIN ONLY, own merchant only)
+// Starting an unfinished backfill of the same range again resumes it from its last
+// finished window. Shares the merchant's sync lock, so 409 while a sync or backfill runs.
+async fn start_backfill(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<BackfillParams>,
+) -> Result<(StatusCode, Json<OrderBackfill>), AppError> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    if params.from >= params.to {
+        return Err(AppError::Validation("from must be before to".to_string()));
+    }
+    let window_days = params.window_days.unwrap_or(DEFAULT_BACKFILL_WINDOW_DAYS);
+    if !(1..=366).contains(&window_days) {
+        return Err(AppError::Validation(
+            "window_days must be between 1 and 366".to_string(),
+        ));
+    }
+
+    let client = shopify_client_for(&ctx, id).await?;
+    let lock = SyncLock::try_acquire(&ctx.db, id)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Sync already running".to_string()))?;
+
+    // With the lock held, a running backfill is one whose process died; resume it too
+    let resumed = sqlx::query_as::<_, OrderBackfill>(&format!(
+        r#"
+        UPDATE order_backfills
+        SET status = 'running', window_days = $4, error = NULL, started_at = NOW(), finished_at = NULL
+        WHERE id = (
+            SELECT id FROM order_backfills
+            WHERE merchant_id = $1 AND created_from = $2 AND created_to = $3 AND status <> 'succeeded'
+            ORDER BY started_at DESC
+            LIMIT 1
+        )
+        RETURNING {}
+        "#,
+        ORDER_BACKFILL_COLUMNS
+    ))
+    .bind(id)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(window_days)
+    .fetch_optional(&ctx.db)
+    .await?;
+    let backfill = match resumed {
+        Some(backfill) => backfill,
+        None => {
+            sqlx::query_as::<_, OrderBackfill>(&format!(
+                r#"
+                INSERT INTO order_backfills (merchant_id, created_from, created_to, window_days, created_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING {}
+                "#,
+                ORDER_BACKFILL_COLUMNS
+            ))
+            .bind(id)
+            .bind(params.from)
+            .bind(params.to)
+            .bind(window_days)
+            .bind(user.user_id().ok())
+            .fetch_one(&ctx.db)
+            .await?
+        }
+    };
+
+    eprintln!(
+        "Starting order backfill: merchant_id={}, backfill_id={}, synced_through={:?}",
+        id, backfill.id, backfill.synced_through
+    );
+    let db = ctx.db.clone();
+    let backfill_id = backfill.id;
+    tokio::spawn(async move {
+        let result = sync::backfill_orders(&client, &db, backfill_id).await;
+        match &result {
+            Ok(orders) => eprintln!(
+                "Order backfill finished: merchant_id={}, orders={}",
+                id, orders
+            ),
+            Err(e) => eprintln!("Order backfill failed: merchant_id={}, error={}", id, e),
+        }
+        if let Err(e) = finish_backfill(&db, backfill_id, &result).await {
+            eprintln!("Could not record order backfill {}: {}", backfill_id, e);
+        }
+        if let Err(e) = lock.release().await {
+            eprintln!(
+                "Could not release sync lock: merchant_id={}, error={}",
+                id, e
+            );
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(backfill)))
+}
+
+/// Mark a backfill succeeded, or failed with why; its progress is already recorded
+async fn finish_backfill(
+    db: &PgPool,
+    backfill_id: Uuid,
+    result: &Result<usize, SyncError>,
+) -> Result<(), sqlx::Error> {
+    let (status, error) = match result {
+        Ok(_) => ("succeeded", None),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    sqlx::query(
+        "UPDATE order_backfills SET status = $2, error = $3, finished_at = NOW() WHERE id = $1",
+    )
+    .bind(backfill_id)
+    .bind(status)
+    .bind(error)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+// Poll a backfill started with POST /merchants/{id}/backfill (ADMIN ONLY, own merchant only)
+async fn get_backfill(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path((id, backfill_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<OrderBackfill> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    let backfill = sqlx::query_as::<_, OrderBackfill>(&format!(
+        "SELECT {} FROM order_backfills WHERE id = $1 AND merchant_id = $2",
+        ORDER_BACKFILL_COLUMNS
+    ))
+    .bind(backfill_id)
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(backfill))
+}
+
+// Sync health: last success, last error, whether a sync is running and how much is
+// mirrored, to spot stuck or failing syncs (ADMIN ONLY, own merchant only)
+async fn get_sync_status(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<SyncStatus> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    let status = sqlx::query_as::<_, SyncStatus>(
+        r#"
+        SELECT
+            m.id AS merchant_id,
+            m.sync_started_at IS NOT NULL AS running,
+            m.sync_started_at,
+            m.last_synced_at,
+            m.last_sync_error AS last_error,
+            m.last_sync_error_at AS last_error_at,
+            (SELECT COUNT(*) FROM products p WHERE p.merchant_id = m.id AND p.deleted_at IS NULL) AS products,
+            (SELECT COUNT(*) FROM orders o WHERE o.merchant_id = m.id AND o.deleted_at IS NULL) AS orders,
+            (SELECT COUNT(*) FROM inventory_items i WHERE i.merchant_id = m.id AND i.deleted_at IS NULL) AS inventory_items
+        FROM merchants m
+        WHERE m.id = $1 AND m.deleted_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(status))
+}
+
+// Tags an order webhook must carry to be stored (ADMIN ONLY, own merchant only)
+async fn get_ingest_filter(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<IngestFilter> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    Ok(Json(IngestFilter {
+        order_tags: order_ingest_tags(&ctx.db, id).await?,
+    }))
+}
+
+// Replace the order tag allowlist; an empty list stores every order again
+async fn update_ingest_filter(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    JsonBody(payload): JsonBody<IngestFilter>,
+) -> AppResult<IngestFilter> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    // Shopify tags can't contain commas, so this only trims and deduplicates
+    let order_tags = parse_tags(&payload.order_tags.join(","));
+    eprintln!(
+        "Updating ingest filter: merchant_id={}, order_tags={:?}",
+        id, order_tags
+    );
+
+    sqlx::query(
+        r#"
+        INSERT INTO app_settings (merchant_id, order_ingest_tags)
+        VALUES ($1, $2)
+        ON CONFLICT (merchant_id) DO UPDATE SET order_ingest_tags = EXCLUDED.order_ingest_tags
+        "#,
+    )
+    .bind(id)
+    .bind(&order_tags)
+    .execute(&ctx.db)
+    .await?;
+
+    Ok(Json(IngestFilter { order_tags }))
+}
+
+/// The merchant's order tag allowlist; empty when none is set
+pub(crate) async fn order_ingest_tags(
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<Vec<String>, AppError> {
+    let tags: Option<Option<Vec<String>>> =
+        sqlx::query_scalar("SELECT order_ingest_tags FROM app_settings WHERE merchant_id = $1")
+            .bind(merchant_id)
+            .fetch_optional(db)
+            .await?;
+    Ok(tags.flatten().unwrap_or_default())
+}
+
+// Delete a merchant and all of its data (ADMIN ONLY, own merchant only)
+// Soft-deletes by default; `?purge=true` hard-deletes everything in one transaction.
+async fn delete_merchant(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DeleteMerchantParams>,
+) -> Result<StatusCode, AppError> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, id).await?;
+
+    let purge = params.purge.unwrap_or(false);
+    eprintln!("Deleting merchant: id={}, purge={}", id, purge);
+
+    retry_uncommitted(|| delete_merchant_data(&ctx.db, id, purge)).await?;
+
+    eprintln!("Merchant deleted successfully: id={}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Soft- or hard-delete a merchant and its data in one transaction
+async fn delete_merchant_data(db: &PgPool, id: Uuid, purge: bool) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    if purge {
+        // Children are removed explicitly rather than relying on ON DELETE CASCADE
+        // so the purge order is obvious (see migration 006)
+        for table in [
+            "product_tags",
+            "order_tags",
+            "tags",
+            "product_metafields",
+            "variants",
+            "products",
+            "inventory_levels",
+            "inventory_items",
+            "locations",
+            "checkouts",
+            "order_refunds",
+            "fulfillments",
+            "order_line_items",
+            "orders",
+            "users",
+            "app_settings",
+            "sync_jobs",
+            "order_backfills",
+            "shopify_installs",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE merchant_id = $1", table))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let result = sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+    } else {
+        let result = sqlx::query(
+            r#"
+            UPDATE merchants
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+
+        for table in ["products", "orders", "inventory_items"] {
+            sqlx::query(&format!(
+                "UPDATE {} SET deleted_at = NOW() WHERE merchant_id = $1 AND deleted_at IS NULL",
+                table
+            ))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("UPDATE users SET is_active = false, updated_at = NOW() WHERE merchant_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, insert_user, send, test_db};
+
+    #[tokio::test]
+    async fn ingest_filter_round_trips_and_can_be_cleared() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let (_, email) = insert_user(&db, merchant_id, "admin").await?;
+        let app = crate::http::test_router(db.clone());
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let token = session["data"]["access_token"].as_str();
+        let uri = format!("/api/v1/merchants/{}/ingest-filter", merchant_id);
+
+        let (status, filter) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(filter, serde_json::json!({ "order_tags": [] }));
+
+        // Saved twice to exercise the upsert; tags are trimmed and deduplicated
+        for tags in [vec!["b2b"], vec![" Wholesale", "wholesale", "VIP "]] {
+            let body = serde_json::json!({ "order_tags": tags });
+            let (status, _) = send(&app, "PUT", &uri, token, Some(body)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (_, filter) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(
+            filter["order_tags"],
+            serde_json::json!(["Wholesale", "VIP"])
+        );
+        assert_eq!(
+            order_ingest_tags(&db, merchant_id).await?,
+            vec!["Wholesale", "VIP"]
+        );
+
+        let clear = serde_json::json!({ "order_tags": [] });
+        let (_, filter) = send(&app, "PUT", &uri, token, Some(clear)).await;
+        assert_eq!(filter, serde_json::json!({ "order_tags": [] }));
+
+        let (status, _) = send(&app, "GET", &uri, None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uninstall_stops_shopify_calls_until_reinstalled() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO shopify_installs (merchant_id, access_scopes, installed_at, status, access_token)
+            VALUES ($1, 'read_orders', NOW() - INTERVAL '1 day', 'active', 'shpat_old')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let (_, email) = insert_user(&db, merchant_id, "admin").await?;
+        let app = crate::http::test_router(db.clone());
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let token = session["data"]["access_token"].as_str();
+
+        assert_eq!(mark_uninstalled(&db, merchant_id).await?, 1);
+        assert_eq!(mark_uninstalled(&db, merchant_id).await?, 0);
+
+        // No client, so no request that would 401 reaches Shopify
+        let shop_uri = format!("/api/v1/merchants/{}/shop", merchant_id);
+        let (status, body) = send(&app, "GET", &shop_uri, token, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "Shopify app uninstalled");
+
+        let uri = format!("/api/v1/merchants/{}/reinstall", merchant_id);
+        let blank = serde_json::json!({ "access_token": " ", "access_scopes": "read_orders" });
+        let (status, _) = send(&app, "POST", &uri, token, Some(blank)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let fresh =
+            serde_json::json!({ "access_token": "shpat_new", "access_scopes": "read_orders" });
+        let (status, _) = send(&app, "POST", &uri, token, Some(fresh)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let installs: Vec<(String, Option<String>)> = sqlx::query_as(
+            "SELECT status, access_token FROM shopify_installs WHERE merchant_id = $1 ORDER BY installed_at",
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+        assert_eq!(
+            installs,
+            vec![
+                ("uninstalled".to_string(), None),
+                ("active".to_string(), Some("shpat_new".to_string())),
+            ]
+        );
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_status_and_manual_sync_trigger() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let app = crate::http::test_router(db.clone());
+        let mut tokens = Vec::new();
+        for role in ["admin", "viewer"] {
+            let (_, email) = insert_user(&db, merchant_id, role).await?;
+            let login = serde_json::json!({ "email": email, "password": "password" });
+            let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+            let token = session["data"]["access_token"].as_str().unwrap();
+            tokens.push(token.to_string());
+        }
+        let (admin, viewer) = (Some(tokens[0].as_str()), Some(tokens[1].as_str()));
+        let uri = format!("/api/v1/merchants/{}/sync-status", merchant_id);
+
+        // Never synced
+        let (status, body) = send(&app, "GET", &uri, admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["running"], false);
+        assert!(body["last_synced_at"].is_null());
+        assert!(body["last_error"].is_null());
+        assert_eq!(body["products"], 0);
+
+        // A sync in progress after a failed one, with some data mirrored (one deleted product)
+        sqlx::query(
+            r#"
+            UPDATE merchants
+            SET sync_started_at = NOW(), last_sync_error = 'Shopify error: boom', last_sync_error_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO products (merchant_id, shopify_product_id, deleted_at)
+            VALUES ($1, 1, NULL), ($1, 2, NULL), ($1, 3, NOW())
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        sqlx::query("INSERT INTO orders (merchant_id, shopify_order_id) VALUES ($1, 1)")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        sqlx::query(
+            "INSERT INTO inventory_items (merchant_id, shopify_inventory_item_id) VALUES ($1, 1)",
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let (_, body) = send(&app, "GET", &uri, admin, None).await;
+        assert_eq!(body["running"], true);
+        assert!(body["sync_started_at"].is_string());
+        assert_eq!(body["last_error"], "Shopify error: boom");
+        assert!(body["last_error_at"].is_string());
+        assert_eq!(body["products"], 2);
+        assert_eq!(body["orders"], 1);
+        assert_eq!(body["inventory_items"], 1);
+
+        let (status, _) = send(&app, "GET", &uri, viewer, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let other = format!("/api/v1/merchants/{}/sync-status", Uuid::new_v4());
+        let (status, _) = send(&app, "GET", &other, admin, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // A manual trigger is refused while a sync holds the merchant's lock
+        sqlx::query(
+            r#"
+            INSERT INTO shopify_installs (merchant_id, access_scopes, installed_at, status, access_token)
+            VALUES ($1, 'read_orders', NOW(), 'active', 'shpat_test')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let trigger = format!("/api/v1/merchants/{}/sync", merchant_id);
+        let (status, _) = send(&app, "POST", &trigger, viewer, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let lock = SyncLock::try_acquire(&db, merchant_id).await?.unwrap();
+        let (status, body) = send(&app, "POST", &trigger, admin, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "Sync already running");
+        lock.release().await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_jobs_are_polled_by_id() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let mut merchants = Vec::new();
+        for _ in 0..2 {
+            let merchant_id = insert_merchant(&db).await?;
+            merchants.push(merchant_id);
+        }
+        let (_, email) = insert_user(&db, merchants[0], "admin").await?;
+        let app = crate::http::test_router(db.clone());
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let token = session["data"]["access_token"].as_str();
+
+        let mut jobs = Vec::new();
+        for merchant_id in &merchants {
+            let job_id: Uuid =
+                sqlx::query_scalar("INSERT INTO sync_jobs (merchant_id) VALUES ($1) RETURNING id")
+                    .bind(merchant_id)
+                    .fetch_one(&db)
+                    .await?;
+            jobs.push(job_id);
+        }
+        let uri = format!("/api/v1/merchants/{}/sync/{}", merchants[0], jobs[0]);
+
+        let (status, job) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(job["status"], "running");
+        assert!(job["finished_at"].is_null());
+
+        let counts = SyncCounts {
+            locations: 2,
+            products: 3,
+            orders: 2,
+            checkouts: 1,
+        };
+        finish_sync_job(&db, jobs[0], &Ok(counts)).await?;
+        let (_, job) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(job["status"], "succeeded");
+        assert_eq!(job["locations"], 2);
+        assert_eq!(job["products"], 3);
+        assert_eq!(job["orders"], 2);
+        assert!(job["error"].is_null());
+        assert!(job["finished_at"].is_string());
+
+        let failure = Err(SyncError::Shopify(ShopifyErrorType::CircuitOpen));
+        finish_sync_job(&db, jobs[0], &failure).await?;
+        let (_, job) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(job["status"], "failed");
+        assert!(job["products"].is_null());
+        assert!(job["error"].as_str().unwrap().starts_with("Shopify error"));
+
+        // Another merchant's job, even under our merchant's path, and unknown ids
+        for (merchant_id, job_id) in [(merchants[0], jobs[1]), (merchants[0], Uuid::new_v4())] {
+            let uri = format!("/api/v1/merchants/{}/sync/{}", merchant_id, job_id);
+            let (status, _) = send(&app, "GET", &uri, token, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        let uri = format!("/api/v1/merchants/{}/sync/{}", merchants[1], jobs[1]);
+        let (status, _) = send(&app, "GET", &uri, token, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        sqlx::query("DELETE FROM merchants WHERE id = ANY($1)")
+            .bind(&merchants)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use crate::shopify::client::REQUEST_DURATION_METRIC;
+use anyhow::Context;
+use axum::{routing::get, Extension, Router};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+/// Bucket bounds (seconds) for Shopify request latency; calls are slow and the client times out at 30s
+const SHOPIFY_LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Install the process-wide Prometheus recorder. Call at most once, before serving.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    builder()?
+        .install_recorder()
+        .context("could not install the metrics recorder")
+}
+
+fn builder() -> anyhow::Result<PrometheusBuilder> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION_METRIC.to_string()),
+            SHOPIFY_LATENCY_BUCKETS,
+        )
+        .context("invalid metric buckets")
+}
+
+/// `GET /metrics` in the Prometheus text format. Unauthenticated, so it lives outside
+/// the versioned API and should only be reachable from the internal network.
+pub fn metrics_router(handle: PrometheusHandle) -> Router {
+    Router::new()
+        .route("/metrics", get(render))
+        .layer(Extension(handle))
+}
+
+async fn render(Extension(handle): Extension<PrometheusHandle>) -> String {
+    handle.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn shopify_latency_is_exported_as_a_histogram() {
+        let recorder = builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            metrics::histogram!(REQUEST_DURATION_METRIC, "endpoint" => "orders", "status" => "200")
+                .record(0.3);
+        });
+
+        let response = metrics_router(handle)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.contains(
+            r#"shopify_request_duration_seconds_bucket{endpoint="orders",status="200",le="0.5"} 1"#
+        ));
+        assert!(body.contains(
+            r#"shopify_request_duration_seconds_bucket{endpoint="orders",status="200",le="0.25"} 0"#
+        ));
+    }
+}
@@ -1,40 +1,132 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::Context;
-use axum::{response::Redirect, routing::get, Extension, Router};
+use axum::{
+    extract::DefaultBodyLimit, middleware, response::Redirect, routing::get, Extension, Router,
+};
 /* use sqlx::prelude::FromRow; */
 use sqlx::PgPool;
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate},
+        CompressionLayer, DefaultPredicate,
+    },
+    cors::CorsLayer,
+    decompression::RequestDecompressionLayer,
+    services::ServeDir,
+    trace::TraceLayer,
+};
 
 use crate::auth::jkws::AuthService;
+use crate::http::rate_limit::RateLimiter;
+use crate::shopify::cache::ShopInfoCache;
+use crate::shopify::circuit::CircuitBreakers;
 use crate::Args;
 
 mod auth;
+mod changes;
+mod checkouts;
+mod db;
+mod envelope;
+mod filters;
+mod health;
 mod inventory;
+mod json;
+mod logging;
+mod merchants;
+mod metrics;
 mod orders;
+mod pagination;
+mod prefer;
+mod problem;
 mod products;
+mod rate_limit;
+mod real_ip;
+mod reports;
+mod tags;
+mod timestamps;
 mod types;
 mod users;
+mod v1;
+mod variants;
+mod version;
+mod webhooks;
 
+pub use db::{with_statement_timeout, DbConn, ReadPool};
+pub use filters::Filters;
+pub use json::JsonBody;
+pub use pagination::{PageLimits, PagedResource, Pagination};
+pub use real_ip::{ClientIp, RealIpConfig};
 pub use types::*;
 
 #[derive(Clone)]
 pub struct ApiContext {
     pub config: Arc<Args>,
     pub db: PgPool,
+    pub read_db: PgPool, // Read replica for `ReadPool`; the primary again when none is configured
     pub auth_service: Arc<AuthService>,
+    pub shop_cache: Arc<ShopInfoCache>,
+    pub shopify_breakers: Arc<CircuitBreakers>,
+    pub rate_limiter: Arc<RateLimiter>,
 }
 
-pub async fn serve(config: Args, db: PgPool) -> anyhow::Result<()> {
+/// Address the HTTP server listens on
+const BIND_ADDR: &str = "0.0.0.0:8080";
+
+/// Largest request body extractors read, counted after `Content-Encoding` is undone
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+pub async fn serve(config: Args, db: PgPool, read_db: PgPool) -> anyhow::Result<()> {
     let auth_service = Arc::new(AuthService::from_config(&config)?);
 
+    // What this process actually resolved, defaults included, with secrets left out
+    println!(
+        "⚙️  Effective config: {}",
+        config.effective_config(
+            auth_service.key_source(),
+            BIND_ADDR,
+            db.options().get_max_connections(),
+        )
+    );
+
     // Initialize auxiliary services here (email, etc.) when available
 
-    let app = api_router()
+    let enable_compression = config.enable_compression;
+    let log_bodies = config.log_bodies;
+    let real_ip_config = Arc::new(config.real_ip_config().map_err(anyhow::Error::msg)?);
+    let shopify_breakers = Arc::new(CircuitBreakers::new(config.circuit_breaker_config()));
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit_per_second,
+        config.rate_limit_burst,
+    ));
+
+    let mut app = api_router();
+
+    // Prometheus scrape endpoint; installing the recorder is what turns the
+    // metrics recorded elsewhere (e.g. by the Shopify client) from no-ops into samples
+    if config.enable_metrics {
+        app = app.merge(metrics::metrics_router(metrics::install_recorder()?));
+    }
+
+    // Per-client token bucket; needs ApiContext, so it sits inside the Extension layer
+    if config.rate_limit_per_second > 0 {
+        app = app.layer(middleware::from_fn(rate_limit::rate_limit));
+    }
+
+    // Accept: application/problem+json turns errors into RFC 7807 problem details;
+    // outside the rate limiter so its 429s follow suit
+    app = app.layer(middleware::from_fn(problem::problem_details));
+
+    let mut app = app
         .layer(Extension(ApiContext {
             config: Arc::new(config),
             db,
+            read_db,
             auth_service: auth_service.clone(),
+            shop_cache: Arc::new(ShopInfoCache::default()),
+            shopify_breakers,
+            rate_limiter,
         }))
         // Enable CORS for cross-origin requests (needed for Swagger UI)
         .layer(
@@ -49,35 +141,240 @@ pub async fn serve(config: Args, db: PgPool) -> anyhow::Result<()> {
                 .allow_headers([
                     axum::http::header::CONTENT_TYPE,
                     axum::http::header::AUTHORIZATION,
+                    axum::http::HeaderName::from_static("x-api-key"),
+                    axum::http::HeaderName::from_static("x-response-envelope"),
+                    axum::http::HeaderName::from_static("x-request-id"),
+                    axum::http::HeaderName::from_static("prefer"),
+                    db::X_READ_PRIMARY,
+                ])
+                // Returned by `Prefer: return=minimal` writes
+                .expose_headers([
+                    axum::http::header::LOCATION,
+                    axum::http::HeaderName::from_static("preference-applied"),
+                    version::X_API_VERSION,
+                    version::X_SERVER_VERSION,
                 ]),
-        )
-        // Enables logging. Use `RUST_LOG=tower_http=debug`
-        .layer(TraceLayer::new_for_http());
+        );
+
+    // Debug logging of request/response bodies, with passwords and tokens redacted.
+    // Sits inside compression so it sees the plain response body.
+    if log_bodies {
+        app = app.layer(middleware::from_fn(logging::log_bodies));
+    }
+
+    // Client IP (from a trusted proxy's header with TRUST_PROXY) for the rate limiter and logs
+    app = app.layer(middleware::from_fn_with_state(
+        real_ip_config,
+        real_ip::real_ip,
+    ));
+
+    // Compress responses when the client advertises support via Accept-Encoding
+    if enable_compression {
+        app = app.layer(compression_layer());
+    }
+
+    // X-Server-Version on every response, including ones outside the versioned API
+    app = app.layer(middleware::from_fn(version::server_version));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
+    // Enables logging. Use `RUST_LOG=tower_http=debug`
+    let app = app.layer(TraceLayer::new_for_http());
+
+    let listener = tokio::net::TcpListener::bind(BIND_ADDR)
         .await
         .context("could not bind to")?;
 
-    axum::serve(listener, app.into_make_service())
-        .await
-        .context("error running HTTP server")
+    // Connection info gives real_ip the peer address
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("error running HTTP server")
+}
+
+/// Gzip/brotli compression that skips small bodies and content that is already compressed
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new("application/zip"))
+        .and(NotForContentType::const_new("application/gzip"))
+        .and(NotForContentType::const_new("video/"))
+        .and(NotForContentType::const_new("audio/"));
+
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(predicate)
 }
 
 fn api_router() -> Router {
-    // This is the order that the modules were authored in.
     Router::new()
         // Redirect root to docs
         .route("/", get(|| async { Redirect::permanent("/docs/") }))
         // Serve static documentation files
         .nest_service("/docs", ServeDir::new("docs"))
-        // API routes
-        .nest(
-            "/api/v1",
-            Router::new()
-                .merge(auth::auth_router())
-                .merge(inventory::inventory_router())
-                .merge(orders::orders_router())
-                .merge(products::products_router())
-                .merge(users::users_router()),
-        )
+        // API routes, one self-contained router per version. To ship breaking
+        // changes add a `v2` module next to `v1` and nest it here alongside it.
+        .nest(v1::PREFIX, v1::router())
+        // Content-Encoding: gzip bodies (e.g. large bulk payloads) are inflated as they
+        // are read; the limit counts inflated bytes, so a small zip bomb can't get past it
+        .layer(decompression_layer())
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+}
+
+/// Gzip request body decompression; other encodings are refused with `415`
+fn decompression_layer() -> RequestDecompressionLayer {
+    RequestDecompressionLayer::new().gzip(true)
+}
+
+/// The full API router wired to `db`, for driving handlers over HTTP in tests
+#[cfg(test)]
+pub(crate) fn test_router(db: PgPool) -> Router {
+    api_router().layer(Extension(test_context(db.clone(), db)))
+}
+
+/// A context on a fresh key pair and default config, reading through `read_db`
+#[cfg(test)]
+pub(crate) fn test_context(db: PgPool, read_db: PgPool) -> ApiContext {
+    let keys = crate::misc::keypair::generate_rsa_key_pair().unwrap();
+    let config = Args::default();
+    let auth_service = AuthService::new(
+        keys.private_key,
+        config.jwt_expiration_hours,
+        keys.public_key,
+    )
+    .unwrap();
+
+    ApiContext {
+        config: Arc::new(config),
+        db,
+        read_db,
+        auth_service: Arc::new(auth_service),
+        shop_cache: Arc::new(ShopInfoCache::default()),
+        shopify_breakers: Arc::new(CircuitBreakers::default()),
+        rate_limiter: Arc::new(RateLimiter::new(0, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        Json,
+    };
+    use tower::ServiceExt;
+
+    fn compressed_router() -> Router {
+        Router::new()
+            .route(
+                "/products",
+                get(|| async {
+                    let products: Vec<_> = (0..500)
+                        .map(|i| serde_json::json!({ "id": i, "title": "Test product", "status": "active" }))
+                        .collect();
+                    Json(products)
+                }),
+            )
+            .route(
+                "/archive",
+                get(|| async {
+                    ([(header::CONTENT_TYPE, "application/zip")], vec![0u8; 4096])
+                }),
+            )
+            .layer(compression_layer())
+    }
+
+    async fn content_encoding(uri: &str, accept_encoding: Option<&str>) -> Option<String> {
+        let mut request = Request::builder().uri(uri);
+        if let Some(encoding) = accept_encoding {
+            request = request.header(header::ACCEPT_ENCODING, encoding);
+        }
+        let response = compressed_router()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn large_list_is_compressed_when_accepted() {
+        assert_eq!(
+            content_encoding("/products", Some("gzip")).await.as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(
+            content_encoding("/products", Some("br")).await.as_deref(),
+            Some("br")
+        );
+    }
+
+    #[tokio::test]
+    async fn response_is_uncompressed_without_accept_encoding() {
+        assert_eq!(content_encoding("/products", None).await, None);
+    }
+
+    #[tokio::test]
+    async fn already_compressed_content_is_skipped() {
+        assert_eq!(content_encoding("/archive", Some("gzip")).await, None);
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn post_json(body: Vec<u8>, encoding: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let app = Router::new()
+            .route(
+                "/levels",
+                axum::routing::post(|JsonBody(body): JsonBody<serde_json::Value>| async move {
+                    Json(body)
+                }),
+            )
+            .layer(decompression_layer())
+            .layer(DefaultBodyLimit::max(MAX_BODY_BYTES));
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/levels")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(encoding) = encoding {
+            request = request.header(header::CONTENT_ENCODING, encoding);
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn gzip_request_bodies_are_decompressed_within_the_body_limit() {
+        let body = br#"{"levels":[{"location_id":655441491,"available":12}]}"#;
+        let (status, echoed) = post_json(gzip(body), Some("gzip")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echoed["levels"][0]["available"], 12);
+        let (status, _) = post_json(body.to_vec(), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // A few KiB on the wire, but over the limit once inflated
+        let bomb = format!(r#"{{"pad":"{}"}}"#, "0".repeat(MAX_BODY_BYTES));
+        let compressed = gzip(bomb.as_bytes());
+        assert!(compressed.len() < 64 * 1024);
+        let (status, error) = post_json(compressed, Some("gzip")).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error["error"], "Payload too large");
+
+        let (status, _) = post_json(body.to_vec(), Some("br")).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
 }
@@ -1,101 +1,151 @@
-use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::auth::jkws::Scope;
+use crate::http::changes::{ChangeFeed, ChangesResponse, IdType};
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::prefer;
+use crate::http::{
+    auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, DbConn, Filters, JsonBody,
+    PagedResource, Pagination, ReadPool,
+};
+use crate::misc::validator;
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
+    middleware,
     routing::get,
     Extension, Json, Router,
 };
+use rust_decimal::Decimal;
+use sqlx::{Connection, PgConnection};
 
 pub fn orders_router() -> Router {
     Router::new()
         .route("/orders", get(list_orders).post(create_order))
+        .route("/orders/changes", get(order_changes))
+        .route("/orders/search", get(search_orders))
         .route(
             "/orders/:id",
             get(get_order).put(update_order).delete(delete_order),
         )
+        .route(
+            "/orders/by-shopify-id/:shopify_order_id",
+            get(get_order_by_shopify_id),
+        )
+        // Prefer: return=minimal on create/update
+        .route_layer(middleware::from_fn(prefer::return_preference))
 }
 
 async fn list_orders(
     Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
     Query(params): Query<ListOrdersParams>,
-) -> AppResult<OrderListResponse> {
+) -> AppResult<ListResponse<Order>> {
     eprintln!(
-        "Listing orders: merchant_id={}, limit={:?}, offset={:?}",
-        params.merchant_id, params.limit, params.offset
+        "Listing orders: merchant_id={}, financial_status={:?}, cancelled={:?}, tag={:?}, limit={:?}, offset={:?}",
+        params.merchant_id, params.financial_status, params.cancelled, params.tag, params.limit, params.offset
     );
 
-    let limit = params.limit.unwrap_or(50).min(100);
-    let offset = params.offset.unwrap_or(0);
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Orders),
+    );
 
-    // Get total count
-    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
-        r#"
-        SELECT COUNT(*) as count
-        FROM orders 
-        WHERE merchant_id = $1
-        "#,
-    )
-    .bind(params.merchant_id)
-    .fetch_one(&ctx.db)
-    .await?
-    .unwrap_or(0);
-
-    // Get orders
-    let orders = sqlx::query_as::<_, Order>(
-        r#"
-        SELECT 
-            id,
-            merchant_id,
-            shopify_order_id,
-            name,
-            processed_at,
-            currency,
-            subtotal_price,
-            total_price,
-            total_discounts,
-            total_shipping_price_set_amount,
-            total_tax,
-            financial_status,
-            cancelled_at,
-            created_at,
-            updated_at
-        FROM orders
-        WHERE merchant_id = $1 
-            AND ($2::text IS NULL OR financial_status = $2)
-        ORDER BY processed_at DESC NULLS LAST, created_at DESC
-        LIMIT $3 OFFSET $4
-        "#,
-    )
-    .bind(params.merchant_id)
-    .bind(params.financial_status)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(&ctx.db)
-    .await?;
+    let mut conn = read_db.acquire().await?;
+    let (orders, total) = find_orders(&mut conn, &params, limit, offset).await?;
 
     eprintln!("Found {} orders (total: {})", orders.len(), total);
 
-    Ok(Json(OrderListResponse {
-        orders,
+    Ok(Json(ListResponse {
+        items: orders,
         total,
         limit,
         offset,
     }))
 }
 
-async fn get_order(
+// Find a merchant's orders by customer email, newest first (own merchant only)
+// The whole address has to match, ignoring case, so partial input never lists
+// other customers' orders.
+async fn search_orders(
+    user: AuthenticatedUser,
     Extension(ctx): Extension<ApiContext>,
-    Path(id): Path<i64>,
-) -> AppResult<Order> {
-    eprintln!("Getting order: id={}", id);
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<SearchOrdersParams>,
+) -> AppResult<ListResponse<OrderSearchResult>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+    let customer_email = params.customer_email.trim();
+    if customer_email.is_empty() {
+        return Err(AppError::Validation(
+            "customer_email is required".to_string(),
+        ));
+    }
 
-    let order = sqlx::query_as::<_, Order>(
-        r#"
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Orders),
+    );
+    let search = ListOrdersParams {
+        merchant_id: params.merchant_id,
+        financial_status: None,
+        cancelled: None,
+        tag: None,
+        customer_email: Some(customer_email.to_string()),
+        limit: None,
+        offset: None,
+    };
+    let mut conn = read_db.acquire().await?;
+    let (orders, total) = find_orders(&mut conn, &search, limit, offset).await?;
+
+    Ok(Json(ListResponse {
+        items: orders.into_iter().map(OrderSearchResult::from).collect(),
+        total,
+        limit,
+        offset,
+    }))
+}
+
+/// One page of orders matching the financial status/cancellation/tag/customer email filters, plus the total match count
+async fn find_orders(
+    conn: &mut PgConnection,
+    params: &ListOrdersParams,
+    limit: i32,
+    offset: i32,
+) -> Result<(Vec<Order>, i64), AppError> {
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and("deleted_at IS NULL", [])
+        .and_opt("financial_status = {}", params.financial_status.as_deref())
+        .and_opt("(cancelled_at IS NOT NULL) = {}", params.cancelled)
+        .and_opt(
+            r#"EXISTS (
+                SELECT 1
+                FROM order_tags ot
+                JOIN tags t ON t.id = ot.tag_id
+                WHERE ot.order_id = orders.id AND lower(t.name) = lower({})
+            )"#,
+            params.tag.as_deref(),
+        )
+        .and_opt(
+            "lower(customer_email) = lower({})",
+            params.customer_email.as_deref(),
+        );
+
+    let total: i64 = filters
+        .count("FROM orders")
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let orders = filters
+        .page(
+            r#"
         SELECT 
             id,
             merchant_id,
             shopify_order_id,
             name,
+            customer_email,
             processed_at,
             currency,
             subtotal_price,
@@ -105,29 +155,190 @@ async fn get_order(
             total_tax,
             financial_status,
             cancelled_at,
+            status,
             created_at,
             updated_at
         FROM orders
-        WHERE id = $1
         "#,
-    )
+            "processed_at DESC NULLS LAST, created_at DESC",
+            "id DESC",
+            limit,
+            offset,
+        )
+        .build_query_as::<Order>()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok((orders, total))
+}
+
+/// Columns of `Order`, for the `SELECT`s that load one
+const ORDER_COLUMNS: &str = "id, merchant_id, shopify_order_id, name, customer_email, \
+    processed_at, currency, subtotal_price, total_price, total_discounts, \
+    total_shipping_price_set_amount, total_tax, financial_status, cancelled_at, status, \
+    created_at, updated_at";
+
+/// Orders changed since `since` or `cursor`, deleted ones included; see `http::changes`
+async fn order_changes(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ChangesParams>,
+) -> AppResult<ChangesResponse<Order>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+
+    let feed = ChangeFeed {
+        table: "orders",
+        columns: ORDER_COLUMNS,
+        id_type: IdType::BigInt,
+    };
+    let mut conn = read_db.acquire().await?;
+    let changes = feed
+        .page(
+            &mut conn,
+            &params,
+            ctx.config.page_limits(PagedResource::Orders),
+            ctx.config.change_feed_lag(),
+        )
+        .await?;
+    Ok(Json(changes))
+}
+
+// Get an order by our `id`; this is never the Shopify order id, see get_order_by_shopify_id
+async fn get_order(ReadPool(read_db): ReadPool, Path(id): Path<i64>) -> AppResult<OrderDetail> {
+    eprintln!("Getting order: id={}", id);
+    let mut conn = read_db.acquire().await?;
+
+    let order = sqlx::query_as::<_, Order>(&format!(
+        "SELECT {} FROM orders WHERE id = $1 AND deleted_at IS NULL",
+        ORDER_COLUMNS
+    ))
     .bind(id)
-    .fetch_optional(&ctx.db)
+    .fetch_optional(&mut *conn)
     .await?
     .ok_or(AppError::NotFound)?;
 
-    Ok(Json(order))
+    Ok(Json(order_detail(&mut conn, order).await?))
+}
+
+// Get a merchant's order by its Shopify order id (`shopify_id` in responses)
+async fn get_order_by_shopify_id(
+    ReadPool(read_db): ReadPool,
+    Path(shopify_order_id): Path<i64>,
+    Query(params): Query<OrderByShopifyIdParams>,
+) -> AppResult<OrderDetail> {
+    eprintln!(
+        "Getting order by Shopify id: merchant_id={}, shopify_order_id={}",
+        params.merchant_id, shopify_order_id
+    );
+    let mut conn = read_db.acquire().await?;
+
+    // Shopify ids are only unique per store
+    let order = sqlx::query_as::<_, Order>(&format!(
+        r#"
+        SELECT {} FROM orders
+        WHERE merchant_id = $1 AND shopify_order_id = $2 AND deleted_at IS NULL
+        "#,
+        ORDER_COLUMNS
+    ))
+    .bind(params.merchant_id)
+    .bind(shopify_order_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(order_detail(&mut conn, order).await?))
+}
+
+/// `order` with its refunds and fulfillments
+async fn order_detail(conn: &mut PgConnection, order: Order) -> Result<OrderDetail, AppError> {
+    let id = order.id;
+    let refunds = sqlx::query_as::<_, OrderRefund>(
+        r#"
+        SELECT id, shopify_refund_id, amount, reason, created_at
+        FROM order_refunds
+        WHERE order_id = $1
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let fulfillments = sqlx::query_as::<_, Fulfillment>(
+        r#"
+        SELECT id, shopify_fulfillment_id, status, tracking_number, tracking_company, created_at
+        FROM fulfillments
+        WHERE order_id = $1
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let (refunded_amount, net_total) = net_of_refunds(order.total_price, &refunds);
+    Ok(OrderDetail {
+        order,
+        refunds,
+        fulfillments,
+        refunded_amount,
+        net_total,
+    })
+}
+
+/// Total refunded and the order total net of refunds
+///
+/// Refunds are summed at full precision and rounded to cents once, and the net is
+/// clamped at zero, so a full refund never leaves a negative sub-cent remainder.
+pub fn net_of_refunds(total_price: Option<Decimal>, refunds: &[OrderRefund]) -> (Decimal, Decimal) {
+    let refunded = refunds
+        .iter()
+        .map(|r| r.amount)
+        .sum::<Decimal>()
+        .round_dp(2);
+    let net = (total_price.unwrap_or_default().round_dp(2) - refunded).max(Decimal::ZERO);
+    (refunded, net)
 }
 
 async fn create_order(
-    Extension(ctx): Extension<ApiContext>,
-    Json(payload): Json<CreateOrderRequest>,
+    mut conn: DbConn,
+    JsonBody(payload): JsonBody<CreateOrderRequest>,
 ) -> AppResult<Order> {
     eprintln!(
         "Creating order: merchant_id={}, shopify_order_id={}, name={:?}",
         payload.merchant_id, payload.shopify_order_id, payload.name
     );
 
+    // Resolve the merchant's configured currency
+    let merchant_currency = sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        SELECT shop_currency FROM merchants
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    // Orders must be in the merchant's currency; a missing currency defaults to it
+    let currency = match (payload.currency, merchant_currency) {
+        (Some(currency), merchant_currency) => {
+            validator::validate_currency(&currency)?;
+            if let Some(expected) = merchant_currency {
+                if currency != expected {
+                    return Err(AppError::Validation(format!(
+                        "Order currency {} does not match merchant currency {}",
+                        currency, expected
+                    )));
+                }
+            }
+            Some(currency)
+        }
+        (None, merchant_currency) => merchant_currency,
+    };
+
     // Check if order already exists
     let existing = sqlx::query_scalar::<_, Option<i64>>(
         r#"
@@ -137,13 +348,13 @@ async fn create_order(
     )
     .bind(payload.merchant_id)
     .bind(payload.shopify_order_id)
-    .fetch_optional(&ctx.db)
+    .fetch_optional(&mut *conn)
     .await?;
 
     eprintln!("Order existence check: {:?}", existing);
 
     if existing.is_some() {
-        return Err(AppError::Validation("Order already exists".to_string()));
+        return Err(AppError::Conflict("Order already exists".to_string()));
     }
 
     eprintln!("Inserting order into database...");
@@ -155,63 +366,147 @@ async fn create_order(
             total_shipping_price_set_amount, total_tax, financial_status
         )
         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-        RETURNING id, merchant_id, shopify_order_id, name, processed_at, currency,
+        RETURNING id, merchant_id, shopify_order_id, name, customer_email, processed_at, currency,
                   subtotal_price, total_price, total_discounts, 
                   total_shipping_price_set_amount, total_tax, financial_status,
-                  cancelled_at, created_at, updated_at
+                  cancelled_at, status, created_at, updated_at
         "#,
     )
     .bind(payload.merchant_id)
     .bind(payload.shopify_order_id)
     .bind(payload.name)
     .bind(payload.processed_at)
-    .bind(payload.currency)
+    .bind(currency)
     .bind(payload.subtotal_price)
     .bind(payload.total_price)
     .bind(payload.total_discounts)
     .bind(payload.total_shipping_price_set_amount)
     .bind(payload.total_tax)
     .bind(payload.financial_status)
-    .fetch_one(&ctx.db)
+    .fetch_one(&mut *conn)
     .await?;
 
     eprintln!("Order created successfully: id={}", order.id);
     Ok(Json(order))
 }
 
+/// Shopify financial statuses and the statuses each one may move to
+///
+/// Staying in the same status is always allowed, so re-sending the current
+/// value is a no-op rather than an error.
+const FINANCIAL_TRANSITIONS: &[(&str, &[&str])] = &[
+    (
+        "pending",
+        &["authorized", "partially_paid", "paid", "voided", "expired"],
+    ),
+    (
+        "authorized",
+        &["partially_paid", "paid", "voided", "expired"],
+    ),
+    (
+        "partially_paid",
+        &["paid", "partially_refunded", "refunded"],
+    ),
+    ("paid", &["partially_refunded", "refunded"]),
+    ("partially_refunded", &["refunded"]),
+    ("refunded", &[]),
+    ("voided", &[]),
+    ("expired", &[]),
+];
+
+/// Reject unknown statuses and moves the state machine doesn't allow
+///
+/// An order with no financial status yet may take any known status.
+fn check_financial_transition(from: Option<&str>, to: &str) -> Result<(), AppError> {
+    if !FINANCIAL_TRANSITIONS
+        .iter()
+        .any(|(status, _)| *status == to)
+    {
+        return Err(AppError::Validation(format!(
+            "unknown financial_status '{}'",
+            to
+        )));
+    }
+    let Some(from) = from else {
+        return Ok(());
+    };
+    let allowed = FINANCIAL_TRANSITIONS
+        .iter()
+        .find(|(status, _)| *status == from)
+        .is_some_and(|(_, next)| next.contains(&to));
+    if from == to || allowed {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "financial_status cannot change from '{}' to '{}'",
+            from, to
+        )))
+    }
+}
+
+// Update an order; `?force=true` (admins only) skips the financial status transition check
 async fn update_order(
-    Extension(ctx): Extension<ApiContext>,
+    user: Option<AuthenticatedUser>,
+    mut conn: DbConn,
     Path(id): Path<i64>,
-    Json(payload): Json<UpdateOrderRequest>,
+    Query(params): Query<UpdateOrderParams>,
+    JsonBody(payload): JsonBody<UpdateOrderRequest>,
 ) -> AppResult<Order> {
+    let force = params.force.unwrap_or(false);
     eprintln!(
-        "Updating order: id={}, name={:?}, financial_status={:?}",
-        id, payload.name, payload.financial_status
+        "Updating order: id={}, name={:?}, financial_status={:?}, force={}",
+        id, payload.name, payload.financial_status, force
     );
 
+    if force {
+        user.ok_or(AppError::Unauthorized)?
+            .require_scope(Scope::Admin)?;
+    }
+
+    let mut tx = conn.begin().await?;
+
+    if let Some(next) = payload.financial_status.as_deref() {
+        // Lock the row so a concurrent update can't slip a status in between check and write
+        let current: Option<String> = sqlx::query_scalar(
+            "SELECT financial_status FROM orders WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+        if force {
+            // Forcing skips the state machine, but never stores an unknown status
+            check_financial_transition(None, next)?;
+        } else {
+            check_financial_transition(current.as_deref(), next)?;
+        }
+    }
+
     let order = sqlx::query_as::<_, Order>(
         r#"
         UPDATE orders 
         SET 
             name = COALESCE($2, name),
             financial_status = COALESCE($3, financial_status),
-            cancelled_at = COALESCE($4, cancelled_at),
-            updated_at = NOW()
-        WHERE id = $1
-        RETURNING id, merchant_id, shopify_order_id, name, processed_at, currency,
+            cancelled_at = COALESCE($4, cancelled_at)
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, merchant_id, shopify_order_id, name, customer_email, processed_at, currency,
                   subtotal_price, total_price, total_discounts, 
                   total_shipping_price_set_amount, total_tax, financial_status,
-                  cancelled_at, created_at, updated_at
+                  cancelled_at, status, created_at, updated_at
         "#,
     )
     .bind(id)
     .bind(payload.name)
     .bind(payload.financial_status)
     .bind(payload.cancelled_at)
-    .fetch_optional(&ctx.db)
+    .fetch_optional(&mut *tx)
     .await?
     .ok_or(AppError::NotFound)?;
 
+    tx.commit().await?;
+
     eprintln!("Order updated successfully: id={}", order.id);
     Ok(Json(order))
 }
@@ -239,3 +534,404 @@ async fn delete_order(
     eprintln!("Order deleted successfully: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{database_url, insert_merchant, test_db, test_db_with};
+    use sqlx::postgres::PgPoolOptions;
+
+    fn refund(amount: &str) -> OrderRefund {
+        OrderRefund {
+            id: uuid::Uuid::new_v4(),
+            shopify_id: 1,
+            shopify_refund_id: 1,
+            amount: amount.parse().unwrap(),
+            reason: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn net_subtracts_every_refund_and_never_goes_negative() {
+        let total = Some("100.00".parse().unwrap());
+
+        let (refunded, net) = net_of_refunds(total, &[refund("10.00"), refund("15.50")]);
+        assert_eq!(refunded, "25.50".parse().unwrap());
+        assert_eq!(net, "74.50".parse().unwrap());
+
+        // Three thirds of a full refund, each stored with extra precision
+        let thirds = [refund("33.3334"), refund("33.3333"), refund("33.3334")];
+        let (_, net) = net_of_refunds(total, &thirds);
+        assert_eq!(net, Decimal::ZERO);
+
+        let (refunded, net) = net_of_refunds(None, &[]);
+        assert_eq!((refunded, net), (Decimal::ZERO, Decimal::ZERO));
+    }
+
+    #[test]
+    fn financial_status_follows_the_state_machine() {
+        for (from, to) in [
+            (None, "paid"),
+            (Some("pending"), "authorized"),
+            (Some("authorized"), "paid"),
+            (Some("paid"), "partially_refunded"),
+            (Some("partially_refunded"), "refunded"),
+            (Some("refunded"), "refunded"),
+        ] {
+            assert!(
+                check_financial_transition(from, to).is_ok(),
+                "{:?} -> {}",
+                from,
+                to
+            );
+        }
+
+        for (from, to) in [
+            (Some("refunded"), "pending"),
+            (Some("paid"), "authorized"),
+            (Some("voided"), "paid"),
+            (None, "settled"),
+        ] {
+            assert!(
+                matches!(
+                    check_financial_transition(from, to),
+                    Err(AppError::Validation(_))
+                ),
+                "{:?} -> {}",
+                from,
+                to
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn find_orders_combines_cancelled_and_financial_status_filters() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        // (shopify order id, financial status, cancelled)
+        for (shopify_order_id, financial_status, cancelled) in [
+            (1_i64, "paid", false),
+            (2, "paid", true),
+            (3, "refunded", true),
+            (4, "pending", false),
+        ] {
+            sqlx::query(
+                r#"
+                INSERT INTO orders (merchant_id, shopify_order_id, financial_status, cancelled_at)
+                VALUES ($1, $2, $3, CASE WHEN $4 THEN NOW() END)
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(shopify_order_id)
+            .bind(financial_status)
+            .bind(cancelled)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let cases = [
+            (None, None, vec![1, 2, 3, 4]),
+            (None, Some(true), vec![2, 3]),
+            (None, Some(false), vec![1, 4]),
+            (Some("paid"), Some(true), vec![2]),
+            (Some("paid"), Some(false), vec![1]),
+            (Some("refunded"), Some(false), vec![]),
+        ];
+        for (financial_status, cancelled, expected) in cases {
+            let params = ListOrdersParams {
+                merchant_id,
+                financial_status: financial_status.map(str::to_string),
+                cancelled,
+                tag: None,
+                customer_email: None,
+                limit: None,
+                offset: None,
+            };
+            let (orders, total) = find_orders(&mut tx, &params, 100, 0).await?;
+            let mut ids: Vec<i64> = orders.iter().map(|o| o.shopify_order_id).collect();
+            ids.sort();
+            assert_eq!(ids, expected, "{:?}/{:?}", financial_status, cancelled);
+            assert_eq!(total, expected.len() as i64);
+            for order in &orders {
+                let status = if order.cancelled_at.is_some() {
+                    "cancelled"
+                } else {
+                    "open"
+                };
+                assert_eq!(order.status, status);
+            }
+        }
+
+        // Setting cancelled_at, as update_order does, flips the derived status
+        let status: String = sqlx::query_scalar(
+            r#"
+            UPDATE orders SET cancelled_at = NOW()
+            WHERE merchant_id = $1 AND shopify_order_id = 1
+            RETURNING status
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(status, "cancelled");
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn paging_through_equal_sort_keys_returns_each_order_once() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        // No processed_at and one transaction, so every order has the same sort key
+        sqlx::query(
+            r#"
+            INSERT INTO orders (merchant_id, shopify_order_id)
+            SELECT $1, n FROM generate_series(1, 50) AS n
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let params = ListOrdersParams {
+            merchant_id,
+            financial_status: None,
+            cancelled: None,
+            tag: None,
+            customer_email: None,
+            limit: None,
+            offset: None,
+        };
+        let mut seen = Vec::new();
+        for offset in (0..50).step_by(7) {
+            let (orders, total) = find_orders(&mut tx, &params, 7, offset).await?;
+            assert_eq!(total, 50);
+            seen.extend(orders.iter().map(|o| o.shopify_order_id));
+        }
+        seen.sort();
+        assert_eq!(seen, (1..=50).collect::<Vec<i64>>());
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn orders_are_found_by_customer_email_ignoring_case() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        // The second order only has the email on its customer
+        for order in [
+            serde_json::json!({ "id": 1, "email": "Jane.Doe@Example.com" }),
+            serde_json::json!({
+                "id": 2,
+                "email": "",
+                "customer": { "id": 7, "email": "jane.doe@example.com" }
+            }),
+            serde_json::json!({ "id": 3, "email": "john@example.com" }),
+            serde_json::json!({ "id": 4, "email": "jane.doe@example.com.evil" }),
+        ] {
+            let order = serde_json::from_value(order)?;
+            crate::shopify::sync::upsert_order(&db, merchant_id, &order, &[], &[]).await?;
+        }
+
+        let search = |email: &str| ListOrdersParams {
+            merchant_id,
+            financial_status: None,
+            cancelled: None,
+            tag: None,
+            customer_email: Some(email.to_string()),
+            limit: None,
+            offset: None,
+        };
+        let mut conn = db.acquire().await?;
+        let (orders, total) =
+            find_orders(&mut conn, &search("JANE.DOE@example.COM"), 10, 0).await?;
+        let mut ids: Vec<i64> = orders.iter().map(|o| o.shopify_order_id).collect();
+        ids.sort();
+        let first_page = find_orders(&mut conn, &search("jane.doe@example.com"), 1, 0).await?;
+        let (unknown, _) = find_orders(&mut conn, &search("jane"), 10, 0).await?;
+        drop(conn);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(total, 2);
+        assert_eq!(
+            orders
+                .iter()
+                .find(|o| o.shopify_order_id == 2)
+                .and_then(|o| o.customer_email.as_deref()),
+            Some("jane.doe@example.com")
+        );
+        assert_eq!((first_page.0.len(), first_page.1), (1, 2));
+        assert!(unknown.is_empty());
+
+        // Only search results carry the email; `/orders` and `/orders/:id` don't
+        let order = orders.into_iter().next().unwrap();
+        assert!(serde_json::to_value(&order)?
+            .get("customer_email")
+            .is_none());
+        let found = serde_json::to_value(OrderSearchResult::from(order))?;
+        assert!(found["customer_email"].is_string());
+        assert!(found["shopify_id"].is_i64());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_order_acquires_one_connection() -> anyhow::Result<()> {
+        use axum::{body::Body, http::Request};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tower::ServiceExt;
+
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        // Every connection handed out, whether freshly opened or reused from idle
+        let acquires = Arc::new(AtomicUsize::new(0));
+        let (on_connect, on_acquire) = (acquires.clone(), acquires.clone());
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .after_connect(move |_, _| {
+                on_connect.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Ok(()) })
+            })
+            .before_acquire(move |_, _| {
+                on_acquire.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Ok(true) })
+            })
+            .connect(&database_url)
+            .await?;
+        sqlx::migrate!("./sql/migrations").run(&db).await?;
+        let merchant_id = insert_merchant(&db).await?;
+        let order_id: i64 = sqlx::query_scalar(
+            "INSERT INTO orders (merchant_id, shopify_order_id) VALUES ($1, 1) RETURNING id",
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await?;
+
+        // Order, refunds and fulfillments: three queries, one connection
+        // (three acquires when each query went to the pool)
+        acquires.store(0, Ordering::SeqCst);
+        let request = Request::builder()
+            .uri(format!("/api/v1/orders/{}", order_id))
+            .body(Body::empty())?;
+        let response = crate::http::test_router(db.clone())
+            .oneshot(request)
+            .await?;
+        let acquired = acquires.load(Ordering::SeqCst);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(acquired, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn internal_and_shopify_ids_are_never_confused() -> anyhow::Result<()> {
+        use axum::{body::Body, http::Request};
+        use tower::ServiceExt;
+
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let mut merchant_ids = Vec::new();
+        for _ in 0..2 {
+            let merchant_id = insert_merchant(&db).await?;
+            merchant_ids.push(merchant_id);
+        }
+
+        // Order B's Shopify id is order A's internal id, so a mix-up returns the wrong order
+        let insert =
+            "INSERT INTO orders (merchant_id, shopify_order_id) VALUES ($1, $2) RETURNING id";
+        let a: i64 = sqlx::query_scalar(insert)
+            .bind(merchant_ids[0])
+            .bind(450789469_i64)
+            .fetch_one(&db)
+            .await?;
+        let b: i64 = sqlx::query_scalar(insert)
+            .bind(merchant_ids[0])
+            .bind(a)
+            .fetch_one(&db)
+            .await?;
+
+        let app = crate::http::test_router(db.clone());
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (
+                    status,
+                    serde_json::from_slice::<serde_json::Value>(&bytes).ok(),
+                )
+            }
+        };
+
+        let (_, by_id) = get(format!("/api/v1/orders/{}", a)).await;
+        let (_, by_shopify_id) = get(format!(
+            "/api/v1/orders/by-shopify-id/{}?merchant_id={}",
+            a, merchant_ids[0]
+        ))
+        .await;
+        let (status, b_by_shopify_id) = get(format!(
+            "/api/v1/orders/by-shopify-id/450789469?merchant_id={}",
+            merchant_ids[0]
+        ))
+        .await;
+        let (other_merchant, _) = get(format!(
+            "/api/v1/orders/by-shopify-id/{}?merchant_id={}",
+            a, merchant_ids[1]
+        ))
+        .await;
+
+        sqlx::query("DELETE FROM merchants WHERE id = ANY($1)")
+            .bind(&merchant_ids)
+            .execute(&db)
+            .await?;
+
+        let by_id = by_id.unwrap();
+        assert_eq!(
+            (by_id["id"].as_i64(), by_id["shopify_id"].as_i64()),
+            (Some(a), Some(450789469))
+        );
+        let by_shopify_id = by_shopify_id.unwrap();
+        assert_eq!(
+            (
+                by_shopify_id["id"].as_i64(),
+                by_shopify_id["shopify_id"].as_i64()
+            ),
+            (Some(b), Some(a))
+        );
+        assert_eq!(status, axum::http::StatusCode::OK);
+        assert_eq!(b_by_shopify_id.unwrap()["id"].as_i64(), Some(a));
+        assert_eq!(other_merchant, axum::http::StatusCode::NOT_FOUND);
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+/// List endpoints with their own configurable page sizes, see `Args::page_limits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagedResource {
+    Products,
+    Orders,
+    Inventory,
+    Users,
+    Variants,
+    Checkouts,
+}
+
+/// Page size used when `limit` is omitted, and the largest page a client may request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    pub default_limit: i32,
+    pub max_limit: i32,
+}
+
+/// `limit`/`offset` for a list query after applying a resource's `PageLimits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub limit: i32,
+    pub offset: i32,
+}
+
+impl Pagination {
+    /// A missing `limit` falls back to the default; out-of-range values are clamped, never rejected
+    pub fn new(limit: Option<i32>, offset: Option<i32>, limits: PageLimits) -> Self {
+        Self {
+            limit: limit
+                .unwrap_or(limits.default_limit)
+                .clamp(1, limits.max_limit.max(1)),
+            offset: offset.unwrap_or(0).max(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: PageLimits = PageLimits {
+        default_limit: 50,
+        max_limit: 100,
+    };
+
+    #[test]
+    fn missing_limit_uses_the_resource_default() {
+        assert_eq!(
+            Pagination::new(None, None, LIMITS),
+            Pagination {
+                limit: 50,
+                offset: 0
+            }
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(Pagination::new(Some(500), None, LIMITS).limit, 100);
+        assert_eq!(Pagination::new(Some(0), None, LIMITS).limit, 1);
+        assert_eq!(Pagination::new(Some(20), Some(-5), LIMITS).offset, 0);
+    }
+}
@@ -0,0 +1,236 @@
+//! `Prefer: return=minimal` (RFC 7240) on create and update endpoints.
+//!
+//! With `return=minimal` a successful write answers `204 No Content` and a
+//! `Location` header pointing at the resource instead of echoing it back.
+//! `return=representation` is the default made explicit. Either way the choice is
+//! echoed in `Preference-Applied`. Routers opt in with `route_layer`, so POSTs
+//! that aren't resource writes (login, token refresh) never drop their body.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, Request},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+const PREFER: HeaderName = HeaderName::from_static("prefer");
+const PREFERENCE_APPLIED: HeaderName = HeaderName::from_static("preference-applied");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Return {
+    Minimal,
+    Representation,
+}
+
+impl Return {
+    fn header_value(self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            Return::Minimal => "return=minimal",
+            Return::Representation => "return=representation",
+        })
+    }
+}
+
+/// Honour `Prefer: return=...` on POST/PUT/PATCH; other methods pass straight through
+pub async fn return_preference(request: Request, next: Next) -> Response {
+    let preference = match *request.method() {
+        Method::POST | Method::PUT | Method::PATCH => return_preference_of(request.headers()),
+        _ => None,
+    };
+    let Some(preference) = preference else {
+        return next.run(request).await;
+    };
+
+    // Nested routers see their path with the prefix stripped; Location needs all of it
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path())
+        .trim_end_matches('/')
+        .to_string();
+    let is_create = request.method() == Method::POST;
+
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let mut response = match preference {
+        Return::Representation => response,
+        Return::Minimal => match minimal(response, &path, is_create).await {
+            Ok(response) => response,
+            Err(response) => return response,
+        },
+    };
+    response
+        .headers_mut()
+        .insert(PREFERENCE_APPLIED, preference.header_value());
+    response
+}
+
+/// Swap a JSON resource for `204` plus its `Location`
+///
+/// A create's resource lives at `{path}/{id}`; an update's at the path it was sent to.
+/// A response that isn't a JSON object with an `id` is returned untouched (as `Err`).
+async fn minimal(response: Response, path: &str, is_create: bool) -> Result<Response, Response> {
+    if !is_json(response.headers()) {
+        return Err(response);
+    }
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    let id = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(resource)) => match resource.get("id") {
+            Some(Value::String(id)) => Some(id.clone()),
+            Some(Value::Number(id)) => Some(id.to_string()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(id) = id else {
+        return Err(Response::from_parts(parts, Body::from(bytes)));
+    };
+
+    let location = if is_create {
+        format!("{}/{}", path, id)
+    } else {
+        path.to_string()
+    };
+    let Ok(location) = HeaderValue::from_str(&location) else {
+        return Err(Response::from_parts(parts, Body::from(bytes)));
+    };
+
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    response.headers_mut().insert(LOCATION, location);
+    // Keep anything else the handler set (e.g. cookies), minus the body's headers
+    for (name, value) in parts.headers.iter() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            response.headers_mut().append(name, value.clone());
+        }
+    }
+    Ok(response)
+}
+
+/// The `return` preference, if any; unknown preferences are ignored as RFC 7240 asks
+fn return_preference_of(headers: &HeaderMap) -> Option<Return> {
+    headers
+        .get_all(PREFER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        // Parameters after `;` don't apply to `return`
+        .filter_map(|preference| preference.split(';').next())
+        .find_map(|preference| {
+            let (name, value) = preference.split_once('=')?;
+            if !name.trim().eq_ignore_ascii_case("return") {
+                return None;
+            }
+            match value.trim().trim_matches('"').to_ascii_lowercase().as_str() {
+                "minimal" => Some(Return::Minimal),
+                "representation" => Some(Return::Representation),
+                _ => None,
+            }
+        })
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        middleware,
+        routing::{post, put},
+        Json, Router,
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/things",
+                post(|| async { Json(json!({ "id": "abc", "name": "Tee" })) })
+                    .get(|| async { Json(json!([])) }),
+            )
+            .route("/things/:id", put(|| async { Json(json!({ "id": 7 })) }))
+            .route("/login", post(|| async { Json(json!({ "token": "t" })) }))
+            .route_layer(middleware::from_fn(return_preference))
+    }
+
+    async fn send(method: &str, uri: &str, prefer: Option<&str>) -> Response {
+        let mut request = axum::http::Request::builder().method(method).uri(uri);
+        if let Some(prefer) = prefer {
+            request = request.header("Prefer", prefer);
+        }
+        app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn minimal_returns_no_content_with_location() {
+        let response = send("POST", "/things", Some("return=minimal")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(header(&response, "location"), Some("/things/abc"));
+        assert_eq!(
+            header(&response, "preference-applied"),
+            Some("return=minimal")
+        );
+        assert_eq!(header(&response, "content-type"), None);
+
+        let response = send("PUT", "/things/7", Some("respond-async, return=minimal")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(header(&response, "location"), Some("/things/7"));
+    }
+
+    #[tokio::test]
+    async fn representation_is_the_default() {
+        let response = send("POST", "/things", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "preference-applied"), None);
+
+        let response = send("POST", "/things", Some("return=representation")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            header(&response, "preference-applied"),
+            Some("return=representation")
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&bytes).unwrap()["name"],
+            "Tee"
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_and_bodies_without_an_id_are_untouched() {
+        let response = send("GET", "/things", Some("return=minimal")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "preference-applied"), None);
+
+        let response = send("POST", "/login", Some("return=minimal")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "preference-applied"), None);
+    }
+}
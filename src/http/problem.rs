@@ -0,0 +1,159 @@
+//! RFC 7807 problem details for clients that ask for them.
+//!
+//! A request whose `Accept` lists `application/problem+json` gets errors as
+//! `{type, title, status, detail, instance}` instead of `{error, message}`. The
+//! middleware stores the choice in a task-local and `AppError::into_response`
+//! reads it, so handlers don't need to know about it.
+
+use axum::{
+    extract::Request,
+    http::{header::ACCEPT, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::http::envelope::X_REQUEST_ID;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+tokio::task_local! {
+    /// `instance` for problem responses; only set when the client asked for them
+    static PROBLEM_INSTANCE: String;
+}
+
+/// Run the rest of the request with problem details on if `Accept` asks for them
+///
+/// `instance` is the caller's `X-Request-Id`, or a fresh UUID.
+pub async fn problem_details(request: Request, next: Next) -> Response {
+    if !accepts_problem_json(request.headers()) {
+        return next.run(request).await;
+    }
+    let instance = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    PROBLEM_INSTANCE.scope(instance, next.run(request)).await
+}
+
+/// The request's `instance` when errors should be problem details; `None` outside of a request
+pub fn current() -> Option<String> {
+    PROBLEM_INSTANCE.try_with(Clone::clone).ok()
+}
+
+/// Whether any media range in `Accept` is `application/problem+json` with a non-zero `q`
+fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default();
+            let refused = parts.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            media_type.eq_ignore_ascii_case(PROBLEM_JSON) && !refused
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::AppError;
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CONTENT_TYPE, StatusCode},
+        middleware,
+        response::IntoResponse,
+        routing::get,
+        Router,
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/missing",
+                get(|| async { AppError::NotFound.into_response() }),
+            )
+            .layer(middleware::from_fn(problem_details))
+    }
+
+    async fn send(headers: &[(&str, &str)]) -> (StatusCode, String, Value) {
+        let mut request = axum::http::Request::builder().uri("/missing");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let content_type = response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            content_type,
+            serde_json::from_slice(&bytes).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn errors_are_problem_details_only_when_accepted() {
+        let (status, content_type, body) = send(&[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(
+            body,
+            json!({ "error": "Resource not found", "message": "Resource not found" })
+        );
+
+        let accept = ("Accept", "application/json;q=0.9, application/problem+json");
+        let (status, content_type, body) = send(&[accept, ("X-Request-Id", "abc-123")]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, PROBLEM_JSON);
+        assert_eq!(
+            body,
+            json!({
+                "type": "about:blank",
+                "title": "Resource not found",
+                "status": 404,
+                "detail": "Resource not found",
+                "instance": "abc-123",
+            })
+        );
+
+        // Without X-Request-Id a fresh one is made up
+        let (_, _, body) = send(&[("Accept", "application/problem+json")]).await;
+        assert!(body["instance"].as_str().unwrap().parse::<Uuid>().is_ok());
+
+        let (_, content_type, _) = send(&[("Accept", "application/problem+json;q=0")]).await;
+        assert_eq!(content_type, "application/json");
+    }
+
+    #[tokio::test]
+    async fn field_errors_carry_over_as_an_extension_member() {
+        let fields = [("email".to_string(), vec!["format".to_string()])].into();
+        let response = PROBLEM_INSTANCE
+            .scope("req-1".to_string(), async {
+                AppError::FieldValidation(fields).into_response()
+            })
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["fields"], json!({ "email": ["format"] }));
+        assert_eq!(body["instance"], "req-1");
+    }
+}
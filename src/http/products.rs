@@ -1,46 +1,82 @@
-use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::auth::jkws::Scope;
+use crate::http::changes::{ChangeFeed, ChangesResponse, IdType};
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::prefer;
+use crate::http::variants::search_variants;
+use crate::http::{
+    auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, Filters, JsonBody,
+    PagedResource, Pagination, ReadPool,
+};
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
+    middleware,
     routing::get,
     Extension, Json, Router,
 };
+use sqlx::PgConnection;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
 
 pub fn products_router() -> Router {
     Router::new()
-        .route("/products", get(list_products).post(create_product))
+        .route(
+            "/products",
+            get(list_products)
+                .post(create_product)
+                .delete(delete_products),
+        )
         .route(
             "/products/:id",
             get(get_product).put(update_product).delete(delete_product),
         )
+        .route("/products/:id/variants", get(list_product_variants))
+        .route("/products/changes", get(product_changes))
+        // Prefer: return=minimal on create/update
+        .route_layer(middleware::from_fn(prefer::return_preference))
 }
 
 async fn list_products(
     Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
     Query(params): Query<ListProductsParams>,
-) -> AppResult<ProductListResponse> {
-    eprintln!("Listing products: merchant_id={}, limit={:?}, offset={:?}", 
-              params.merchant_id, params.limit, params.offset);
+) -> AppResult<ListResponse<ProductWithVariants>> {
+    eprintln!("Listing products: merchant_id={}, tag={:?}, limit={:?}, offset={:?}", 
+              params.merchant_id, params.tag, params.limit, params.offset);
     
-    let limit = params.limit.unwrap_or(50).min(100);
-    let offset = params.offset.unwrap_or(0);
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Products),
+    );
+    let mut conn = read_db.acquire().await?;
+
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and("deleted_at IS NULL", [])
+        .and_opt("product_type = {}", params.product_type.as_deref())
+        .and_opt("status = {}", params.status.as_deref())
+        .and_opt(
+            r#"EXISTS (
+                SELECT 1
+                FROM product_tags pt
+                JOIN tags t ON t.id = pt.tag_id
+                WHERE pt.product_id = products.id AND lower(t.name) = lower({})
+            )"#,
+            params.tag.as_deref(),
+        );
 
     // Get total count
-    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
-        r#"
-        SELECT COUNT(*) as count
-        FROM products 
-        WHERE merchant_id = $1 AND deleted_at IS NULL
-        "#,
-    )
-    .bind(params.merchant_id)
-    .fetch_one(&ctx.db)
-    .await?
-    .unwrap_or(0);
+    let total: i64 = filters
+        .count("FROM products")
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
 
     // Get products
-    let products = sqlx::query_as::<_, Product>(
-        r#"
+    let products = filters
+        .page(
+            r#"
         SELECT 
             id,
             merchant_id,
@@ -52,71 +88,60 @@ async fn list_products(
             updated_at,
             deleted_at
         FROM products
-        WHERE merchant_id = $1 
-            AND deleted_at IS NULL
-            AND ($2::text IS NULL OR product_type = $2)
-            AND ($3::text IS NULL OR status = $3)
-        ORDER BY updated_at DESC
-        LIMIT $4 OFFSET $5
         "#,
-    )
-    .bind(params.merchant_id)
-    .bind(params.product_type)
-    .bind(params.status)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(&ctx.db)
-    .await?;
-
-    // Get variants for each product
-    let mut products_with_variants = Vec::new();
-    for product in products {
-        let variants = sqlx::query_as::<_, Variant>(
-            r#"
-            SELECT 
-                id,
-                merchant_id,
-                shopify_variant_id,
-                shopify_product_id,
-                sku,
-                title,
-                barcode,
-                weight,
-                weight_unit,
-                created_at,
-                updated_at
-            FROM variants
-            WHERE merchant_id = $1 AND shopify_product_id = $2
-            ORDER BY created_at
-            "#,
+            "updated_at DESC",
+            "id DESC",
+            limit,
+            offset,
         )
-        .bind(product.merchant_id)
-        .bind(product.shopify_product_id)
-        .fetch_all(&ctx.db)
+        .build_query_as::<Product>()
+        .fetch_all(&mut *conn)
         .await?;
 
-        let product_with_variants = ProductWithVariants {
-            product: product.clone(),
-            variants,
-            variant_count: 0, // Will be set correctly when we have the actual count
-        };
+    // Attach variants and metafields for the whole page in one query each
+    let products_with_variants = attach_variants(&mut conn, products).await?;
 
-        products_with_variants.push(product_with_variants);
-    }
-
-    Ok(Json(ProductListResponse {
-        products: products_with_variants,
+    Ok(Json(ListResponse {
+        items: products_with_variants,
         total,
         limit,
         offset,
     }))
 }
 
-async fn get_product(
+/// Products changed since `since` or `cursor`, deleted ones included; see `http::changes`
+async fn product_changes(
+    user: AuthenticatedUser,
     Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ChangesParams>,
+) -> AppResult<ChangesResponse<Product>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+
+    let feed = ChangeFeed {
+        table: "products",
+        columns: "id, merchant_id, shopify_product_id, title, product_type, status, \
+            created_at, updated_at, deleted_at",
+        id_type: IdType::Uuid,
+    };
+    let mut conn = read_db.acquire().await?;
+    let changes = feed
+        .page(
+            &mut conn,
+            &params,
+            ctx.config.page_limits(PagedResource::Products),
+            ctx.config.change_feed_lag(),
+        )
+        .await?;
+    Ok(Json(changes))
+}
+
+async fn get_product(
+    ReadPool(read_db): ReadPool,
     Path(id): Path<uuid::Uuid>,
 ) -> AppResult<ProductWithVariants> {
     eprintln!("Getting product: id={}", id);
+    let mut conn = read_db.acquire().await?;
     
     // Get product
     let product = sqlx::query_as::<_, Product>(
@@ -136,14 +161,105 @@ async fn get_product(
         "#,
     )
     .bind(id)
-    .fetch_optional(&ctx.db)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let product_with_variants = attach_variants(&mut conn, vec![product])
+        .await?
+        .pop()
+        .ok_or(AppError::NotFound)?;
+
+    Ok(Json(product_with_variants))
+}
+
+// Page through one product's variants, for products with more than fit inline
+// Another merchant's product answers 404, the same as one that doesn't exist.
+async fn list_product_variants(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ProductVariantsParams>,
+) -> AppResult<ListResponse<Variant>> {
+    eprintln!(
+        "Listing product variants: id={}, limit={:?}, offset={:?}",
+        id, params.limit, params.offset
+    );
+
+    let (merchant_id, shopify_product_id): (Uuid, i64) = sqlx::query_as(
+        "SELECT merchant_id, shopify_product_id FROM products WHERE id = $1 AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .fetch_optional(&read_db)
     .await?
     .ok_or(AppError::NotFound)?;
+    ensure_own_merchant(&ctx, &user, merchant_id)
+        .await
+        .map_err(|e| match e {
+            AppError::Forbidden => AppError::NotFound,
+            e => e,
+        })?;
 
-    // Get variants
-    let variants = sqlx::query_as::<_, Variant>(
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Variants),
+    );
+    let search = ListVariantsParams {
+        merchant_id,
+        sku: None,
+        barcode: None,
+        exact: None,
+        shopify_product_id: Some(shopify_product_id),
+        limit: None,
+        offset: None,
+    };
+    let mut conn = read_db.acquire().await?;
+    let (variants, total) = search_variants(&mut conn, &search, limit, offset).await?;
+
+    Ok(Json(ListResponse {
+        items: variants,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+/// Most variants returned inline per product; the rest are paged via `/products/:id/variants`
+const INLINE_VARIANT_LIMIT: i64 = 100;
+
+/// A variant row plus how many variants its product has in total
+#[derive(sqlx::FromRow)]
+struct InlineVariant {
+    #[sqlx(flatten)]
+    variant: Variant,
+    variant_count: i64,
+}
+
+/// Load variants, metafields and the primary image for a page of products
+///
+/// Issues a single query per child table (`ANY($1)`) and groups the rows in
+/// memory, so the cost stays constant regardless of how many products are on
+/// the page. Products are returned in the order they were passed in.
+///
+/// At most `INLINE_VARIANT_LIMIT` variants are attached per product;
+/// `variant_count` is counted by the same query, so it is the full total either way.
+pub async fn attach_variants(
+    conn: &mut PgConnection,
+    products: Vec<Product>,
+) -> Result<Vec<ProductWithVariants>, AppError> {
+    if products.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let merchant_ids: Vec<Uuid> = products.iter().map(|p| p.merchant_id).collect();
+    let shopify_product_ids: Vec<i64> = products.iter().map(|p| p.shopify_product_id).collect();
+    let product_ids: Vec<Uuid> = products.iter().map(|p| p.id).collect();
+
+    let variants = sqlx::query_as::<_, InlineVariant>(
         r#"
-        SELECT 
+        SELECT
             id,
             merchant_id,
             shopify_variant_id,
@@ -154,34 +270,111 @@ async fn get_product(
             weight,
             weight_unit,
             created_at,
-            updated_at
-        FROM variants
-        WHERE merchant_id = $1 AND shopify_product_id = $2
-        ORDER BY created_at
+            updated_at,
+            variant_count
+        FROM (
+            SELECT
+                id,
+                merchant_id,
+                shopify_variant_id,
+                shopify_product_id,
+                sku,
+                title,
+                barcode,
+                weight::float8 AS weight,
+                weight_unit,
+                created_at,
+                updated_at,
+                COUNT(*) OVER product_variants AS variant_count,
+                ROW_NUMBER() OVER (product_variants ORDER BY created_at, id) AS position
+            FROM variants
+            WHERE merchant_id = ANY($1) AND shopify_product_id = ANY($2)
+            WINDOW product_variants AS (PARTITION BY merchant_id, shopify_product_id)
+        ) v
+        WHERE position <= $3
+        ORDER BY created_at, id
         "#,
     )
-    .bind(product.merchant_id)
-    .bind(product.shopify_product_id)
-    .fetch_all(&ctx.db)
+    .bind(&merchant_ids)
+    .bind(&shopify_product_ids)
+    .bind(INLINE_VARIANT_LIMIT)
+    .fetch_all(&mut *conn)
     .await?;
 
-    let product_with_variants = ProductWithVariants {
-        product: product.clone(),
-        variants: variants.clone(),
-        variant_count: variants.len() as i64,
-    };
+    let metafields = sqlx::query_as::<_, (Uuid, String, String, Option<String>)>(
+        r#"
+        SELECT product_id, namespace, key, value
+        FROM product_metafields
+        WHERE product_id = ANY($1)
+        ORDER BY namespace, key
+        "#,
+    )
+    .bind(&product_ids)
+    .fetch_all(&mut *conn)
+    .await?;
 
-    Ok(Json(product_with_variants))
+    let mut primary_images: HashMap<Uuid, String> = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT DISTINCT ON (product_id) product_id, src
+        FROM product_images
+        WHERE product_id = ANY($1)
+        ORDER BY product_id, position
+        "#,
+    )
+    .bind(&product_ids)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut variants_by_product: HashMap<(Uuid, i64), (i64, Vec<Variant>)> = HashMap::new();
+    for InlineVariant {
+        variant,
+        variant_count,
+    } in variants
+    {
+        let (count, variants) = variants_by_product
+            .entry((variant.merchant_id, variant.shopify_product_id))
+            .or_default();
+        *count = variant_count;
+        variants.push(variant);
+    }
+
+    let mut metafields_by_product: HashMap<Uuid, BTreeMap<String, String>> = HashMap::new();
+    for (product_id, namespace, key, value) in metafields {
+        metafields_by_product
+            .entry(product_id)
+            .or_default()
+            .insert(format!("{}.{}", namespace, key), value.unwrap_or_default());
+    }
+
+    Ok(products
+        .into_iter()
+        .map(|product| {
+            let (variant_count, variants) = variants_by_product
+                .remove(&(product.merchant_id, product.shopify_product_id))
+                .unwrap_or_default();
+            let metafields = metafields_by_product.remove(&product.id).unwrap_or_default();
+            ProductWithVariants {
+                variant_count,
+                primary_image_url: primary_images.remove(&product.id),
+                product,
+                variants,
+                metafields,
+            }
+        })
+        .collect())
 }
 
 async fn create_product(
     Extension(ctx): Extension<ApiContext>,
-    Json(payload): Json<CreateProductRequest>,
+    JsonBody(payload): JsonBody<CreateProductRequest>,
 ) -> AppResult<Product> {
     eprintln!("Creating product: merchant_id={}, shopify_product_id={}, title={:?}", 
               payload.merchant_id, payload.shopify_product_id, payload.title);
     
-    // Check if product already exists
+    // Check if product already exists; a soft-deleted one doesn't count and is
+    // kept as history next to the new row (see migration 020)
     let existing = sqlx::query_scalar::<_, Option<uuid::Uuid>>(
         r#"
         SELECT id FROM products 
@@ -196,7 +389,7 @@ async fn create_product(
     eprintln!("Product existence check: {:?}", existing);
 
     if existing.is_some() {
-        return Err(AppError::Validation("Product already exists".to_string()));
+        return Err(AppError::Conflict("Product already exists".to_string()));
     }
 
     eprintln!("Inserting product into database...");
@@ -222,27 +415,30 @@ async fn create_product(
 async fn update_product(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<uuid::Uuid>,
-    Json(payload): Json<UpdateProductRequest>,
+    JsonBody(payload): JsonBody<UpdateProductRequest>,
 ) -> AppResult<Product> {
     eprintln!("Updating product: id={}, title={:?}, product_type={:?}, status={:?}", 
               id, payload.title, payload.product_type, payload.status);
     
+    // Each field is (present?, new value) so an explicit null can clear the column
     let product = sqlx::query_as::<_, Product>(
         r#"
         UPDATE products 
         SET 
-            title = COALESCE($2, title),
-            product_type = COALESCE($3, product_type),
-            status = COALESCE($4, status),
-            updated_at = NOW()
+            title = CASE WHEN $2 THEN $3 ELSE title END,
+            product_type = CASE WHEN $4 THEN $5 ELSE product_type END,
+            status = CASE WHEN $6 THEN $7 ELSE status END
         WHERE id = $1 AND deleted_at IS NULL
         RETURNING id, merchant_id, shopify_product_id, title, product_type, status, created_at, updated_at, deleted_at
         "#,
     )
     .bind(id)
-    .bind(payload.title)
-    .bind(payload.product_type)
-    .bind(payload.status)
+    .bind(!payload.title.is_missing())
+    .bind(payload.title.into_option())
+    .bind(!payload.product_type.is_missing())
+    .bind(payload.product_type.into_option())
+    .bind(!payload.status.is_missing())
+    .bind(payload.status.into_option())
     .fetch_optional(&ctx.db)
     .await?
     .ok_or(AppError::NotFound)?;
@@ -259,7 +455,7 @@ async fn delete_product(
     let result = sqlx::query(
         r#"
         UPDATE products 
-        SET deleted_at = NOW(), updated_at = NOW()
+        SET deleted_at = NOW()
         WHERE id = $1 AND deleted_at IS NULL
         "#,
     )
@@ -272,4 +468,618 @@ async fn delete_product(
     }
 
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
+
+// Soft-delete every product matching the filters in one statement (ADMIN ONLY, own merchant only)
+// At least one filter besides merchant_id is required so a bare call can't empty the catalog.
+async fn delete_products(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<BulkDeleteProductsParams>,
+) -> AppResult<BulkDeleteResponse> {
+    user.require_scope(Scope::Admin)?;
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+
+    if params.product_type.is_none() && params.status.is_none() {
+        return Err(AppError::Validation(
+            "at least one of product_type or status is required".to_string(),
+        ));
+    }
+    let dry_run = params.dry_run.unwrap_or(false);
+    eprintln!(
+        "Bulk deleting products: merchant_id={}, product_type={:?}, status={:?}, dry_run={}",
+        params.merchant_id, params.product_type, params.status, dry_run
+    );
+
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and("deleted_at IS NULL", [])
+        .and_opt("product_type = {}", params.product_type.as_deref())
+        .and_opt("status = {}", params.status.as_deref());
+
+    let deleted = if dry_run {
+        let count: i64 = filters
+            .count("FROM products")
+            .build_query_scalar()
+            .fetch_one(&ctx.db)
+            .await?;
+        count as u64
+    } else {
+        filters
+            .query("UPDATE products SET deleted_at = NOW()")
+            .build()
+            .execute(&ctx.db)
+            .await?
+            .rows_affected()
+    };
+
+    Ok(Json(BulkDeleteResponse { deleted, dry_run }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, insert_user, send, test_db, test_db_with};
+    use sqlx::postgres::PgPoolOptions;
+
+    async fn table_scans(conn: &mut PgConnection, table: &str) -> anyhow::Result<i64> {
+        let scans = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COALESCE(seq_scan, 0) + COALESCE(idx_scan, 0)
+            FROM pg_stat_xact_user_tables
+            WHERE relname = $1
+            "#,
+        )
+        .bind(table)
+        .fetch_one(conn)
+        .await?;
+        Ok(scans)
+    }
+
+    #[tokio::test]
+    async fn attach_variants_runs_one_query_per_table() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        // Force sequential scans so each statement counts as exactly one table scan
+        sqlx::query("SET LOCAL enable_indexscan = off")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("SET LOCAL enable_bitmapscan = off")
+            .execute(&mut *tx)
+            .await?;
+
+        let merchant_id = insert_merchant(&mut *tx).await?;
+
+        let mut products = Vec::new();
+        for shopify_product_id in 1..=5_i64 {
+            let product = sqlx::query_as::<_, Product>(
+                r#"
+                INSERT INTO products (merchant_id, shopify_product_id, title)
+                VALUES ($1, $2, 'Test product')
+                RETURNING id, merchant_id, shopify_product_id, title, product_type, status, created_at, updated_at, deleted_at
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(shopify_product_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            for variant in 0..2_i64 {
+                sqlx::query(
+                    r#"
+                    INSERT INTO variants (merchant_id, shopify_variant_id, shopify_product_id, weight)
+                    VALUES ($1, $2, $3, 1.5)
+                    "#,
+                )
+                .bind(merchant_id)
+                .bind(shopify_product_id * 100 + variant)
+                .bind(shopify_product_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            // Odd products get two images, stored out of order; even ones have none
+            if shopify_product_id % 2 == 1 {
+                for position in [2, 1] {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO product_images (merchant_id, product_id, shopify_image_id, src, position)
+                        VALUES ($1, $2, $3, $4, $3)
+                        "#,
+                    )
+                    .bind(merchant_id)
+                    .bind(product.id)
+                    .bind(position)
+                    .bind(format!("https://cdn.test/{}-{}.jpg", shopify_product_id, position))
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+            products.push(product);
+        }
+
+        let variant_scans = table_scans(&mut tx, "variants").await?;
+        let metafield_scans = table_scans(&mut tx, "product_metafields").await?;
+        let image_scans = table_scans(&mut tx, "product_images").await?;
+
+        let loaded = attach_variants(&mut tx, products).await?;
+
+        assert_eq!(table_scans(&mut tx, "variants").await? - variant_scans, 1);
+        assert_eq!(
+            table_scans(&mut tx, "product_metafields").await? - metafield_scans,
+            1
+        );
+        assert_eq!(
+            table_scans(&mut tx, "product_images").await? - image_scans,
+            1
+        );
+        assert_eq!(loaded.len(), 5);
+        for product in &loaded {
+            assert_eq!(product.variant_count, 2);
+            let shopify_product_id = product.product.shopify_product_id;
+            let expected_image = (shopify_product_id % 2 == 1)
+                .then(|| format!("https://cdn.test/{}-1.jpg", shopify_product_id));
+            assert_eq!(product.primary_image_url, expected_image);
+            assert!(product
+                .variants
+                .iter()
+                .all(|v| v.shopify_product_id == product.product.shopify_product_id));
+        }
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn variant_count_includes_variants_beyond_the_inline_limit() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        let products = sqlx::query_as::<_, Product>(
+            r#"
+            INSERT INTO products (merchant_id, shopify_product_id, title)
+            VALUES ($1, 1, 'Many variants'), ($1, 2, 'No variants')
+            RETURNING id, merchant_id, shopify_product_id, title, product_type, status, created_at, updated_at, deleted_at
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO variants (merchant_id, shopify_variant_id, shopify_product_id)
+            SELECT $1, n, 1 FROM generate_series(1, $2) AS n
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(INLINE_VARIANT_LIMIT + 5)
+        .execute(&mut *tx)
+        .await?;
+
+        let loaded = attach_variants(&mut tx, products).await?;
+
+        assert_eq!(loaded[0].variants.len() as i64, INLINE_VARIANT_LIMIT);
+        assert_eq!(loaded[0].variant_count, INLINE_VARIANT_LIMIT + 5);
+        assert!(loaded[1].variants.is_empty());
+        assert_eq!(loaded[1].variant_count, 0);
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn updating_a_product_bumps_updated_at() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        let stale = "2000-01-01T00:00:00Z".parse::<chrono::DateTime<chrono::Utc>>()?;
+        let product_id: uuid::Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO products (merchant_id, shopify_product_id, title, updated_at)
+            VALUES ($1, 1, 'Before', $2)
+            RETURNING id
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(stale)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // No updated_at in the SET list; the trigger from migration 009 fills it in
+        let updated_at: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
+            "UPDATE products SET title = 'After' WHERE id = $1 RETURNING updated_at",
+        )
+        .bind(product_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.rollback().await?;
+
+        assert!(updated_at > stale);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn product_crud_over_http() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let app = crate::http::test_router(db.clone());
+
+        // Create
+        let tee = serde_json::json!({
+            "merchant_id": merchant_id,
+            "shopify_product_id": 1,
+            "title": "Tee",
+            "product_type": "Shirts",
+            "status": "active"
+        });
+        let (status, created) =
+            send(&app, "POST", "/api/v1/products", None, Some(tee.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(created["title"], "Tee");
+        let id = created["id"].as_str().unwrap().to_string();
+
+        // The same Shopify product twice is a conflict
+        let (status, body) = send(&app, "POST", "/api/v1/products", None, Some(tee)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "Product already exists");
+
+        let mug = serde_json::json!({
+            "merchant_id": merchant_id,
+            "shopify_product_id": 2,
+            "title": "Mug",
+            "product_type": "Mugs",
+            "status": "draft"
+        });
+        let (status, _) = send(&app, "POST", "/api/v1/products", None, Some(mug)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Prefer: return=minimal answers with where the new product lives instead
+        let cup = serde_json::json!({ "merchant_id": merchant_id, "shopify_product_id": 3 });
+        let request = axum::http::Request::post("/api/v1/products")
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .header("Prefer", "return=minimal")
+            .body(axum::body::Body::from(cup.to_string()))?;
+        let response = tower::ServiceExt::oneshot(app.clone(), request).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let location = response.headers()["location"].to_str()?.to_string();
+        assert!(location.starts_with("/api/v1/products/"), "{}", location);
+        let (status, _) = send(&app, "DELETE", &location, None, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // Read
+        let (status, product) =
+            send(&app, "GET", &format!("/api/v1/products/{}", id), None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(product["shopify_id"], 1);
+        assert_eq!(product["shopify_product_id"], 1); // Deprecated alias
+        assert_eq!(product["variant_count"], 0);
+        assert!(product["created_at"].is_string());
+
+        // Timestamps as Unix seconds on request
+        let uri = format!("/api/v1/products/{}?ts_format=epoch", id);
+        let (status, product) = send(&app, "GET", &uri, None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(product["created_at"].is_i64());
+        assert!(product["deleted_at"].is_null());
+        let uri = format!("/api/v1/products/{}?ts_format=unix", id);
+        let (status, _) = send(&app, "GET", &uri, None, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // List with filters; total counts only the matching products
+        for (filter, expected) in [
+            ("", vec!["Mug", "Tee"]),
+            ("&product_type=Shirts", vec!["Tee"]),
+            ("&status=draft", vec!["Mug"]),
+        ] {
+            let uri = format!("/api/v1/products?merchant_id={}{}", merchant_id, filter);
+            let (status, page) = send(&app, "GET", &uri, None, None).await;
+            assert_eq!(status, StatusCode::OK);
+            let mut titles: Vec<_> = page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["title"].as_str().unwrap())
+                .collect();
+            titles.sort();
+            assert_eq!(titles, expected, "{}", filter);
+            assert_eq!(page["total"], expected.len());
+        }
+
+        // Update: a changed title, a cleared product_type, an untouched status
+        let patch = serde_json::json!({ "title": "Tee v2", "product_type": null });
+        let uri = format!("/api/v1/products/{}", id);
+        let (status, updated) = send(&app, "PUT", &uri, None, Some(patch)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["title"], "Tee v2");
+        assert_eq!(updated["product_type"], serde_json::Value::Null);
+        assert_eq!(updated["status"], "active");
+
+        // Delete, after which the product is gone
+        let (status, _) = send(&app, "DELETE", &uri, None, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        for method in ["GET", "DELETE"] {
+            let (status, _) = send(&app, method, &uri, None, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", method);
+        }
+        let missing = format!("/api/v1/products/{}", Uuid::new_v4());
+        let (status, body) = send(&app, "GET", &missing, None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Resource not found");
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_needs_a_filter_and_supports_dry_run() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO products (merchant_id, shopify_product_id, product_type, status)
+            VALUES ($1, 1, 'Shirts', 'active'), ($1, 2, 'Shirts', 'draft'), ($1, 3, 'Mugs', 'draft')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let app = crate::http::test_router(db.clone());
+        let mut tokens = HashMap::new();
+        for role in ["admin", "viewer"] {
+            let (_, email) = insert_user(&db, merchant_id, role).await?;
+            let login = serde_json::json!({ "email": email, "password": "password" });
+            let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+            let token = session["data"]["access_token"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            tokens.insert(role, token);
+        }
+        let uri = |filter: &str| format!("/api/v1/products?merchant_id={}{}", merchant_id, filter);
+        let admin = Some(tokens["admin"].as_str());
+
+        let (status, _) = send(&app, "DELETE", &uri("&product_type=Shirts"), None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let viewer = Some(tokens["viewer"].as_str());
+        let (status, _) = send(&app, "DELETE", &uri("&product_type=Shirts"), viewer, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, "DELETE", &uri(""), admin, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A dry run counts without deleting
+        let dry_run = uri("&product_type=Shirts&status=draft&dry_run=true");
+        let (status, body) = send(&app, "DELETE", &dry_run, admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({ "deleted": 1, "dry_run": true }));
+        let (_, page) = send(&app, "GET", &uri(""), None, None).await;
+        assert_eq!(page["total"], 3);
+
+        let (status, body) = send(&app, "DELETE", &uri("&product_type=Shirts"), admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({ "deleted": 2, "dry_run": false }));
+        let (_, page) = send(&app, "GET", &uri(""), None, None).await;
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["shopify_id"], 3);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn product_variants_are_paged_and_scoped_to_the_merchant() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let app = crate::http::test_router(db.clone());
+        let mut merchants = Vec::new();
+        let mut tokens = Vec::new();
+        for _ in 0..2 {
+            let merchant_id = insert_merchant(&db).await?;
+            let (_, email) = insert_user(&db, merchant_id, "viewer").await?;
+            let login = serde_json::json!({ "email": email, "password": "password" });
+            let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+            let token = session["data"]["access_token"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            merchants.push(merchant_id);
+            tokens.push(token);
+        }
+        let product_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO products (merchant_id, shopify_product_id) VALUES ($1, 1) RETURNING id",
+        )
+        .bind(merchants[0])
+        .fetch_one(&db)
+        .await?;
+        // Three variants on this product, one on another product of the same merchant
+        for (shopify_variant_id, shopify_product_id, sku) in
+            [(1, 1, "TEE-S"), (2, 1, "TEE-M"), (3, 1, "TEE-L"), (4, 2, "MUG")]
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO variants (merchant_id, shopify_variant_id, shopify_product_id, sku)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(merchants[0])
+            .bind(shopify_variant_id)
+            .bind(shopify_product_id as i64)
+            .bind(sku)
+            .execute(&db)
+            .await?;
+        }
+        let uri = |query: &str| format!("/api/v1/products/{}/variants{}", product_id, query);
+        let owner = Some(tokens[0].as_str());
+
+        let (status, _) = send(&app, "GET", &uri(""), None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, page) = send(&app, "GET", &uri("?limit=2"), owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["limit"], 2);
+        let skus: Vec<_> = page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["sku"].as_str().unwrap())
+            .collect();
+        assert_eq!(skus, ["TEE-L", "TEE-M"]);
+        let (status, page) = send(&app, "GET", &uri("?limit=2&offset=2"), owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+        assert_eq!(page["items"][0]["sku"], "TEE-S");
+
+        // Another merchant's product looks the same as a missing one
+        let other = Some(tokens[1].as_str());
+        let (status, _) = send(&app, "GET", &uri(""), other, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let missing = format!("/api/v1/products/{}/variants", Uuid::new_v4());
+        let (status, _) = send(&app, "GET", &missing, owner, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        sqlx::query("DELETE FROM merchants WHERE id = ANY($1)")
+            .bind(&merchants)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn product_changes_include_deletions_and_continue_from_the_cursor() -> anyhow::Result<()>
+    {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        // Two routers on the same keys: the default one holds back changes younger than
+        // CHANGE_FEED_LAG_MS, so the rest of the test reads through one without the lag
+        let lagged = crate::http::test_context(db.clone(), db.clone());
+        let ctx = ApiContext {
+            config: std::sync::Arc::new(crate::Args {
+                change_feed_lag_ms: 0,
+                ..Default::default()
+            }),
+            ..lagged.clone()
+        };
+        let lagged_app = crate::http::api_router().layer(Extension(lagged));
+        let app = crate::http::api_router().layer(Extension(ctx));
+        let mut merchants = Vec::new();
+        for _ in 0..2 {
+            let merchant_id = insert_merchant(&db).await?;
+            merchants.push(merchant_id);
+        }
+        let (_, email) = insert_user(&db, merchants[0], "viewer").await?;
+        let login = serde_json::json!({ "email": email, "password": "password" });
+        let (_, session) = send(&app, "POST", "/api/v1/login", None, Some(login)).await;
+        let token = session["data"]["access_token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let owner = Some(token.as_str());
+
+        for shopify_product_id in 1..=3i64 {
+            sqlx::query("INSERT INTO products (merchant_id, shopify_product_id) VALUES ($1, $2)")
+                .bind(merchants[0])
+                .bind(shopify_product_id)
+                .execute(&db)
+                .await?;
+        }
+        let uri = |query: &str| {
+            format!(
+                "/api/v1/products/changes?merchant_id={}{}",
+                merchants[0], query
+            )
+        };
+        let after = |cursor: &str| uri(&format!("&cursor={}", cursor));
+        let shopify_ids = |page: &serde_json::Value| -> Vec<i64> {
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["shopify_id"].as_i64().unwrap())
+                .collect()
+        };
+
+        // Just written, so possibly still uncommitted as far as the feed knows
+        let (status, page) = send(&lagged_app, "GET", &uri(""), owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(shopify_ids(&page), Vec::<i64>::new());
+        assert_eq!(page["next_cursor"], serde_json::Value::Null);
+
+        let (status, page) = send(&app, "GET", &uri("&limit=2"), owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(shopify_ids(&page), [1, 2]);
+        assert_eq!(page["has_more"], true);
+        assert_eq!(page["items"][0]["deleted"], false);
+        let cursor = page["next_cursor"].as_str().unwrap().to_string();
+        let (status, page) = send(&app, "GET", &after(&cursor), owner, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(shopify_ids(&page), [3]);
+        assert_eq!(page["has_more"], false);
+        let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+        // A soft delete bumps updated_at, so the deletion shows up after the cursor
+        sqlx::query(
+            "UPDATE products SET deleted_at = NOW() WHERE merchant_id = $1 AND shopify_product_id = 1",
+        )
+        .bind(merchants[0])
+        .execute(&db)
+        .await?;
+        let (_, page) = send(&app, "GET", &after(&cursor), owner, None).await;
+        assert_eq!(shopify_ids(&page), [1]);
+        assert_eq!(page["items"][0]["deleted"], true);
+        let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+        // Nothing new: the same cursor comes back
+        let (_, page) = send(&app, "GET", &after(&cursor), owner, None).await;
+        assert_eq!(shopify_ids(&page), Vec::<i64>::new());
+        assert_eq!(page["next_cursor"], cursor.as_str());
+
+        let since = uri("&since=2100-01-01T00:00:00Z");
+        let (_, page) = send(&app, "GET", &since, owner, None).await;
+        assert_eq!(shopify_ids(&page), Vec::<i64>::new());
+        assert_eq!(page["next_cursor"], serde_json::Value::Null);
+
+        let (status, _) = send(&app, "GET", &uri("&cursor=garbage"), owner, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let other = format!("/api/v1/products/changes?merchant_id={}", merchants[1]);
+        let (status, _) = send(&app, "GET", &other, owner, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, "GET", &uri(""), None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Orders and inventory items have the same feed
+        for feed in ["orders", "inventory"] {
+            let uri = format!("/api/v1/{}/changes?merchant_id={}", feed, merchants[0]);
+            let (status, page) = send(&app, "GET", &uri, owner, None).await;
+            assert_eq!(status, StatusCode::OK, "{}", feed);
+            assert_eq!(page["items"], serde_json::json!([]), "{}", feed);
+        }
+
+        sqlx::query("DELETE FROM merchants WHERE id = ANY($1)")
+            .bind(&merchants)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::Request,
+    http::{header::RETRY_AFTER, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+
+use crate::http::auth::{access_token, VerifiedAccessToken};
+use crate::http::{ApiContext, AppError, ClientIp};
+
+const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+/// Idle buckets are pruned once this many clients are being tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Never limited: Shopify sends every merchant's webhooks from a few shared IPs, and
+/// the gateway calls verify-batch on behalf of all of its clients
+const UNLIMITED_PATHS: [&str; 2] = ["/api/v1/webhooks/shopify", "/api/v1/auth/verify-batch"];
+
+/// In-memory token buckets, one per client key
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: DashMap<String, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed { remaining: u32 },
+    Limited { retry_after: Duration },
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        Self {
+            per_second: per_second.max(1) as f64,
+            burst: burst.max(1) as f64,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Take one token from `key`'s bucket, refilling it for the time since its last request
+    pub fn check(&self, key: &str) -> RateLimitDecision {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> RateLimitDecision {
+        if self.buckets.len() > PRUNE_THRESHOLD {
+            self.prune(now);
+        }
+
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(&bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            RateLimitDecision::Allowed {
+                remaining: bucket.tokens.floor() as u32,
+            }
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.per_second;
+            RateLimitDecision::Limited {
+                retry_after: Duration::from_secs_f64(wait),
+            }
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_second).min(self.burst)
+    }
+
+    // A full bucket behaves exactly like a missing one, so those are safe to drop
+    fn prune(&self, now: Instant) {
+        self.buckets
+            .retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+    }
+}
+
+/// Per-client rate limiting, keyed by the verified token's `sub` or the client IP
+///
+/// Enabled when `RATE_LIMIT_PER_SECOND` is above 0. Over-limit requests get a 429 with
+/// `Retry-After`; other responses outside `UNLIMITED_PATHS` carry `X-RateLimit-Remaining`. The verified
+/// token is passed on to `AuthenticatedUser`, so it is only verified once.
+pub async fn rate_limit(mut request: Request, next: Next) -> Response {
+    if UNLIMITED_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let Some(ctx) = request.extensions().get::<ApiContext>().cloned() else {
+        return next.run(request).await;
+    };
+    let key = client_key(&ctx, &mut request);
+
+    match ctx.rate_limiter.check(&key) {
+        RateLimitDecision::Allowed { remaining } => {
+            let mut response = next.run(request).await;
+            response
+                .headers_mut()
+                .insert(X_RATELIMIT_REMAINING, HeaderValue::from(remaining));
+            response
+        }
+        RateLimitDecision::Limited { retry_after } => {
+            eprintln!("Rate limit exceeded for {}", key);
+            let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = AppError::RateLimited.into_response();
+            let headers = response.headers_mut();
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+            headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(0));
+            response
+        }
+    }
+}
+
+fn client_key(ctx: &ApiContext, request: &mut Request) -> String {
+    let claims = access_token(request.headers(), &ctx.config.access_token_cookie)
+        .and_then(|token| ctx.auth_service.verify_access_token(token).ok());
+    let subject = claims.as_ref().map(|claims| claims.sub.clone());
+    if let Some(claims) = claims {
+        request.extensions_mut().insert(VerifiedAccessToken(claims));
+    }
+
+    match subject {
+        Some(sub) => format!("sub:{}", sub),
+        None => {
+            let ip = request
+                .extensions()
+                .get::<ClientIp>()
+                .map(|ClientIp(ip)| ip.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            format!("ip:{}", ip)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_then_refills_at_the_configured_rate() {
+        let limiter = RateLimiter::new(2, 3);
+        let start = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            assert_eq!(
+                limiter.check_at("sub:a", start),
+                RateLimitDecision::Allowed { remaining }
+            );
+        }
+        assert_eq!(
+            limiter.check_at("sub:a", start),
+            RateLimitDecision::Limited {
+                retry_after: Duration::from_millis(500)
+            }
+        );
+
+        // Other clients have their own bucket
+        assert_eq!(
+            limiter.check_at("ip:127.0.0.1", start),
+            RateLimitDecision::Allowed { remaining: 2 }
+        );
+
+        // Half a second refills one token at 2/s
+        let later = start + Duration::from_millis(500);
+        assert_eq!(
+            limiter.check_at("sub:a", later),
+            RateLimitDecision::Allowed { remaining: 0 }
+        );
+    }
+
+    #[test]
+    fn prune_drops_only_fully_refilled_buckets() {
+        let limiter = RateLimiter::new(1, 2);
+        let start = Instant::now();
+        limiter.check_at("idle", start);
+        limiter.check_at("busy", start + Duration::from_secs(5));
+
+        limiter.prune(start + Duration::from_secs(5));
+
+        assert!(!limiter.buckets.contains_key("idle"));
+        assert!(limiter.buckets.contains_key("busy"));
+    }
+
+    #[tokio::test]
+    async fn webhooks_are_not_limited_and_verified_tokens_are_passed_on() {
+        use crate::auth::jkws::{Scope, Subject};
+        use axum::{body::Body, http::StatusCode, middleware, routing::get, Extension, Router};
+        use tower::ServiceExt;
+
+        let db = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        // One request per second and no burst
+        let ctx = crate::http::test_context(db.clone(), db);
+        let token = ctx
+            .auth_service
+            .gen_access_token(
+                Subject::Service("worker".to_string()),
+                String::new(),
+                vec![Scope::Viewer],
+            )
+            .unwrap();
+        let app = Router::new()
+            .route("/api/v1/webhooks/shopify", get(|| async {}))
+            .route(
+                "/api/v1/me",
+                get(|Extension(VerifiedAccessToken(claims))| async move { claims.sub }),
+            )
+            .layer(middleware::from_fn(rate_limit))
+            .layer(Extension(ctx));
+        let send = |uri: &str| {
+            let request = Request::get(uri)
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        for _ in 0..3 {
+            let response = send("/api/v1/webhooks/shopify").await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send("/api/v1/me").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"worker");
+        let response = send("/api/v1/me").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,207 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+
+/// The client's IP address, stored in request extensions by `real_ip`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Where the client IP comes from
+///
+/// `Default` uses the socket peer address. With `trust_proxy`, requests whose peer
+/// is one of the trusted proxies are attributed to the address in `header` instead.
+#[derive(Clone, Debug, Default)]
+pub struct RealIpConfig {
+    proxy: Option<TrustedProxy>,
+}
+
+#[derive(Clone, Debug)]
+struct TrustedProxy {
+    header: HeaderName,
+    networks: Vec<IpNet>,
+}
+
+impl RealIpConfig {
+    /// Read the client IP from `header` (e.g. `X-Forwarded-For`) on requests sent by
+    /// one of `trusted_proxies`, a comma-separated list of IPs and CIDRs
+    pub fn trust_proxy(header: &str, trusted_proxies: &str) -> Result<Self, String> {
+        let header = HeaderName::from_bytes(header.trim().as_bytes())
+            .map_err(|_| format!("'{}' is not a valid header name", header))?;
+        let networks = trusted_proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("'{}' is not an IP address or CIDR", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if networks.is_empty() {
+            return Err("at least one trusted proxy is required".to_string());
+        }
+        Ok(Self {
+            proxy: Some(TrustedProxy { header, networks }),
+        })
+    }
+
+    /// The client behind `peer`
+    ///
+    /// The header is only read when `peer` is a trusted proxy, so clients that connect
+    /// directly can't spoof it. It is walked from the right (the hop our proxy added)
+    /// past any further trusted proxies; the first untrusted hop is the client. If
+    /// every hop is trusted the left-most one is used, and an unparseable hop stops
+    /// the walk at the last address that could be checked.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let Some(proxy) = &self.proxy else {
+            return peer;
+        };
+        if !proxy.trusts(peer) {
+            return peer;
+        }
+
+        let hops: Vec<&str> = headers
+            .get_all(&proxy.header)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            let Some(ip) = parse_hop(hop) else {
+                break;
+            };
+            client = ip;
+            if !proxy.trusts(ip) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+impl TrustedProxy {
+    fn trusts(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        self.networks.iter().any(|network| network.contains(&ip))
+    }
+}
+
+/// A hop is a bare IP, or occasionally `ip:port` / `[v6]:port`
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    hop.parse::<IpAddr>()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Store the client's `ClientIp` for the rate limiter and request logging
+///
+/// Requests without connection info (e.g. in tests) get no `ClientIp`.
+pub async fn real_ip(
+    State(config): State<Arc<RealIpConfig>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(peer) = peer {
+        let ip = config.client_ip(peer, request.headers());
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &str, values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_only_honoured_from_trusted_proxies() {
+        let config = RealIpConfig::trust_proxy("X-Forwarded-For", "10.0.0.0/8").unwrap();
+        let forwarded = headers("x-forwarded-for", &["203.0.113.7"]);
+
+        assert_eq!(
+            config.client_ip(ip("10.0.0.2"), &forwarded),
+            ip("203.0.113.7")
+        );
+        // A client connecting directly can't pick its own address
+        assert_eq!(
+            config.client_ip(ip("198.51.100.1"), &forwarded),
+            ip("198.51.100.1")
+        );
+        // Nor when the feature is off
+        let disabled = RealIpConfig::default();
+        assert_eq!(
+            disabled.client_ip(ip("10.0.0.2"), &forwarded),
+            ip("10.0.0.2")
+        );
+        // Trusted proxy that sent no header
+        assert_eq!(
+            config.client_ip(ip("10.0.0.2"), &HeaderMap::new()),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn client_is_the_first_untrusted_hop_from_the_right() {
+        let config = RealIpConfig::trust_proxy("x-forwarded-for", "10.0.0.0/8, 192.0.2.1");
+        let config = config.unwrap();
+        let peer = ip("10.0.0.2");
+
+        // The client prepended a fake address; the proxies appended the real one
+        let spoofed = headers("x-forwarded-for", &["1.2.3.4, 203.0.113.7, 192.0.2.1"]);
+        assert_eq!(config.client_ip(peer, &spoofed), ip("203.0.113.7"));
+
+        // Repeated headers are one list
+        let split = headers(
+            "x-forwarded-for",
+            &["1.2.3.4", "203.0.113.7:5123, 10.1.1.1"],
+        );
+        assert_eq!(config.client_ip(peer, &split), ip("203.0.113.7"));
+
+        // Garbage stops the walk at the last address we could check
+        let garbage = headers("x-forwarded-for", &["203.0.113.7, unknown, 10.1.1.1"]);
+        assert_eq!(config.client_ip(peer, &garbage), ip("10.1.1.1"));
+
+        let real_ip = RealIpConfig::trust_proxy("X-Real-IP", "10.0.0.0/8").unwrap();
+        let headers = headers("x-real-ip", &["2001:db8::1"]);
+        assert_eq!(real_ip.client_ip(peer, &headers), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn bad_settings_are_reported() {
+        assert!(RealIpConfig::trust_proxy("x forwarded", "10.0.0.0/8").is_err());
+        assert!(RealIpConfig::trust_proxy("x-forwarded-for", "10.0.0.0/33").is_err());
+        assert!(RealIpConfig::trust_proxy("x-forwarded-for", " , ").is_err());
+    }
+}
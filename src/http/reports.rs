@@ -0,0 +1,383 @@
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, ReadPool};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use sqlx::PgConnection;
+
+pub fn reports_router() -> Router {
+    Router::new()
+        .route("/reports/top-products", get(top_products))
+        .route("/reports/discounts", get(discounts))
+}
+
+// Best-selling products over a period, by units sold or revenue
+async fn top_products(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<TopProductsParams>,
+) -> AppResult<Vec<TopProduct>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+    eprintln!(
+        "Top products report: merchant_id={}, from={:?}, to={:?}, sort={:?}",
+        params.merchant_id, params.from, params.to, params.sort
+    );
+
+    let by_revenue = match params.sort.as_deref() {
+        None | Some("quantity") => false,
+        Some("revenue") => true,
+        Some(other) => {
+            return Err(AppError::Validation(format!(
+                "Invalid sort '{}': expected 'quantity' or 'revenue'",
+                other
+            )))
+        }
+    };
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+
+    let mut conn = read_db.acquire().await?;
+    let products = query_top_products(&mut conn, &params, limit, by_revenue).await?;
+
+    Ok(Json(products))
+}
+
+/// Sum line items per product for non-cancelled orders processed in `[from, to)`
+///
+/// Line items resolve to a product through their variant, falling back to the product id
+/// recorded on the line item. Sales whose product has since been deleted are still
+/// counted, under the title they were sold with.
+async fn query_top_products(
+    conn: &mut PgConnection,
+    params: &TopProductsParams,
+    limit: i64,
+    by_revenue: bool,
+) -> Result<Vec<TopProduct>, AppError> {
+    let products = sqlx::query_as::<_, TopProduct>(
+        r#"
+        WITH sales AS (
+            SELECT
+                COALESCE(v.shopify_product_id, li.shopify_product_id) AS shopify_product_id,
+                li.title,
+                li.quantity,
+                li.price * li.quantity AS revenue
+            FROM order_line_items li
+            JOIN orders o ON o.id = li.order_id
+            LEFT JOIN variants v
+                ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+            WHERE li.merchant_id = $1
+                AND o.deleted_at IS NULL
+                AND o.cancelled_at IS NULL
+                AND ($2::timestamptz IS NULL OR COALESCE(o.processed_at, o.created_at) >= $2)
+                AND ($3::timestamptz IS NULL OR COALESCE(o.processed_at, o.created_at) < $3)
+        )
+        SELECT
+            p.id AS product_id,
+            s.shopify_product_id,
+            COALESCE(p.title, MAX(s.title)) AS title,
+            SUM(s.quantity)::bigint AS quantity,
+            SUM(s.revenue) AS revenue
+        FROM sales s
+        LEFT JOIN products p
+            ON p.merchant_id = $1
+            AND p.shopify_product_id = s.shopify_product_id
+            AND p.deleted_at IS NULL
+        -- Custom line items have no product id; keep them apart by title
+        GROUP BY s.shopify_product_id, p.id, p.title,
+            CASE WHEN s.shopify_product_id IS NULL THEN s.title END
+        ORDER BY
+            CASE WHEN $5 THEN SUM(s.revenue) ELSE SUM(s.quantity) END DESC,
+            CASE WHEN $5 THEN SUM(s.quantity) ELSE SUM(s.revenue) END DESC,
+            s.shopify_product_id,
+            MIN(s.title)
+        LIMIT $4
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(limit)
+    .bind(by_revenue)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(products)
+}
+
+// Usage and total discount per discount code over a period
+async fn discounts(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<DiscountReportParams>,
+) -> AppResult<Vec<DiscountCodeUsage>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+    eprintln!(
+        "Discount report: merchant_id={}, from={:?}, to={:?}",
+        params.merchant_id, params.from, params.to
+    );
+
+    let mut conn = read_db.acquire().await?;
+    let usage = query_discount_usage(&mut conn, &params).await?;
+
+    Ok(Json(usage))
+}
+
+/// Per code (case-insensitive), the non-cancelled orders processed in `[from, to)` that used it
+///
+/// An order with several codes counts towards each of them, so `total_sales` across
+/// codes can add up to more than the period's sales. Sorted by sales, highest first.
+async fn query_discount_usage(
+    conn: &mut PgConnection,
+    params: &DiscountReportParams,
+) -> Result<Vec<DiscountCodeUsage>, AppError> {
+    let usage = sqlx::query_as::<_, DiscountCodeUsage>(
+        r#"
+        WITH used AS (
+            -- One row per order and code, even if the code was entered twice
+            SELECT
+                lower(d.code) AS code_key,
+                MIN(d.code) AS code,
+                SUM(d.amount) AS discount,
+                o.total_price
+            FROM order_discount_codes d
+            JOIN orders o ON o.id = d.order_id
+            WHERE d.merchant_id = $1
+                AND o.deleted_at IS NULL
+                AND o.cancelled_at IS NULL
+                AND ($2::timestamptz IS NULL OR COALESCE(o.processed_at, o.created_at) >= $2)
+                AND ($3::timestamptz IS NULL OR COALESCE(o.processed_at, o.created_at) < $3)
+            GROUP BY o.id, lower(d.code)
+        )
+        SELECT
+            MIN(code) AS code,
+            COUNT(*) AS order_count,
+            SUM(discount) AS total_discount,
+            COALESCE(SUM(total_price), 0) AS total_sales
+        FROM used
+        GROUP BY code_key
+        ORDER BY total_sales DESC, order_count DESC, code_key
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.from)
+    .bind(params.to)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, test_db_with};
+    use chrono::{TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::postgres::PgPoolOptions;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn top_products_keeps_sales_of_deleted_products() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+
+        // Product 1 still exists with its variant 11; product 2 and variant 21 were deleted
+        let product_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO products (merchant_id, shopify_product_id, title)
+            VALUES ($1, 1, 'Tee') RETURNING id
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO variants (merchant_id, shopify_variant_id, shopify_product_id)
+            VALUES ($1, 11, 1)
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let in_range = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let out_of_range = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        // (shopify order id, processed_at, cancelled, [(variant, product, title, qty, price)])
+        let orders = [
+            (
+                1_i64,
+                in_range,
+                false,
+                vec![
+                    (11_i64, None, "Tee", 2, "10.00"),
+                    (21, Some(2_i64), "Mug", 5, "4.00"),
+                ],
+            ),
+            (2, in_range, false, vec![(11, None, "Tee", 1, "10.00")]),
+            (3, in_range, true, vec![(21, Some(2), "Mug", 50, "4.00")]),
+            (4, out_of_range, false, vec![(11, None, "Tee", 40, "10.00")]),
+        ];
+        for (shopify_order_id, processed_at, cancelled, line_items) in orders {
+            let order_id: i64 = sqlx::query_scalar(
+                r#"
+                INSERT INTO orders (merchant_id, shopify_order_id, processed_at, cancelled_at)
+                VALUES ($1, $2, $3, CASE WHEN $4 THEN $3 END)
+                RETURNING id
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(shopify_order_id)
+            .bind(processed_at)
+            .bind(cancelled)
+            .fetch_one(&mut *tx)
+            .await?;
+            for (i, (variant_id, product_id, title, quantity, price)) in
+                line_items.into_iter().enumerate()
+            {
+                sqlx::query(
+                    r#"
+                    INSERT INTO order_line_items (
+                        merchant_id, order_id, shopify_line_item_id, shopify_product_id,
+                        shopify_variant_id, title, quantity, price
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8::numeric)
+                    "#,
+                )
+                .bind(merchant_id)
+                .bind(order_id)
+                .bind(i as i64)
+                .bind(product_id)
+                .bind(variant_id)
+                .bind(title)
+                .bind(quantity)
+                .bind(price)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        let params = TopProductsParams {
+            merchant_id,
+            from: Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+            to: Some(Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()),
+            limit: None,
+            sort: None,
+        };
+
+        let by_quantity = query_top_products(&mut tx, &params, 10, false).await?;
+        let summary: Vec<_> = by_quantity
+            .iter()
+            .map(|p| (p.title.as_deref(), p.product_id, p.quantity, p.revenue))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("Mug"), None, 5, Decimal::new(20, 0)),
+                (Some("Tee"), Some(product_id), 3, Decimal::new(30, 0)),
+            ]
+        );
+
+        let by_revenue = query_top_products(&mut tx, &params, 10, true).await?;
+        assert_eq!(by_revenue[0].product_id, Some(product_id));
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn discount_report_groups_orders_by_code() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+
+        let in_range = "2024-03-10T12:00:00Z";
+        // (shopify order id, processed_at, cancelled, total_price, [(code, amount)])
+        let orders = [
+            (
+                1_i64,
+                in_range,
+                false,
+                "100.00",
+                vec![("SPRING", "5.00"), ("FREESHIP", "8.00")],
+            ),
+            (2, in_range, false, "50.00", vec![("spring", "10.00")]),
+            (3, in_range, true, "70.00", vec![("SPRING", "7.00")]),
+            (
+                4,
+                "2024-05-01T12:00:00Z",
+                false,
+                "90.00",
+                vec![("SPRING", "9.00")],
+            ),
+            (5, in_range, false, "30.00", vec![]),
+        ];
+        let orders = orders
+            .into_iter()
+            .map(|(id, processed_at, cancelled, total, codes)| {
+                let codes: Vec<_> = codes
+                    .into_iter()
+                    .map(|(code, amount)| {
+                        serde_json::json!({ "code": code, "amount": amount, "type": "fixed_amount" })
+                    })
+                    .collect();
+                let money = serde_json::json!({ "amount": "0.00", "currency_code": "USD" });
+                serde_json::from_value::<crate::shopify::ShopifyOrder>(serde_json::json!({
+                    "id": id,
+                    "name": format!("#{}", id),
+                    "created_at": processed_at,
+                    "updated_at": processed_at,
+                    "processed_at": processed_at,
+                    "cancelled_at": cancelled.then_some(processed_at),
+                    "currency": "USD",
+                    "subtotal_price": total,
+                    "total_price": total,
+                    "total_discounts": "0.00",
+                    "total_shipping_price_set": { "shop_money": money },
+                    "total_tax": "0.00",
+                    "discount_codes": codes,
+                    "line_items": []
+                }))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for order in &orders {
+            crate::shopify::sync::upsert_order(&db, merchant_id, order, &[], &[]).await?;
+        }
+        // Re-syncing replaces an order's codes rather than adding to them
+        crate::shopify::sync::upsert_order(&db, merchant_id, &orders[0], &[], &[]).await?;
+
+        let params = DiscountReportParams {
+            merchant_id,
+            from: Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+            to: Some(Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()),
+        };
+        let mut conn = db.acquire().await?;
+        let usage = query_discount_usage(&mut conn, &params).await?;
+        drop(conn);
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        let summary: Vec<_> = usage
+            .iter()
+            .map(|u| (u.order_count, u.total_discount, u.total_sales))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (2, Decimal::new(15, 0), Decimal::new(150, 0)),
+                (1, Decimal::new(8, 0), Decimal::new(100, 0)),
+            ]
+        );
+        // "SPRING" and "spring" are one code
+        assert_eq!(usage[0].code.to_lowercase(), "spring");
+        assert_eq!(usage[1].code, "FREESHIP");
+        Ok(())
+    }
+}
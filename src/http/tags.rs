@@ -0,0 +1,142 @@
+use crate::http::merchants::ensure_own_merchant;
+use crate::http::{auth::AuthenticatedUser, types::*, ApiContext, AppError, AppResult, ReadPool};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+pub fn tags_router() -> Router {
+    Router::new().route("/tags", get(list_tags))
+}
+
+// Every tag the merchant's products and orders carry, with how many of each
+async fn list_tags(
+    user: AuthenticatedUser,
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ListTagsParams>,
+) -> AppResult<Vec<TagCount>> {
+    ensure_own_merchant(&ctx, &user, params.merchant_id).await?;
+    eprintln!("Listing tags: merchant_id={}", params.merchant_id);
+
+    let mut conn = read_db.acquire().await?;
+    let tags = tag_counts(&mut conn, params.merchant_id).await?;
+
+    Ok(Json(tags))
+}
+
+/// Tags in use by live (not deleted) products or orders, most used first
+///
+/// Tags left behind once nothing carries them any more are skipped.
+async fn tag_counts(conn: &mut PgConnection, merchant_id: Uuid) -> Result<Vec<TagCount>, AppError> {
+    let tags = sqlx::query_as::<_, TagCount>(
+        r#"
+        SELECT name, product_count, order_count
+        FROM (
+            SELECT
+                t.name,
+                (
+                    SELECT COUNT(*)
+                    FROM product_tags pt
+                    JOIN products p ON p.id = pt.product_id
+                    WHERE pt.tag_id = t.id AND p.deleted_at IS NULL
+                ) AS product_count,
+                (
+                    SELECT COUNT(*)
+                    FROM order_tags ot
+                    JOIN orders o ON o.id = ot.order_id
+                    WHERE ot.tag_id = t.id AND o.deleted_at IS NULL
+                ) AS order_count
+            FROM tags t
+            WHERE t.merchant_id = $1
+        ) counts
+        WHERE product_count > 0 OR order_count > 0
+        ORDER BY product_count + order_count DESC, lower(name)
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, test_db_with};
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn tag_counts_skip_deleted_rows_and_unused_tags() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        let tag = |name: &'static str| {
+            sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO tags (merchant_id, name) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(merchant_id)
+            .bind(name)
+        };
+        let sale = tag("Sale").fetch_one(&mut *tx).await?;
+        let vip = tag("vip").fetch_one(&mut *tx).await?;
+        tag("unused").fetch_one(&mut *tx).await?;
+
+        // (shopify product id, deleted, tags)
+        for (shopify_product_id, deleted, tags) in [
+            (1_i64, false, vec![sale]),
+            (2, false, vec![sale, vip]),
+            (3, true, vec![vip]),
+        ] {
+            sqlx::query(
+                r#"
+                WITH product AS (
+                    INSERT INTO products (merchant_id, shopify_product_id, deleted_at)
+                    VALUES ($1, $2, CASE WHEN $3 THEN NOW() END)
+                    RETURNING id
+                )
+                INSERT INTO product_tags (merchant_id, product_id, tag_id)
+                SELECT $1, product.id, UNNEST($4::uuid[]) FROM product
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(shopify_product_id)
+            .bind(deleted)
+            .bind(&tags)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            r#"
+            WITH inserted AS (
+                INSERT INTO orders (merchant_id, shopify_order_id)
+                VALUES ($1, 1), ($1, 2)
+                RETURNING id
+            )
+            INSERT INTO order_tags (merchant_id, order_id, tag_id)
+            SELECT $1, inserted.id, $2 FROM inserted
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(vip)
+        .execute(&mut *tx)
+        .await?;
+
+        let counts: Vec<(String, i64, i64)> = tag_counts(&mut tx, merchant_id)
+            .await?
+            .into_iter()
+            .map(|t| (t.name, t.product_count, t.order_count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("vip".to_string(), 1, 2), ("Sale".to_string(), 2, 0)]
+        );
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
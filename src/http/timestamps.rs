@@ -0,0 +1,115 @@
+//! Per-request timestamp format for response bodies.
+//!
+//! Clients pick `?ts_format=epoch` (Unix seconds) or `?ts_format=rfc3339` (the
+//! default) on any endpoint. The middleware stores the choice in a task-local and
+//! DTO timestamp fields serialize through `serialize`/`option::serialize`, so
+//! handlers don't need to know about it.
+
+use axum::{
+    extract::{Query, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serializer};
+
+use crate::http::AppError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    #[default]
+    Rfc3339,
+    Epoch,
+}
+
+tokio::task_local! {
+    static TIMESTAMP_FORMAT: TimestampFormat;
+}
+
+#[derive(Deserialize)]
+struct TimestampFormatParams {
+    ts_format: Option<TimestampFormat>,
+}
+
+/// Run the rest of the request with the `ts_format` the client asked for
+pub async fn timestamp_format(request: Request, next: Next) -> Response {
+    let format = match Query::<TimestampFormatParams>::try_from_uri(request.uri()) {
+        Ok(Query(params)) => params.ts_format.unwrap_or_default(),
+        Err(_) => {
+            return AppError::Validation("ts_format must be 'epoch' or 'rfc3339'".to_string())
+                .into_response()
+        }
+    };
+    TIMESTAMP_FORMAT.scope(format, next.run(request)).await
+}
+
+/// Format for the current request; RFC 3339 outside of a request
+pub fn current() -> TimestampFormat {
+    TIMESTAMP_FORMAT
+        .try_with(|format| *format)
+        .unwrap_or_default()
+}
+
+/// `#[serde(serialize_with = "...")]` for `DateTime<Utc>` fields in response bodies
+pub fn serialize<S: Serializer>(ts: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    match current() {
+        TimestampFormat::Rfc3339 => {
+            serializer.serialize_str(&ts.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        TimestampFormat::Epoch => serializer.serialize_i64(ts.timestamp()),
+    }
+}
+
+/// The same for `Option<DateTime<Utc>>`; `None` stays `null`
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        ts: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match ts {
+            Some(ts) => super::serialize(ts, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Row {
+        #[serde(serialize_with = "serialize")]
+        created_at: DateTime<Utc>,
+        #[serde(serialize_with = "option::serialize")]
+        processed_at: Option<DateTime<Utc>>,
+    }
+
+    #[tokio::test]
+    async fn format_follows_the_request_scope() {
+        let row = Row {
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            processed_at: None,
+        };
+
+        let default = serde_json::to_value(&row).unwrap();
+        assert_eq!(default["created_at"], "2023-11-14T22:13:20Z");
+        // Same string chrono's own Serialize produces
+        assert_eq!(
+            default["created_at"],
+            serde_json::to_value(row.created_at).unwrap()
+        );
+
+        let epoch = TIMESTAMP_FORMAT
+            .scope(TimestampFormat::Epoch, async {
+                serde_json::to_value(&row).unwrap()
+            })
+            .await;
+        assert_eq!(epoch["created_at"], 1_700_000_000);
+        assert!(epoch["processed_at"].is_null());
+    }
+}
@@ -1,33 +1,81 @@
 use axum::{
-    http::StatusCode,
+    http::{
+        header::{CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
     response::{IntoResponse, Response},
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use thiserror::Error;
 use uuid::Uuid;
 
+/// `Retry-After` on a 503 for an exhausted connection pool; connections free up quickly
+const UNAVAILABLE_RETRY_AFTER_SECS: u64 = 2;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
     #[error("Validation error: {0}")]
     Validation(String),
+    #[error("Validation error: {0:?}")]
+    FieldValidation(BTreeMap<String, Vec<String>>), // field name -> failed rules
     #[error("Not found")]
     NotFound,
+    #[error("Conflict: {0}")]
+    Conflict(String), // The resource already exists
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid token")]
+    InvalidToken, // Malformed, badly signed or revoked
+    #[error("Token expired")]
+    ExpiredToken,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Insufficient scope: {0} required")]
+    InsufficientScope(crate::auth::jkws::Scope),
+    #[error("Too many requests")]
+    RateLimited,
+    #[error("Payload too large")]
+    PayloadTooLarge, // Over `MAX_BODY_BYTES` once decompressed
+    #[error("Service unavailable")]
+    Unavailable, // No pooled database connection in time
     #[error("Invalid credentials")]
     InvalidCredentials,
     #[error("Internal server error")]
     InternalServerError,
     #[error("Internal server error: {0}")]
     Internal(String),
+    #[error("Shopify error: {0}")]
+    Shopify(#[from] crate::shopify::ShopifyErrorType),
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::PoolTimedOut => AppError::Unavailable,
+            e => AppError::Database(e),
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message, message) = match &self {
+            AppError::Database(sqlx::Error::Database(e))
+                if e.code().as_deref() == Some(crate::http::db::QUERY_CANCELED) =>
+            {
+                eprintln!("Query cancelled: {}", e);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Query timeout",
+                    "The query took too long and was cancelled".to_string(),
+                )
+            },
             AppError::Database(e) => {
                 // Log the full error for debugging
                 eprintln!("Database error: {:?}", e);
@@ -38,22 +86,103 @@ impl IntoResponse for AppError {
                 )
             },
             AppError::Validation(ref msg) => (StatusCode::BAD_REQUEST, "Validation error", msg.clone()),
+            AppError::FieldValidation(_) => (StatusCode::BAD_REQUEST, "Validation error", "One or more fields are invalid".to_string()),
             AppError::NotFound => (StatusCode::NOT_FOUND, "Resource not found", "Resource not found".to_string()),
+            AppError::Conflict(ref msg) => (StatusCode::CONFLICT, "Conflict", msg.clone()),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized", "Unauthorized".to_string()),
+            AppError::MissingToken => (StatusCode::UNAUTHORIZED, "Unauthorized", "A bearer token is required".to_string()),
+            AppError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token", "The access token is invalid".to_string()),
+            AppError::ExpiredToken => (StatusCode::UNAUTHORIZED, "Token expired", "The access token expired".to_string()),
+            AppError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden", "Not permitted for this caller".to_string()),
+            AppError::InsufficientScope(scope) => (StatusCode::FORBIDDEN, "Forbidden", format!("This operation requires the '{}' scope", scope)),
+            AppError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Payload too large", format!("Request bodies are limited to {} bytes", crate::http::MAX_BODY_BYTES)),
+            AppError::RateLimited => (StatusCode::TOO_MANY_REQUESTS, "Too many requests", "Rate limit exceeded, retry later".to_string()),
+            AppError::Unavailable => {
+                eprintln!("Database pool exhausted");
+                (StatusCode::SERVICE_UNAVAILABLE, "Service unavailable", "The server is busy, retry later".to_string())
+            },
             AppError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials", "Invalid email or password".to_string()),
             AppError::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", "Internal server error".to_string()),
             AppError::Internal(ref msg) => {
                 eprintln!("Internal error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", msg.clone())
             },
+            AppError::Shopify(e @ crate::shopify::ShopifyErrorType::CircuitOpen) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Shopify unavailable", e.to_string())
+            },
+            // Expected until the merchant reinstalls, so not logged
+            AppError::Shopify(e @ crate::shopify::ShopifyErrorType::Uninstalled) => {
+                (StatusCode::CONFLICT, "Shopify app uninstalled", e.to_string())
+            },
+            AppError::Shopify(e) => {
+                eprintln!("Shopify error: {}", e);
+                (StatusCode::BAD_GATEWAY, "Shopify error", e.to_string())
+            },
         };
 
-        let body = Json(serde_json::json!({
-            "error": error_message,
-            "message": message
-        }));
+        // RFC 7807 when the client's Accept asked for it (see `problem`)
+        let instance = crate::http::problem::current();
+        let mut body = match &instance {
+            Some(instance) => serde_json::json!({
+                "type": "about:blank",
+                "title": error_message,
+                "status": status.as_u16(),
+                "detail": message,
+                "instance": instance,
+            }),
+            None => serde_json::json!({
+                "error": error_message,
+                "message": message
+            }),
+        };
+        if let AppError::FieldValidation(fields) = &self {
+            body["fields"] = serde_json::json!(fields);
+        }
+        let body = Json(body);
+
+        let mut response = (status, body).into_response();
+        if instance.is_some() {
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static(crate::http::problem::PROBLEM_JSON),
+            );
+        }
+        if let AppError::Unavailable = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(UNAVAILABLE_RETRY_AFTER_SECS));
+        }
+        if let Some(challenge) = self.bearer_challenge() {
+            if let Ok(value) = HeaderValue::from_str(&challenge) {
+                response.headers_mut().insert(WWW_AUTHENTICATE, value);
+            }
+        }
+        response
+    }
+}
 
-        (status, body).into_response()
+impl AppError {
+    /// RFC 6750 `WWW-Authenticate` for errors about the caller's bearer token
+    ///
+    /// RFC 6750 has no code of its own for an expired token; it is an `invalid_token`
+    /// whose description says so, which OAuth clients take as "refresh and retry".
+    fn bearer_challenge(&self) -> Option<String> {
+        match self {
+            AppError::MissingToken => Some("Bearer".to_string()),
+            AppError::InvalidToken => Some(
+                r#"Bearer error="invalid_token", error_description="The access token is invalid""#
+                    .to_string(),
+            ),
+            AppError::ExpiredToken => Some(
+                r#"Bearer error="invalid_token", error_description="The access token expired""#
+                    .to_string(),
+            ),
+            AppError::InsufficientScope(scope) => Some(format!(
+                r#"Bearer error="insufficient_scope", scope="{}""#,
+                scope
+            )),
+            _ => None,
+        }
     }
 }
 
@@ -63,12 +192,17 @@ pub type AppResult<T> = Result<Json<T>, AppError>;
 pub struct Product {
     pub id: Uuid,
     pub merchant_id: Uuid,
-    pub shopify_product_id: i64,
+    #[sqlx(rename = "shopify_product_id")]
+    pub shopify_id: i64,
+    pub shopify_product_id: i64, // Deprecated alias of `shopify_id`
     pub title: Option<String>,
     pub product_type: Option<String>,
     pub status: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
     pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
@@ -76,23 +210,51 @@ pub struct Product {
 pub struct Variant {
     pub id: Uuid,
     pub merchant_id: Uuid,
-    pub shopify_variant_id: i64,
+    #[sqlx(rename = "shopify_variant_id")]
+    pub shopify_id: i64,
+    pub shopify_variant_id: i64, // Deprecated alias of `shopify_id`
     pub shopify_product_id: i64,
     pub sku: Option<String>,
     pub title: Option<String>,
     pub barcode: Option<String>,
     pub weight: Option<f64>,
-    pub weight_unit: Option<String>,
+    pub weight_unit: Option<crate::shopify::WeightUnit>, // "g", "kg", "oz" or "lb"
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl Variant {
+    /// Weight in grams, to compare variants weighed in different units; `None` without a unit
+    #[allow(dead_code)]
+    pub fn weight_in_grams(&self) -> Option<f64> {
+        Some(self.weight_unit?.to_grams(self.weight?))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListVariantsParams {
+    pub merchant_id: Uuid,
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+    pub exact: Option<bool>, // Match sku/barcode whole instead of by substring
+    pub shopify_product_id: Option<i64>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ProductWithVariants {
     #[serde(flatten)]
     pub product: Product,
     pub variants: Vec<Variant>,
+    /// All of the product's variants, even when `variants` holds only the first page
     pub variant_count: i64,
+    /// Shopify metafields keyed by `namespace.key`
+    pub metafields: BTreeMap<String, String>,
+    /// Thumbnail: the product's first image (position 1), if it has any
+    pub primary_image_url: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -100,32 +262,118 @@ pub struct ListProductsParams {
     pub merchant_id: Uuid,
     pub product_type: Option<String>,
     pub status: Option<String>,
+    pub tag: Option<String>, // Case-insensitive, as in Shopify
     pub limit: Option<i32>,
     pub offset: Option<i32>,
 }
 
+/// `GET /products/changes` and the other change feeds
 #[derive(Deserialize)]
-pub struct CreateProductRequest {
+pub struct ChangesParams {
+    pub merchant_id: Uuid,
+    /// Only rows changed after this; omit for a full first pull
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ProductVariantsParams {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkDeleteProductsParams {
     pub merchant_id: Uuid,
-    pub shopify_product_id: i64,
-    pub title: Option<String>,
     pub product_type: Option<String>,
     pub status: Option<String>,
+    pub dry_run: Option<bool>, // Count the matches without deleting them
+}
+
+#[derive(Serialize)]
+pub struct BulkDeleteResponse {
+    pub deleted: u64, // Rows soft-deleted, or that would be on a dry run
+    pub dry_run: bool,
 }
 
 #[derive(Deserialize)]
-pub struct UpdateProductRequest {
+pub struct CreateProductRequest {
+    pub merchant_id: Uuid,
+    #[serde(alias = "shopify_id")]
+    pub shopify_product_id: i64,
     pub title: Option<String>,
     pub product_type: Option<String>,
     pub status: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct ProductListResponse {
-    pub products: Vec<ProductWithVariants>,
-    pub total: i64,
-    pub limit: i32,
-    pub offset: i32,
+/// A field in a JSON Merge Patch (RFC 7386) body
+///
+/// Fields need `#[serde(default)]` so an omitted key becomes `Missing`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Patch<T> {
+    /// Key omitted: leave the stored value unchanged
+    #[default]
+    Missing,
+    /// Key sent as `null`: clear the stored value
+    Null,
+    /// Key sent with a value: replace the stored value
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Patch::Missing)
+    }
+
+    /// The new value to store, `None` meaning NULL; only meaningful when not `Missing`
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Patch::Value(value) => Some(value),
+            Patch::Missing | Patch::Null => None,
+        }
+    }
+
+    pub fn as_ref(&self) -> Patch<&T> {
+        match self {
+            Patch::Missing => Patch::Missing,
+            Patch::Null => Patch::Null,
+            Patch::Value(value) => Patch::Value(value),
+        }
+    }
+}
+
+// `Missing` can only round-trip with `skip_serializing_if = "Patch::is_missing"`
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Patch::Value(value) => value.serialize(serializer),
+            Patch::Missing | Patch::Null => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Only called when the key is present, so None here is an explicit null
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patch::Value(value),
+            None => Patch::Null,
+        })
+    }
+}
+
+// Merge Patch body for PUT /products/:id. Each field is three-state:
+// omitted leaves it unchanged, `null` clears it, a string sets it.
+#[derive(Deserialize)]
+pub struct UpdateProductRequest {
+    #[serde(default)]
+    pub title: Patch<String>,
+    #[serde(default)]
+    pub product_type: Patch<String>,
+    #[serde(default)]
+    pub status: Patch<String>,
 }
 
 // Orders
@@ -133,8 +381,14 @@ pub struct ProductListResponse {
 pub struct Order {
     pub id: i64,
     pub merchant_id: Uuid,
-    pub shopify_order_id: i64,
+    #[sqlx(rename = "shopify_order_id")]
+    pub shopify_id: i64,
+    pub shopify_order_id: i64, // Deprecated alias of `shopify_id`
     pub name: Option<String>,
+    // Customer PII: only `/orders/search`, which checks the caller's merchant, returns it
+    #[serde(skip_serializing)]
+    pub customer_email: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
     pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub currency: Option<String>,
     pub subtotal_price: Option<rust_decimal::Decimal>,
@@ -143,15 +397,91 @@ pub struct Order {
     pub total_shipping_price_set_amount: Option<rust_decimal::Decimal>,
     pub total_tax: Option<rust_decimal::Decimal>,
     pub financial_status: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
     pub cancelled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub status: String, // "open" or "cancelled", derived from cancelled_at
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct OrderRefund {
+    pub id: Uuid,
+    #[sqlx(rename = "shopify_refund_id")]
+    pub shopify_id: i64,
+    pub shopify_refund_id: i64, // Deprecated alias of `shopify_id`
+    pub amount: rust_decimal::Decimal,
+    pub reason: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct Fulfillment {
+    pub id: Uuid,
+    #[sqlx(rename = "shopify_fulfillment_id")]
+    pub shopify_id: i64,
+    pub shopify_fulfillment_id: i64, // Deprecated alias of `shopify_id`
+    pub status: Option<String>,
+    pub tracking_number: Option<String>,
+    pub tracking_company: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrderDetail {
+    #[serde(flatten)]
+    pub order: Order,
+    pub refunds: Vec<OrderRefund>,
+    pub fulfillments: Vec<Fulfillment>,
+    pub refunded_amount: rust_decimal::Decimal,
+    /// `total_price` minus refunds, never below zero
+    pub net_total: rust_decimal::Decimal,
+}
+
+#[derive(Deserialize)]
+pub struct OrderByShopifyIdParams {
+    pub merchant_id: Uuid,
+}
+
 #[derive(Deserialize)]
 pub struct ListOrdersParams {
     pub merchant_id: Uuid,
     pub financial_status: Option<String>,
+    pub cancelled: Option<bool>, // true: only cancelled orders, false: only live ones
+    pub tag: Option<String>,     // Case-insensitive, as in Shopify
+    /// Only set by `/orders/search`, which checks the caller belongs to the merchant
+    #[serde(skip)]
+    pub customer_email: Option<String>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+/// An order found by `/orders/search`, with the customer email other order routes leave out
+#[derive(Serialize)]
+pub struct OrderSearchResult {
+    #[serde(flatten)]
+    pub order: Order,
+    pub customer_email: Option<String>,
+}
+
+impl From<Order> for OrderSearchResult {
+    fn from(order: Order) -> Self {
+        let customer_email = order.customer_email.clone();
+        OrderSearchResult {
+            order,
+            customer_email,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchOrdersParams {
+    pub merchant_id: Uuid,
+    pub customer_email: String, // Whole address, case-insensitive
     pub limit: Option<i32>,
     pub offset: Option<i32>,
 }
@@ -159,6 +489,7 @@ pub struct ListOrdersParams {
 #[derive(Deserialize)]
 pub struct CreateOrderRequest {
     pub merchant_id: Uuid,
+    #[serde(alias = "shopify_id")]
     pub shopify_order_id: i64,
     pub name: Option<String>,
     pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
@@ -178,12 +509,91 @@ pub struct UpdateOrderRequest {
     pub cancelled_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+#[derive(Deserialize)]
+pub struct UpdateOrderParams {
+    pub force: Option<bool>, // Admins only: skip the financial status transition check
+}
+
+// Reports
+#[derive(Deserialize)]
+pub struct TopProductsParams {
+    pub merchant_id: Uuid,
+    pub from: Option<chrono::DateTime<chrono::Utc>>, // Inclusive, on the order's processed_at
+    pub to: Option<chrono::DateTime<chrono::Utc>>,   // Exclusive
+    pub limit: Option<i64>,
+    pub sort: Option<String>, // "quantity" (default) or "revenue"
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct TopProduct {
+    pub product_id: Option<Uuid>, // None when the product no longer exists locally
+    pub shopify_product_id: Option<i64>,
+    pub title: Option<String>,
+    pub quantity: i64,
+    pub revenue: rust_decimal::Decimal,
+}
+
+#[derive(Deserialize)]
+pub struct DiscountReportParams {
+    pub merchant_id: Uuid,
+    pub from: Option<chrono::DateTime<chrono::Utc>>, // Inclusive, on the order's processed_at
+    pub to: Option<chrono::DateTime<chrono::Utc>>,   // Exclusive
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DiscountCodeUsage {
+    pub code: String,
+    pub order_count: i64,
+    pub total_discount: rust_decimal::Decimal, // Sum of what the code took off those orders
+    pub total_sales: rust_decimal::Decimal,    // Sum of those orders' total_price
+}
+
+// Tags
+#[derive(Deserialize)]
+pub struct ListTagsParams {
+    pub merchant_id: Uuid,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct TagCount {
+    pub name: String,
+    pub product_count: i64, // Live products carrying the tag
+    pub order_count: i64,   // Live orders carrying the tag
+}
+
+// Checkouts
+#[derive(Deserialize)]
+pub struct ListCheckoutsParams {
+    pub merchant_id: Uuid,
+    pub converted: Option<bool>, // true: only checkouts that became an order, false: only abandoned ones
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct Checkout {
+    pub id: Uuid,
+    #[sqlx(rename = "shopify_checkout_id")]
+    pub shopify_id: i64,
+    pub shopify_checkout_id: i64, // Deprecated alias of `shopify_id`
+    pub email: Option<String>,
+    pub abandoned_checkout_url: Option<String>, // Recovery link to send the customer
+    pub currency: Option<String>,
+    pub total_price: Option<rust_decimal::Decimal>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub shopify_created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub order_id: Option<i64>, // The order the checkout converted into, once that order is synced
+}
+
+// Health
 #[derive(Serialize)]
-pub struct OrderListResponse {
-    pub orders: Vec<Order>,
-    pub total: i64,
-    pub limit: i32,
-    pub offset: i32,
+pub struct DetailedHealth {
+    pub applied_migration_version: Option<i64>, // Highest successfully applied migration
+    pub latest_migration_version: Option<i64>,  // Highest migration embedded in this binary
+    pub pending_migrations: Vec<i64>,           // Embedded migrations not applied yet
+    pub schema_outdated: bool,
 }
 
 // Inventory Items
@@ -191,9 +601,13 @@ pub struct OrderListResponse {
 pub struct InventoryItem {
     pub id: Uuid,
     pub merchant_id: Uuid,
-    pub shopify_inventory_item_id: i64,
+    #[sqlx(rename = "shopify_inventory_item_id")]
+    pub shopify_id: i64,
+    pub shopify_inventory_item_id: i64, // Deprecated alias of `shopify_id`
     pub shopify_variant_id: Option<i64>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -207,6 +621,7 @@ pub struct ListInventoryItemsParams {
 #[derive(Deserialize)]
 pub struct CreateInventoryItemRequest {
     pub merchant_id: Uuid,
+    #[serde(alias = "shopify_id")]
     pub shopify_inventory_item_id: i64,
     pub shopify_variant_id: Option<i64>,
 }
@@ -216,12 +631,166 @@ pub struct UpdateInventoryItemRequest {
     pub shopify_variant_id: Option<i64>,
 }
 
+#[derive(Deserialize)]
+pub struct AdjustInventoryRequest {
+    pub location_id: i64, // Shopify location ID
+    pub delta: i32,       // Negative to remove stock
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct InventoryLevel {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub inventory_item_id: Uuid,
+    pub shopify_location_id: i64,
+    /// From the synced `locations`; `None` until the location has been synced
+    #[sqlx(default)]
+    pub location_name: Option<String>,
+    pub available: i32,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkUpsertLevelsRequest {
+    pub merchant_id: Uuid,
+    pub levels: Vec<LevelUpsert>,
+}
+
+#[derive(Deserialize)]
+pub struct LevelUpsert {
+    pub inventory_item_id: Uuid,
+    pub location_id: i64, // Shopify location ID
+    pub available: i32,   // Absolute stock, not a delta
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct BulkUpsertLevelsResponse {
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64, // Already at the given quantity; left untouched
+}
+
+// Merchants
+#[derive(Deserialize)]
+pub struct DeleteMerchantParams {
+    pub purge: Option<bool>, // Hard delete instead of soft delete
+}
+
+/// A fresh install's credentials, from the OAuth callback of a reinstall
+#[derive(Deserialize)]
+pub struct ReinstallRequest {
+    pub access_token: String,
+    pub access_scopes: String, // Comma-separated, as granted
+}
+
+/// A merchant's sync health; the counts are the live rows mirrored right now
+#[derive(Serialize, sqlx::FromRow)]
+pub struct SyncStatus {
+    pub merchant_id: Uuid,
+    pub running: bool,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub sync_started_at: Option<chrono::DateTime<chrono::Utc>>, // Start of the running sync
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub last_synced_at: Option<chrono::DateTime<chrono::Utc>>, // Start of the last successful sync
+    pub last_error: Option<String>, // Why the last sync failed; None once one succeeds
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub last_error_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub products: i64,
+    pub orders: i64,
+    pub inventory_items: i64,
+}
+
+/// A manual sync started with `POST /merchants/{id}/sync`
+#[derive(Serialize, sqlx::FromRow)]
+pub struct SyncJob {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub status: String,         // running|succeeded|failed
+    pub locations: Option<i64>, // Records mirrored; set once succeeded
+    pub products: Option<i64>,
+    pub orders: Option<i64>,
+    pub checkouts: Option<i64>,
+    pub error: Option<String>, // Why it failed
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct BackfillParams {
+    pub from: chrono::DateTime<chrono::Utc>, // Inclusive, on the order's created_at
+    pub to: chrono::DateTime<chrono::Utc>,   // Exclusive
+    pub window_days: Option<i32>,            // Days per Shopify request window; 30 by default
+}
+
+/// An order backfill started with `POST /merchants/{id}/backfill`
+#[derive(Serialize, sqlx::FromRow)]
+pub struct OrderBackfill {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub from: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub to: chrono::DateTime<chrono::Utc>,
+    pub window_days: i32,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub synced_through: Option<chrono::DateTime<chrono::Utc>>, // Orders created before this are mirrored
+    pub orders: i64,    // Orders mirrored so far
+    pub status: String, // running|succeeded|failed
+    pub error: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Which webhook orders get stored; an empty `order_tags` stores them all
+#[derive(Serialize, Deserialize)]
+pub struct IngestFilter {
+    pub order_tags: Vec<String>, // Case-insensitive, as in Shopify
+}
+
+// Signing keys
 #[derive(Serialize)]
-pub struct InventoryItemListResponse {
-    pub items: Vec<InventoryItem>,
-    pub total: i64,
-    pub limit: i32,
-    pub offset: i32,
+pub struct RotateKeysResponse {
+    pub kid: String,                    // kid of the new signing key
+    pub verification_kids: Vec<String>, // Every kid currently served in the JWKS
+}
+
+#[derive(Deserialize)]
+pub struct ListKeysParams {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+// Built from `KeySummary`, which has no key material, so none can be serialized here
+#[derive(Serialize)]
+pub struct SigningKeyInfo {
+    pub kid: String,
+    pub alg: String,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub retired_at: Option<chrono::DateTime<chrono::Utc>>, // None for the current key
+    pub current: bool, // Signs new tokens; the rest only verify
+}
+
+#[derive(Serialize)]
+pub struct InvalidateTokensResponse {
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub min_iat: chrono::DateTime<chrono::Utc>, // Tokens issued before this are rejected
+    pub revoked_refresh_tokens: u64,            // Opaque refresh tokens revoked in the database
+}
+
+#[derive(Serialize)]
+pub struct RevokeSessionsResponse {
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub tokens_valid_after: chrono::DateTime<chrono::Utc>, // Refresh tokens issued before this are rejected
+    pub revoked_sessions: u64, // Opaque refresh tokens revoked; JWT refresh tokens aren't tracked
 }
 
 // Authentication Types
@@ -229,15 +798,113 @@ pub struct InventoryItemListResponse {
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    pub scope: Option<String>, // Space-delimited subset of the role's scopes, e.g. "viewer"
+}
+
+#[derive(Deserialize)]
+pub struct LoginParams {
+    pub cookie: Option<bool>, // Also set the access token as an HttpOnly cookie
+    pub refresh_cookie: Option<bool>, // Set the refresh token as a cookie instead of returning it
 }
 
 #[derive(Serialize)]
 pub struct LoginResponseData {
     pub access_token: String,
-    pub refresh_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>, // Omitted when it was set as a cookie
     pub user: UserInfo,
 }
 
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: Option<String>, // Falls back to the refresh token cookie
+}
+
+#[derive(Serialize)]
+pub struct RefreshResponseData {
+    pub access_token: String,
+}
+
+// OAuth client-credentials grant; sent form-encoded like any OAuth token request
+#[derive(Deserialize)]
+pub struct ClientCredentialsRequest {
+    pub grant_type: String, // Must be "client_credentials"
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Serialize)]
+pub struct ClientTokenResponse {
+    pub access_token: String,
+    pub token_type: String, // Always "Bearer"
+    pub expires_in: i64,    // Seconds
+    pub scope: String,      // Space-delimited scopes granted to the client
+}
+
+#[derive(Deserialize)]
+pub struct DecodeTokenRequest {
+    pub token: String,
+}
+
+/// Unverified view of a token; `verified` is always false and comes first on purpose
+#[derive(Serialize)]
+pub struct DecodedToken {
+    pub verified: bool,
+    pub header: serde_json::Value,
+    pub claims: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyBatchRequest {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenVerification {
+    pub valid: bool,
+    pub sub: Option<String>,
+    pub scope: Option<Vec<crate::auth::jkws::Scope>>,
+    pub error: Option<String>, // e.g. "ExpiredSignature", "InvalidSignature"
+}
+
+#[derive(Serialize)]
+pub struct TokenInfo {
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub expires_in_seconds: i64,
+    pub scopes: Vec<crate::auth::jkws::Scope>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub scopes: String, // Space-delimited, e.g. "viewer backoffice"
+    pub description: Option<String>,
+}
+
+/// An API key as listed; the key itself is never stored, only its prefix
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ApiKeyInfo {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub key_prefix: String,
+    pub scopes: String,
+    pub description: Option<String>,
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>, // To the minute; None if never used
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A newly created API key; the only response that ever contains `key`
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
 #[derive(Serialize)]
 pub struct UserInfo {
     pub id: Uuid,
@@ -249,7 +916,6 @@ pub struct UserInfo {
 #[derive(sqlx::FromRow)]
 pub struct User {
     pub id: Uuid,
-    pub merchant_id: Uuid,
     pub email: String,
     pub password_hash: Option<String>,
     pub display_name: Option<String>,
@@ -282,6 +948,15 @@ impl<T> ApiResponse<T> {
     }
 }
 
+// Paginated body shared by every list endpoint; rows are always under `items`
+#[derive(Serialize)]
+pub struct ListResponse<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
 // User Management Types
 #[derive(Serialize, sqlx::FromRow)]
 pub struct UserResponse {
@@ -290,10 +965,15 @@ pub struct UserResponse {
     pub email: String,
     pub display_name: Option<String>,
     pub role: String,
-    pub shopify_user_id: Option<i64>,
+    #[sqlx(rename = "shopify_user_id")]
+    pub shopify_id: Option<i64>,
+    pub shopify_user_id: Option<i64>, // Deprecated alias of `shopify_id`
+    #[serde(serialize_with = "crate::http::timestamps::option::serialize")]
     pub last_login_at: Option<chrono::DateTime<chrono::Utc>>,
     pub is_active: bool,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(serialize_with = "crate::http::timestamps::serialize")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -326,10 +1006,17 @@ pub struct UpdateUserRequest {
     // Must use SQL scripts to change roles or deactivate users
 }
 
-#[derive(Serialize)]
-pub struct UserListResponse {
-    pub users: Vec<UserResponse>,
-    pub total: i64,
-    pub limit: i32,
-    pub offset: i32,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_fields_distinguish_omitted_null_and_value() {
+        let request: UpdateProductRequest =
+            serde_json::from_str(r#"{"product_type": null, "status": "draft"}"#).unwrap();
+
+        assert_eq!(request.title, Patch::Missing);
+        assert_eq!(request.product_type, Patch::Null);
+        assert_eq!(request.status, Patch::Value("draft".to_string()));
+    }
+}
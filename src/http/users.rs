@@ -1,11 +1,15 @@
-use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::http::prefer;
+use crate::http::{
+    types::*, ApiContext, AppError, AppResult, Filters, JsonBody, PagedResource, Pagination,
+};
+use crate::misc::password_hash::hash_password;
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
+    middleware,
     routing::get,
     Extension, Json, Router,
 };
-use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 pub fn users_router() -> Router {
@@ -15,6 +19,8 @@ pub fn users_router() -> Router {
             "/users/:id",
             get(get_user).put(update_user).delete(delete_user),
         )
+        // Prefer: return=minimal on create/update
+        .route_layer(middleware::from_fn(prefer::return_preference))
 }
 
 // List all users for a merchant
@@ -22,25 +28,29 @@ pub fn users_router() -> Router {
 async fn list_users(
     Extension(ctx): Extension<ApiContext>,
     Query(params): Query<ListUsersParams>,
-) -> AppResult<UserListResponse> {
-    let limit = params.limit.unwrap_or(50).min(100);
-    let offset = params.offset.unwrap_or(0);
+) -> AppResult<ListResponse<UserResponse>> {
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Users),
+    );
+
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and_opt("role = {}", params.role.as_deref())
+        .and_opt("is_active = {}", params.is_active);
 
     // Get total count
-    let total: i64 = sqlx::query_scalar::<_, i64>(
-        r#"
-        SELECT COUNT(*) 
-        FROM users 
-        WHERE merchant_id = $1
-        "#,
-    )
-    .bind(params.merchant_id)
-    .fetch_one(&ctx.db)
-    .await?;
+    let total: i64 = filters
+        .count("FROM users")
+        .build_query_scalar()
+        .fetch_one(&ctx.db)
+        .await?;
 
     // Get users (excluding password_hash for security)
-    let users = sqlx::query_as::<_, UserResponse>(
-        r#"
+    let users = filters
+        .page(
+            r#"
         SELECT 
             id,
             merchant_id,
@@ -53,19 +63,18 @@ async fn list_users(
             created_at,
             updated_at
         FROM users
-        WHERE merchant_id = $1
-        ORDER BY created_at DESC
-        LIMIT $2 OFFSET $3
         "#,
-    )
-    .bind(params.merchant_id)
-    .bind(limit as i64)
-    .bind(offset as i64)
-    .fetch_all(&ctx.db)
-    .await?;
+            "created_at DESC",
+            "id DESC",
+            limit,
+            offset,
+        )
+        .build_query_as::<UserResponse>()
+        .fetch_all(&ctx.db)
+        .await?;
 
-    Ok(Json(UserListResponse {
-        users,
+    Ok(Json(ListResponse {
+        items: users,
         total,
         limit,
         offset,
@@ -107,14 +116,14 @@ async fn get_user(
 // TODO: Add middleware - ADMIN ONLY
 async fn create_user(
     Extension(ctx): Extension<ApiContext>,
-    Json(req): Json<CreateUserRequest>,
+    JsonBody(req): JsonBody<CreateUserRequest>,
 ) -> Result<(StatusCode, Json<UserResponse>), AppError> {
     // Validate email
     crate::misc::validator::validate_email(&req.email)?;
 
     // Validate password if provided
     if let Some(ref password) = req.password {
-        crate::misc::validator::validate_password(password)?;
+        crate::misc::validator::validate_password(password, &ctx.config.password_policy())?;
     }
 
     // Check if user already exists
@@ -127,17 +136,16 @@ async fn create_user(
     .await?;
 
     if existing.is_some() {
-        return Err(AppError::Validation(
+        return Err(AppError::Conflict(
             "User with this email already exists for this merchant".to_string(),
         ));
     }
 
     // Hash password if provided
-    let password_hash = req.password.map(|password| {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        format!("{:x}", hasher.finalize())
-    });
+    let password_hash = req
+        .password
+        .map(|password| hash_password(&password, &ctx.config.password_hash_params()))
+        .transpose()?;
 
     // Always create as viewer - admin/manager roles must be set via SQL scripts
     let role = "viewer".to_string();
@@ -186,22 +194,21 @@ async fn create_user(
 async fn update_user(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<Uuid>,
-    Json(req): Json<UpdateUserRequest>,
+    JsonBody(req): JsonBody<UpdateUserRequest>,
 ) -> AppResult<UserResponse> {
     // Validate password if provided
     if let Some(ref password) = req.password {
-        crate::misc::validator::validate_password(password)?;
+        crate::misc::validator::validate_password(password, &ctx.config.password_policy())?;
     }
 
     // Role changes not allowed via API - must use SQL scripts
     // This prevents privilege escalation attacks
 
     // Build password hash if password is being updated
-    let password_hash = req.password.map(|password| {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        format!("{:x}", hasher.finalize())
-    });
+    let password_hash = req
+        .password
+        .map(|password| hash_password(&password, &ctx.config.password_hash_params()))
+        .transpose()?;
 
     // Update user (role and is_active changes not allowed via API)
     let user = sqlx::query_as::<_, UserResponse>(
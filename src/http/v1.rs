@@ -0,0 +1,44 @@
+//! Version 1 of the HTTP API, mounted under `/api/v1`.
+//!
+//! Every API version owns its own router so breaking changes can ship as a new
+//! version without forking the server. Handlers and DTOs that are identical
+//! between versions are shared from the parent `http` module; anything that
+//! changes shape lives inside the version module that introduced it.
+
+use axum::{middleware, Router};
+
+use crate::http::{
+    auth, checkouts, envelope, health, inventory, merchants, orders, products, reports, tags,
+    timestamps, users, variants, version, webhooks,
+};
+
+pub const PREFIX: &str = "/api/v1";
+
+/// Sent back in `X-Api-Version`
+pub const VERSION: &str = "v1";
+
+pub fn router() -> Router {
+    // This is the order that the modules were authored in.
+    Router::new()
+        .merge(auth::auth_router())
+        .merge(inventory::inventory_router())
+        .merge(orders::orders_router())
+        .merge(products::products_router())
+        .merge(users::users_router())
+        .merge(merchants::merchants_router())
+        .merge(webhooks::webhooks_router())
+        .merge(variants::variants_router())
+        .merge(reports::reports_router())
+        .merge(health::health_router())
+        .merge(tags::tags_router())
+        .merge(checkouts::checkouts_router())
+        // ?ts_format=epoch|rfc3339 on any endpoint
+        .layer(middleware::from_fn(timestamps::timestamp_format))
+        // X-Response-Envelope: true or ?envelope=true on any endpoint
+        .layer(middleware::from_fn(envelope::response_envelope))
+        // X-Api-Version: v1 on everything this router matched
+        .layer(middleware::from_fn_with_state(
+            VERSION,
+            version::api_version,
+        ))
+}
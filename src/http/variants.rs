@@ -0,0 +1,181 @@
+use crate::http::{
+    types::*, ApiContext, AppError, AppResult, Filters, PagedResource, Pagination, ReadPool,
+};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use sqlx::PgConnection;
+
+pub fn variants_router() -> Router {
+    Router::new().route("/variants", get(list_variants))
+}
+
+// Flat SKU list across all of a merchant's products
+async fn list_variants(
+    Extension(ctx): Extension<ApiContext>,
+    ReadPool(read_db): ReadPool,
+    Query(params): Query<ListVariantsParams>,
+) -> AppResult<ListResponse<Variant>> {
+    eprintln!(
+        "Listing variants: merchant_id={}, sku={:?}, barcode={:?}, exact={:?}",
+        params.merchant_id, params.sku, params.barcode, params.exact
+    );
+
+    let Pagination { limit, offset } = Pagination::new(
+        params.limit,
+        params.offset,
+        ctx.config.page_limits(PagedResource::Variants),
+    );
+
+    let mut conn = read_db.acquire().await?;
+    let (variants, total) = search_variants(&mut conn, &params, limit, offset).await?;
+
+    // Barcode scanners look up a single code; report a miss as 404 rather than an empty page
+    if params.exact.unwrap_or(false) && params.barcode.is_some() && variants.is_empty() {
+        return Err(AppError::NotFound);
+    }
+
+    eprintln!("Found {} variants (total: {})", variants.len(), total);
+
+    Ok(Json(ListResponse {
+        items: variants,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+/// One page of variants matching the SKU/barcode/product filters, plus the total match count
+///
+/// Filters are case-insensitive substring matches, or whole-value matches when `exact` is set.
+pub(crate) async fn search_variants(
+    conn: &mut PgConnection,
+    params: &ListVariantsParams,
+    limit: i32,
+    offset: i32,
+) -> Result<(Vec<Variant>, i64), AppError> {
+    let exact = params.exact.unwrap_or(false);
+    let filters = Filters::new()
+        .and("merchant_id = {}", [params.merchant_id.into()])
+        .and_opt(
+            if exact {
+                "lower(sku) = lower({})"
+            } else {
+                "strpos(lower(sku), lower({})) > 0"
+            },
+            params.sku.as_deref(),
+        )
+        .and_opt(
+            if exact {
+                "barcode = {}"
+            } else {
+                "strpos(lower(barcode), lower({})) > 0"
+            },
+            params.barcode.as_deref(),
+        )
+        .and_opt("shopify_product_id = {}", params.shopify_product_id);
+
+    let total: i64 = filters
+        .count("FROM variants")
+        .build_query_scalar()
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let variants = filters
+        .page(
+            r#"
+        SELECT 
+            id,
+            merchant_id,
+            shopify_variant_id,
+            shopify_product_id,
+            sku,
+            title,
+            barcode,
+            weight::float8 AS weight,
+            weight_unit,
+            created_at,
+            updated_at
+        FROM variants
+        "#,
+            "sku NULLS LAST, created_at",
+            "id",
+            limit,
+            offset,
+        )
+        .build_query_as::<Variant>()
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok((variants, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_merchant, test_db_with};
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn search_variants_filters_by_substring_or_exact_barcode() -> anyhow::Result<()> {
+        let Some(db) = test_db_with(PgPoolOptions::new().max_connections(1)).await? else {
+            return Ok(());
+        };
+
+        // Everything happens inside a transaction that is rolled back at the end
+        let mut tx = db.begin().await?;
+        let merchant_id = insert_merchant(&mut *tx).await?;
+        for (shopify_variant_id, shopify_product_id, sku, barcode) in [
+            (1_i64, 1_i64, "TEE-RED-S", "0012345678905"),
+            (2, 1, "TEE-RED-M", "0012345678912"),
+            (3, 2, "MUG-BLUE", "0098765432109"),
+        ] {
+            sqlx::query(
+                r#"
+                INSERT INTO variants (merchant_id, shopify_variant_id, shopify_product_id, sku, barcode)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(shopify_variant_id)
+            .bind(shopify_product_id)
+            .bind(sku)
+            .bind(barcode)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let params = |sku: Option<&str>, barcode: Option<&str>, exact| ListVariantsParams {
+            merchant_id,
+            sku: sku.map(str::to_string),
+            barcode: barcode.map(str::to_string),
+            exact: Some(exact),
+            shopify_product_id: None,
+            limit: None,
+            offset: None,
+        };
+
+        let (variants, total) =
+            search_variants(&mut tx, &params(Some("tee-red"), None, false), 50, 0).await?;
+        assert_eq!(total, 2);
+        assert_eq!(variants.len(), 2);
+
+        let (variants, _) =
+            search_variants(&mut tx, &params(None, Some("0012345678912"), true), 50, 0).await?;
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].sku.as_deref(), Some("TEE-RED-M"));
+
+        let (variants, total) =
+            search_variants(&mut tx, &params(None, Some("00123456789"), true), 50, 0).await?;
+        assert_eq!((variants.len(), total), (0, 0));
+
+        let mugs = ListVariantsParams {
+            shopify_product_id: Some(2),
+            ..params(None, None, false)
+        };
+        let (variants, total) = search_variants(&mut tx, &mugs, 50, 0).await?;
+        assert_eq!((variants.len(), total), (1, 1));
+        assert_eq!(variants[0].sku.as_deref(), Some("MUG-BLUE"));
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! `X-Api-Version` and `X-Server-Version` response headers.
+//!
+//! With several API versions behind one gateway, these say which version of the
+//! API and which build of the server produced a response. Each version router
+//! stamps its own name, so only routes it matched carry `X-Api-Version`.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const X_API_VERSION: HeaderName = HeaderName::from_static("x-api-version");
+pub const X_SERVER_VERSION: HeaderName = HeaderName::from_static("x-server-version");
+
+/// The crate version this server was built from
+const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Stamp `X-Api-Version: {version}`; layered on a version router with its name as state
+pub async fn api_version(
+    State(version): State<&'static str>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(X_API_VERSION, HeaderValue::from_static(version));
+    response
+}
+
+/// Stamp `X-Server-Version` on every response
+pub async fn server_version(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(X_SERVER_VERSION, HeaderValue::from_static(SERVER_VERSION));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let v1 = Router::new()
+            .route("/things", get(|| async { "v1" }))
+            .layer(middleware::from_fn_with_state("v1", api_version));
+        Router::new()
+            .route("/", get(|| async { "root" }))
+            .nest("/api/v1", v1)
+            .layer(middleware::from_fn(server_version))
+    }
+
+    async fn headers(uri: &str) -> (Option<String>, Option<String>) {
+        let response = app()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        (header(&X_API_VERSION), header(&X_SERVER_VERSION))
+    }
+
+    #[tokio::test]
+    async fn versioned_routes_carry_both_headers() {
+        let (api, server) = headers("/api/v1/things").await;
+        assert_eq!(api.as_deref(), Some("v1"));
+        assert_eq!(server.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[tokio::test]
+    async fn other_routes_carry_only_the_server_version() {
+        let (api, server) = headers("/").await;
+        assert_eq!(api, None);
+        assert!(server.is_some());
+
+        // Unmatched paths too
+        let (api, server) = headers("/api/v1/missing").await;
+        assert_eq!(api, None);
+        assert!(server.is_some());
+    }
+}
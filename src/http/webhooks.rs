@@ -0,0 +1,196 @@
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::http::merchants::{mark_uninstalled, order_ingest_tags};
+use crate::http::{ApiContext, AppError};
+use crate::shopify::{parse_tags, sync, ShopifyFulfillment, ShopifyOrder};
+
+pub fn webhooks_router() -> Router {
+    Router::new().route("/webhooks/shopify", post(handle_shopify_webhook))
+}
+
+// Receive a Shopify webhook, verified with the app's shared secret
+// Always answers 200 once the signature checks out, since Shopify retries
+// anything else; topics and shops we don't handle are logged and ignored.
+async fn handle_shopify_webhook(
+    Extension(ctx): Extension<ApiContext>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    let secret = ctx
+        .config
+        .shopify_webhook_secret
+        .as_deref()
+        .ok_or(AppError::Unauthorized)?;
+    let signature = header(&headers, "x-shopify-hmac-sha256").ok_or(AppError::Unauthorized)?;
+    if !verify_signature(secret, &body, signature) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let topic = header(&headers, "x-shopify-topic")
+        .ok_or_else(|| AppError::Validation("Missing X-Shopify-Topic header".to_string()))?;
+    let shop_domain = header(&headers, "x-shopify-shop-domain")
+        .ok_or_else(|| AppError::Validation("Missing X-Shopify-Shop-Domain header".to_string()))?;
+    eprintln!(
+        "Received Shopify webhook: topic={}, shop={}",
+        topic, shop_domain
+    );
+
+    let merchant_id: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM merchants WHERE shop_domain = $1 AND deleted_at IS NULL",
+    )
+    .bind(shop_domain)
+    .fetch_optional(&ctx.db)
+    .await?;
+    let Some(merchant_id) = merchant_id else {
+        eprintln!("Ignoring webhook for unknown shop: {}", shop_domain);
+        return Ok(StatusCode::OK);
+    };
+
+    match topic {
+        "fulfillments/create" | "fulfillments/update" => {
+            handle_fulfillment(&ctx, merchant_id, &body).await?
+        }
+        "orders/create" | "orders/updated" => handle_order(&ctx, merchant_id, &body).await?,
+        "app/uninstalled" => {
+            let installs = mark_uninstalled(&ctx.db, merchant_id).await?;
+            eprintln!(
+                "App uninstalled: merchant_id={}, installs_deactivated={}",
+                merchant_id, installs
+            );
+        }
+        _ => eprintln!("Ignoring unhandled webhook topic: {}", topic),
+    }
+
+    Ok(StatusCode::OK)
+}
+
+async fn handle_fulfillment(
+    ctx: &ApiContext,
+    merchant_id: Uuid,
+    body: &[u8],
+) -> Result<(), AppError> {
+    let fulfillment: ShopifyFulfillment = serde_json::from_slice(body)
+        .map_err(|e| AppError::Validation(format!("Invalid fulfillment payload: {}", e)))?;
+
+    let order_id: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM orders
+        WHERE merchant_id = $1 AND shopify_order_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(fulfillment.order_id)
+    .fetch_optional(&ctx.db)
+    .await?;
+    // The next order sync will pick it up along with the order itself
+    let Some(order_id) = order_id else {
+        eprintln!(
+            "Ignoring fulfillment {} for unsynced order {}",
+            fulfillment.id, fulfillment.order_id
+        );
+        return Ok(());
+    };
+
+    let mut conn = ctx.db.acquire().await?;
+    sync::upsert_fulfillment(&mut conn, merchant_id, order_id, &fulfillment).await?;
+    eprintln!(
+        "Fulfillment stored: order_id={}, fulfillment_id={}",
+        order_id, fulfillment.id
+    );
+    Ok(())
+}
+
+/// Store an order from its webhook payload, unless the merchant's ingest filter skips it
+///
+/// Refunds and fulfillments aren't part of the order payload we parse; the next
+/// order sync and the fulfillment webhooks fill them in.
+async fn handle_order(ctx: &ApiContext, merchant_id: Uuid, body: &[u8]) -> Result<(), AppError> {
+    let order: ShopifyOrder = serde_json::from_slice(body)
+        .map_err(|e| AppError::Validation(format!("Invalid order payload: {}", e)))?;
+
+    let allowlist = order_ingest_tags(&ctx.db, merchant_id).await?;
+    if !passes_ingest_filter(&allowlist, &order.tags) {
+        eprintln!(
+            "Skipping order {} without an ingest tag (tags={:?})",
+            order.id, order.tags
+        );
+        return Ok(());
+    }
+
+    let order_id = sync::upsert_order(&ctx.db, merchant_id, &order, &[], &[]).await?;
+    eprintln!(
+        "Order stored: order_id={}, shopify_order_id={}",
+        order_id, order.id
+    );
+    Ok(())
+}
+
+/// Whether an order with Shopify `tags` should be stored: it carries an allowlisted
+/// tag (case-insensitively), or there is no allowlist
+fn passes_ingest_filter(allowlist: &[String], tags: &str) -> bool {
+    allowlist.is_empty()
+        || parse_tags(tags).iter().any(|tag| {
+            let tag = tag.to_lowercase();
+            allowlist
+                .iter()
+                .any(|allowed| allowed.to_lowercase() == tag)
+        })
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Check `X-Shopify-Hmac-Sha256`: base64 HMAC-SHA256 of the raw body, compared in constant time
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn ingest_filter_matches_any_allowlisted_tag() {
+        let allowlist = vec!["wholesale".to_string(), "B2B".to_string()];
+
+        assert!(passes_ingest_filter(&allowlist, "vip, Wholesale"));
+        assert!(passes_ingest_filter(&allowlist, "b2b"));
+        assert!(!passes_ingest_filter(&allowlist, "vip, wholesale-ish"));
+        assert!(!passes_ingest_filter(&allowlist, ""));
+        assert!(passes_ingest_filter(&[], ""));
+    }
+
+    #[test]
+    fn only_bodies_signed_with_the_shared_secret_verify() {
+        let body = br#"{"id":1,"order_id":2,"created_at":"2024-01-05T10:00:00-05:00"}"#;
+        let signature = sign("shpss_secret", body);
+
+        assert!(verify_signature("shpss_secret", body, &signature));
+        assert!(!verify_signature("other_secret", body, &signature));
+        assert!(!verify_signature("shpss_secret", b"{}", &signature));
+        assert!(!verify_signature("shpss_secret", body, "not base64!"));
+    }
+}
@@ -1,19 +1,33 @@
 use anyhow::Context;
 use args::{Args, CliArgs};
 use clap::Parser;
-use sqlx::postgres::PgPoolOptions;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Connection};
+use std::path::Path;
+use std::str::FromStr;
 
 mod args;
 mod auth;
 mod http;
 pub mod misc;
 pub mod shopify;
+#[cfg(test)]
+mod test_support;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli_args = CliArgs::parse();
+    let mut cli_args = CliArgs::parse();
+    // Parse again once the env file is loaded so clap's `env` attributes see its values
+    if let Some(path) = args::load_env_file(&cli_args)? {
+        println!("📄 Loaded environment from {}", path.display());
+        cli_args = CliArgs::parse();
+    }
     let config = Args::from(cli_args);
+    config.validate()?;
 
+    let connect_options =
+        PgConnectOptions::from_str(&config.database_url).context("could not parse database_url")?;
     let db = PgPoolOptions::new()
         // The default connection limit for a Postgres server is 100 connections, minus 3 for superusers.
         // Since we're using the default superuser we don't have to worry about this too much,
@@ -22,20 +36,60 @@ async fn main() -> anyhow::Result<()> {
         // If you're deploying your application with multiple replicas, then the total
         // across all replicas should not exceed the Postgres connection limit.
         .max_connections(50)
-        .connect(&config.database_url)
+        .connect_with(http::with_statement_timeout(
+            connect_options.clone(),
+            config.db_statement_timeout_ms,
+        ))
         .await
         .context("could not connect to database_url")?;
 
-    sqlx::migrate!("./sql/migrations")
-        .run(&db)
+    // Migrations (index builds, backfills) may rightly outlast the statement timeout
+    let mut migration_conn = connect_options
+        .connect()
+        .await
+        .context("could not connect to database_url")?;
+    migrator(&config)
+        .await?
+        .run(&mut migration_conn)
         .await
         .context("could not run migrations")?;
+    migration_conn.close().await?;
+
+    // Read-only endpoints use the replica when one is configured, see `http::ReadPool`
+    let read_db = match &config.read_replica_url {
+        Some(url) => {
+            let replica_options =
+                PgConnectOptions::from_str(url).context("could not parse read_replica_url")?;
+            PgPoolOptions::new()
+                .max_connections(50)
+                .connect_with(http::with_statement_timeout(
+                    replica_options,
+                    config.db_statement_timeout_ms,
+                ))
+                .await
+                .context("could not connect to read_replica_url")?
+        }
+        None => db.clone(),
+    };
 
-    http::serve(config, db).await?;
+    http::serve(config, db, read_db).await?;
 
     Ok(())
 }
 
+/// Migrations from `--migrations-dir` when given, otherwise the set embedded at build time
+async fn migrator(config: &Args) -> anyhow::Result<Migrator> {
+    match &config.migrations_dir {
+        Some(dir) => {
+            println!("📦 Loading migrations from {}", dir);
+            Migrator::new(Path::new(dir))
+                .await
+                .with_context(|| format!("could not load migrations from {}", dir))
+        }
+        None => Ok(sqlx::migrate!("./sql/migrations")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::shopify::ShopifyClient;
@@ -61,7 +115,7 @@ mod tests {
         println!("=== Shopify Products (first 5) ===");
         for product in &products {
             println!(
-                "- {} (id: {}) | status: {:?} | variants: {}",
+                "- {:?} (id: {}) | status: {:?} | variants: {}",
                 product.title,
                 product.id,
                 product.status,
@@ -70,13 +124,20 @@ mod tests {
         }
 
         let orders = client
-            .get_orders(Some(5), None, Some("any"), None)
+            .get_orders(
+                Some(5),
+                None,
+                &crate::shopify::types::OrderFilter {
+                    status: Some("any".to_string()),
+                    ..Default::default()
+                },
+            )
             .await
             .map_err(|e| anyhow::anyhow!(e))?;
         println!("=== Shopify Orders (first 5) ===");
         for order in &orders {
             println!(
-                "- {} (id: {}) | status: {:?} | total_price: {} | items: {}",
+                "- {:?} (id: {}) | status: {:?} | total_price: {:?} | items: {}",
                 order.name,
                 order.id,
                 order.financial_status,
@@ -1,10 +1,11 @@
 use anyhow;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
-use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey};
+use rsa::pkcs8::{DecodePublicKey, EncodePrivateKey, EncodePublicKey};
 use rsa::traits::PublicKeyParts;
 use rsa::{RsaPrivateKey, RsaPublicKey};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::fs;
 
 pub struct KeyPair {
@@ -12,9 +13,8 @@ pub struct KeyPair {
     pub public_key: String,
 }
 
-pub fn generate_key_pair() -> anyhow::Result<KeyPair> {
-    println!("Generating RSA key pair...");
-
+/// Generate a fresh 2048-bit RSA key pair in PEM format without writing anything to disk
+pub fn generate_rsa_key_pair() -> anyhow::Result<KeyPair> {
     // Generate a new RSA private key (2048 bits)
     let mut rng = rand::thread_rng();
     let private_key = RsaPrivateKey::new(&mut rng, 2048)?;
@@ -24,11 +24,42 @@ pub fn generate_key_pair() -> anyhow::Result<KeyPair> {
     let private_key_pem = private_key.to_pkcs8_pem(rsa::pkcs8::LineEnding::LF)?;
     let public_key_pem = public_key.to_public_key_pem(rsa::pkcs8::LineEnding::LF)?;
 
+    Ok(KeyPair {
+        private_key: private_key_pem.to_string(),
+        public_key: public_key_pem,
+    })
+}
+
+/// `kid` for an RSA public key: its RFC 7638 JWK thumbprint
+///
+/// Derived from the key itself, so every place that names the key agrees on it
+/// and rotated keys never collide.
+pub fn key_id(public_key_pem: &str) -> anyhow::Result<String> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)?;
+    Ok(jwk_thumbprint(
+        &URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+        &URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+    ))
+}
+
+/// SHA-256 over the required RSA members in lexicographic order, base64url-encoded
+fn jwk_thumbprint(n: &str, e: &str) -> String {
+    let canonical = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+pub fn generate_key_pair() -> anyhow::Result<KeyPair> {
+    println!("Generating RSA key pair...");
+
+    let keys = generate_rsa_key_pair()?;
+    let public_key = RsaPublicKey::from_public_key_pem(&keys.public_key)?;
+    let kid = key_id(&keys.public_key)?;
+
     // Create the keys.json structure
     let keys_data = json!({
-        "private_key": private_key_pem.as_str(),
-        "public_key": public_key_pem,
-        "key_id": "exchange_api_key_1",
+        "private_key": keys.private_key,
+        "public_key": keys.public_key,
+        "key_id": kid,
         "algorithm": "RS256",
         "generated_at": chrono::Utc::now().to_rfc3339()
     });
@@ -44,11 +75,11 @@ pub fn generate_key_pair() -> anyhow::Result<KeyPair> {
     let public_key_data = json!({
         "keys": [{
             "kty": "RSA",
-            "kid": "exchange_api_key_1",
+            "kid": kid,
             "use": "sig",
             "alg": "RS256",
-            "n": URL_SAFE_NO_PAD.encode(&public_key.n().to_bytes_be()),
-            "e": URL_SAFE_NO_PAD.encode(&public_key.e().to_bytes_be())
+            "n": URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be())
         }]
     });
 
@@ -60,8 +91,20 @@ pub fn generate_key_pair() -> anyhow::Result<KeyPair> {
 
     println!("📄 Public key saved to: {}", public_key_path);
 
-    Ok(KeyPair {
-        private_key: private_key_pem.to_string(),
-        public_key: public_key_pem,
-    })
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbprint_matches_rfc7638_example() {
+        // Example key and expected thumbprint from RFC 7638, section 3.1
+        let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+        assert_eq!(
+            jwk_thumbprint(n, "AQAB"),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
 }
@@ -1,2 +1,6 @@
 pub mod keypair;
+pub mod password_hash;
+pub mod password_policy;
+pub mod redact;
+pub mod retry;
 pub mod validator;
@@ -0,0 +1,131 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, PasswordVerifier, Version};
+use sha2::{Digest, Sha256};
+
+use crate::http::AppError;
+
+/// Argon2id cost used when hashing passwords
+///
+/// Built from `Args` via `Args::password_hash_params()`; `Default` is the OWASP
+/// baseline (19 MiB, 2 passes, 1 lane). Stored hashes carry the parameters they
+/// were made with, so changing these only affects new and re-hashed passwords.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordHashParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl PasswordHashParams {
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Why these parameters can't be used, if they can't
+    pub fn check(&self) -> Result<(), String> {
+        self.argon2().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Whether `stored` should be replaced by a fresh hash: legacy SHA-256 hashes
+    /// and Argon2 hashes made with other parameters
+    pub fn needs_rehash(&self, stored: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(stored) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&hash) else {
+            return true;
+        };
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.memory_kib
+            || params.t_cost() != self.iterations
+            || params.p_cost() != self.parallelism
+    }
+}
+
+/// Argon2id PHC string (`$argon2id$v=19$m=...`) for `password` with a random salt
+pub fn hash_password(password: &str, params: &PasswordHashParams) -> Result<String, AppError> {
+    let argon2 = params.argon2().map_err(|e| {
+        eprintln!("Invalid password hash parameters: {}", e);
+        AppError::InternalServerError
+    })?;
+    let salt = SaltString::generate(&mut OsRng);
+    argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            eprintln!("Failed to hash password: {}", e);
+            AppError::InternalServerError
+        })
+}
+
+/// Check `password` against a stored hash
+///
+/// Argon2 hashes are verified with the parameters embedded in them; anything else
+/// is treated as a legacy unsalted SHA-256 hex digest.
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => format!("{:x}", Sha256::digest(password.as_bytes())) == stored,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap enough to keep the tests fast
+    const TEST_PARAMS: PasswordHashParams = PasswordHashParams {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn argon2_hashes_verify_and_record_their_params() {
+        let hash = hash_password("Tr1cky-Horse-Battery", &TEST_PARAMS).unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(verify_password("Tr1cky-Horse-Battery", &hash));
+        assert!(!verify_password("tr1cky-horse-battery", &hash));
+
+        assert!(!TEST_PARAMS.needs_rehash(&hash));
+        let costlier = PasswordHashParams {
+            iterations: 3,
+            ..TEST_PARAMS
+        };
+        assert!(costlier.needs_rehash(&hash));
+        // Old hashes keep verifying after the cost changes
+        assert!(verify_password("Tr1cky-Horse-Battery", &hash));
+    }
+
+    #[test]
+    fn legacy_sha256_hashes_verify_and_need_rehash() {
+        let legacy = format!("{:x}", Sha256::digest(b"password"));
+        assert!(verify_password("password", &legacy));
+        assert!(!verify_password("Password", &legacy));
+        assert!(TEST_PARAMS.needs_rehash(&legacy));
+    }
+
+    #[test]
+    fn unusable_params_are_reported() {
+        let params = PasswordHashParams {
+            memory_kib: 1,
+            ..TEST_PARAMS
+        };
+        assert!(params.check().is_err());
+        assert!(TEST_PARAMS.check().is_ok());
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use crate::http::AppError;
+
+/// Frequently breached passwords that still satisfy the character-class rules,
+/// one per line, lower case
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Password rules applied whenever a password is set
+///
+/// Built from `Args` via `Args::password_policy()`; `Default` matches the
+/// out-of-the-box configuration.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_mixed_case: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub reject_common: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: true,
+            reject_common: true,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Names of the rules `password` fails, empty if it is acceptable
+    pub fn violations(&self, password: &str) -> Vec<String> {
+        let mut failed = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            failed.push(format!("min_length:{}", self.min_length));
+        }
+        if self.require_mixed_case
+            && !(password.chars().any(char::is_uppercase)
+                && password.chars().any(char::is_lowercase))
+        {
+            failed.push("mixed_case".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            failed.push("digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            failed.push("symbol".to_string());
+        }
+        if self.reject_common && is_common(password) {
+            failed.push("common_password".to_string());
+        }
+
+        failed
+    }
+
+    /// Fail with a field-level validation error on `password` listing every broken rule
+    pub fn validate(&self, password: &str) -> Result<(), AppError> {
+        let failed = self.violations(password);
+        if failed.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::FieldValidation(BTreeMap::from([(
+            "password".to_string(),
+            failed,
+        )])))
+    }
+}
+
+fn is_common(password: &str) -> bool {
+    let password = password.to_lowercase();
+    COMMON_PASSWORDS.lines().any(|common| common == password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_every_failed_rule() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(
+            policy.violations("abc"),
+            vec!["min_length:8", "mixed_case", "digit", "symbol"]
+        );
+        assert!(policy.violations("Tr1cky-Horse-Battery").is_empty());
+    }
+
+    #[test]
+    fn rejects_common_passwords_that_pass_other_rules() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.violations("P@ssw0rd"), vec!["common_password"]);
+
+        let lenient = PasswordPolicy {
+            reject_common: false,
+            ..PasswordPolicy::default()
+        };
+        assert!(lenient.violations("P@ssw0rd").is_empty());
+    }
+}
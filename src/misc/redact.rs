@@ -0,0 +1,135 @@
+use axum::http::HeaderMap;
+use serde_json::Value;
+
+/// Field and header names whose values must never appear in logs
+pub const SENSITIVE_FIELDS: &[&str] = &[
+    "password",
+    "access_token",
+    "refresh_token",
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "key",    // Plaintext key in the create API key response
+    "tokens", // POST /auth/verify-batch
+    "token",  // POST /auth/decode
+];
+
+pub const REDACTED: &str = "***";
+
+fn is_sensitive(name: &str) -> bool {
+    SENSITIVE_FIELDS
+        .iter()
+        .any(|field| field.eq_ignore_ascii_case(name))
+}
+
+/// Recursively mask sensitive fields in a JSON value, including inside nested objects and arrays
+pub fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if is_sensitive(key) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// Render a request/response body for logging with sensitive fields masked
+///
+/// Non-JSON bodies are never logged verbatim since we can't tell what they contain.
+pub fn redact_body(body: &[u8]) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut json) => {
+            redact_json(&mut json);
+            json.to_string()
+        }
+        Err(_) => format!("<{} bytes non-JSON body>", body.len()),
+    }
+}
+
+/// Render headers for logging with sensitive values masked
+pub fn redact_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_sensitive(name.as_str()) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_body_password_is_masked() {
+        let body = br#"{"email":"admin@test-shop.com","password":"admin123"}"#;
+        let logged = redact_body(body);
+        assert!(logged.contains(r#""password":"***""#));
+        assert!(logged.contains(r#""email":"admin@test-shop.com""#));
+        assert!(!logged.contains("admin123"));
+    }
+
+    #[test]
+    fn nested_tokens_are_masked() {
+        let body = br#"{"success":true,"data":{"access_token":"a.b.c","refresh_token":"d.e.f","user":{"email":"x@y.com"}}}"#;
+        let logged = redact_body(body);
+        assert!(!logged.contains("a.b.c"));
+        assert!(!logged.contains("d.e.f"));
+        assert!(logged.contains(r#""access_token":"***""#));
+    }
+
+    #[test]
+    fn authorization_header_is_masked() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let logged = redact_headers(&headers);
+        assert!(logged.contains("authorization: ***"));
+        assert!(logged.contains("content-type: application/json"));
+        assert!(!logged.contains("secret"));
+    }
+
+    #[test]
+    fn api_keys_are_masked() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "ak_3f9c1a2bsecret".parse().unwrap());
+        let logged = redact_headers(&headers);
+        assert_eq!(logged, "x-api-key: ***");
+
+        let body = br#"{"key":"ak_3f9c1a2bsecret","key_prefix":"ak_3f9c1a2b","scopes":["read"]}"#;
+        let logged = redact_body(body);
+        assert!(logged.contains(r#""key":"***""#));
+        assert!(logged.contains(r#""key_prefix":"ak_3f9c1a2b""#));
+        assert!(!logged.contains("secret"));
+    }
+
+    #[test]
+    fn verify_batch_tokens_are_masked() {
+        let body = br#"{"tokens":["a.b.c","d.e.f"]}"#;
+        let logged = redact_body(body);
+        assert_eq!(logged, r#"{"tokens":"***"}"#);
+    }
+
+    #[test]
+    fn decoded_token_is_masked() {
+        let body = br#"{"token":"eyJhbGciOiJSUzI1NiJ9.eyJzdWIiOiJ4In0.sig"}"#;
+        let logged = redact_body(body);
+        assert_eq!(logged, r#"{"token":"***"}"#);
+    }
+}
@@ -0,0 +1,200 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::http::AppError;
+
+/// Total attempts, including the first
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Backoff before retry `n` is `BASE_DELAY * 2^n` plus up to as much again in jitter
+const BASE_DELAY: Duration = Duration::from_millis(25);
+
+/// Errors that may succeed if the whole operation is simply run again
+pub trait Transient {
+    fn is_transient(&self) -> bool;
+
+    /// Transient, and certain to have committed nothing: an I/O error during COMMIT
+    /// may hide a transaction that went through
+    fn is_uncommitted(&self) -> bool;
+}
+
+impl Transient for sqlx::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            // Connection reset/refused, or no pooled connection in time
+            sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+            // serialization_failure, deadlock_detected
+            sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("40001" | "40P01")),
+            _ => false,
+        }
+    }
+
+    fn is_uncommitted(&self) -> bool {
+        match self {
+            // The operation never got a connection
+            sqlx::Error::PoolTimedOut => true,
+            // Postgres rolled the transaction back
+            sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("40001" | "40P01")),
+            _ => false,
+        }
+    }
+}
+
+impl Transient for AppError {
+    fn is_transient(&self) -> bool {
+        match self {
+            AppError::Unavailable => true,
+            AppError::Database(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+
+    fn is_uncommitted(&self) -> bool {
+        match self {
+            AppError::Unavailable => true,
+            AppError::Database(e) => e.is_uncommitted(),
+            _ => false,
+        }
+    }
+}
+
+/// Run `op` again (up to `MAX_ATTEMPTS` in total) while it fails with a transient error
+///
+/// `op` must be safe to repeat: a whole transaction that rolls back on error, not
+/// one that also calls out to Shopify. Constraint violations and other errors are
+/// returned immediately.
+pub async fn retry_transient<T, E, F, Fut>(op: F) -> Result<T, E>
+where
+    E: Transient + std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_while(op, E::is_transient).await
+}
+
+/// `retry_transient` for transactions that aren't idempotent, e.g. deletes
+///
+/// Only retries errors that guarantee nothing was committed, so a delete that went
+/// through before the connection dropped isn't run again and reported as a 404.
+pub async fn retry_uncommitted<T, E, F, Fut>(op: F) -> Result<T, E>
+where
+    E: Transient + std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_while(op, E::is_uncommitted).await
+}
+
+async fn retry_while<T, E, F, Fut>(mut op: F, retryable: fn(&E) -> bool) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(e) if attempt < MAX_ATTEMPTS && retryable(&e) => {
+                let backoff = BASE_DELAY * 2u32.pow(attempt - 1);
+                let jitter = rand::thread_rng().gen_range(Duration::ZERO..=backoff);
+                eprintln!(
+                    "Transient database error (attempt {}/{}), retrying: {}",
+                    attempt, MAX_ATTEMPTS, e
+                );
+                tokio::time::sleep(backoff + jitter).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::database_url;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn io_errors_are_not_retried_around_mutations() {
+        let io = || sqlx::Error::Io(std::io::ErrorKind::ConnectionReset.into());
+        assert!(io().is_transient());
+        assert!(!io().is_uncommitted());
+        assert!(sqlx::Error::PoolTimedOut.is_uncommitted());
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), _> = retry_uncommitted(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AppError::Database(io()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), _> = retry_uncommitted(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AppError::Unavailable)
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn only_transient_errors_are_retried() -> anyhow::Result<()> {
+        let Some(database_url) = database_url() else {
+            return Ok(());
+        };
+
+        let db = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url)
+            .await?;
+        let raise = |sqlstate: &str| {
+            format!(
+                "DO $$ BEGIN RAISE EXCEPTION 'simulated' USING ERRCODE = '{}'; END $$",
+                sqlstate
+            )
+        };
+
+        // A serialization failure on the first attempt succeeds on the second
+        let attempts = AtomicU32::new(0);
+        let value = retry_transient(|| async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                sqlx::query(&raise("40001")).execute(&db).await?;
+            }
+            sqlx::query_scalar::<_, i32>("SELECT 1")
+                .fetch_one(&db)
+                .await
+        })
+        .await?;
+        assert_eq!(value, 1);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // A unique violation is returned straight away
+        let attempts = AtomicU32::new(0);
+        let result = retry_transient(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            sqlx::query(&raise("23505")).execute(&db).await
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // A failure that never clears gives up after MAX_ATTEMPTS
+        let attempts = AtomicU32::new(0);
+        let result = retry_transient(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            sqlx::query(&raise("40P01")).execute(&db).await
+        })
+        .await;
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use regex::Regex;
 
 use crate::http::AppError;
+use crate::misc::password_policy::PasswordPolicy;
 
 /// Validates email format using regex
 /// Matches standard email format: user@domain.com
@@ -13,33 +14,36 @@ pub fn validate_email(email: &str) -> Result<bool, AppError> {
         .ok_or_else(|| AppError::Validation("Invalid email format".to_string()))
 }
 
-/// Validates password strength using regex
-/// Requirements:
-/// - At least 8 characters long
-/// - At least one uppercase letter
-/// - At least one lowercase letter  
-/// - At least one digit
-/// - At least one special character
-pub fn validate_password(password: &str) -> Result<bool, AppError> {
-    // Check minimum length
-    if password.len() < 8 {
-        return Err(AppError::Validation("Password must be at least 8 characters long".to_string()));
-    }
-
-    // Check for at least one uppercase letter
-    let has_uppercase = Regex::new(r"[A-Z]").unwrap().is_match(password);
-
-    // Check for at least one lowercase letter
-    let has_lowercase = Regex::new(r"[a-z]").unwrap().is_match(password);
-
-    // Check for at least one digit
-    let has_digit = Regex::new(r"[0-9]").unwrap().is_match(password);
-
-    // Check for at least one special character - using a different approach
-    // Match any non-alphanumeric character
-    let has_special = !password.chars().all(|c| c.is_alphanumeric());
+/// Validates password strength against a `PasswordPolicy`
+/// Errors list every failed rule under the `password` field
+pub fn validate_password(password: &str, policy: &PasswordPolicy) -> Result<bool, AppError> {
+    policy.validate(password).map(|_| true)
+}
 
-    (has_uppercase && has_lowercase && has_digit && has_special)
+/// Active ISO 4217 currency codes
+const ISO_4217_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD",
+    "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN",
+    "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF",
+    "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS",
+    "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA",
+    "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD",
+    "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN",
+    "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// Validates that a currency is a known ISO 4217 code (e.g. USD, GBP)
+/// Codes must be upper case, matching how Shopify reports them
+pub fn validate_currency(currency: &str) -> Result<bool, AppError> {
+    ISO_4217_CODES
+        .contains(&currency)
         .then_some(true)
-        .ok_or_else(|| AppError::Validation("Password must contain uppercase, lowercase, digit, and special character".to_string()))
+        .ok_or_else(|| {
+            AppError::Validation(format!("Invalid ISO 4217 currency code: {}", currency))
+        })
 }
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::shopify::types::ShopInfo;
+
+/// Store info rarely changes, so a few minutes of staleness is fine
+pub const SHOP_INFO_TTL: Duration = Duration::from_secs(300);
+
+/// Per-merchant cache of `/shop.json` responses
+pub struct ShopInfoCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<Uuid, (Instant, ShopInfo)>>,
+}
+
+impl ShopInfoCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Cached shop info for `merchant_id`, if fetched within the TTL
+    pub fn get(&self, merchant_id: Uuid) -> Option<ShopInfo> {
+        let entries = self.entries.lock().expect("shop cache lock poisoned");
+        entries
+            .get(&merchant_id)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, shop)| shop.clone())
+    }
+
+    pub fn insert(&self, merchant_id: Uuid, shop: ShopInfo) {
+        let mut entries = self.entries.lock().expect("shop cache lock poisoned");
+        entries.insert(merchant_id, (Instant::now(), shop));
+    }
+}
+
+impl Default for ShopInfoCache {
+    fn default() -> Self {
+        Self::new(SHOP_INFO_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shop() -> ShopInfo {
+        ShopInfo {
+            id: 1,
+            name: "Test Shop".to_string(),
+            domain: "test-shop.com".to_string(),
+            myshopify_domain: "test-shop.myshopify.com".to_string(),
+            plan_name: Some("basic".to_string()),
+            plan_display_name: Some("Basic".to_string()),
+            currency: "USD".to_string(),
+            iana_timezone: Some("America/New_York".to_string()),
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let cache = ShopInfoCache::new(Duration::from_millis(50));
+        let merchant_id = Uuid::new_v4();
+        cache.insert(merchant_id, shop());
+
+        assert_eq!(cache.get(merchant_id).unwrap().name, "Test Shop");
+        assert!(cache.get(Uuid::new_v4()).is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(cache.get(merchant_id).is_none());
+    }
+}
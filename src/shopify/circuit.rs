@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::shopify::types::ShopifyErrorType;
+
+/// When a circuit opens and how long it stays open
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit; 0 disables the breaker
+    pub failure_threshold: u32,
+    /// How long calls fail fast before a single probe call is let through
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// The cooldown is over and one probe call, started at `since`, is in flight
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// Fails Shopify calls fast while a store keeps failing
+///
+/// Closed: calls go through and consecutive failures are counted. At
+/// `failure_threshold` the circuit opens and every call fails with
+/// `ShopifyErrorType::CircuitOpen` until the cooldown ends. Then it is half-open:
+/// the next call is let through as a probe (others still fail fast). A successful
+/// probe closes the circuit; a failed one opens it for another cooldown. A probe
+/// that records neither (its future was dropped) is given up on after a cooldown,
+/// and the next call becomes the new probe.
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go out now; the caller must then record its outcome
+    pub fn before_call(&self) -> Result<(), ShopifyErrorType> {
+        if self.config.failure_threshold == 0 {
+            return Ok(());
+        }
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+            State::HalfOpen { since } if now >= since + self.config.cooldown => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+            State::Open { .. } | State::HalfOpen { .. } => Err(ShopifyErrorType::CircuitOpen),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            // A failed probe, or a straggler that was sent before the circuit opened
+            State::HalfOpen { .. } | State::Open { .. } => self.config.failure_threshold,
+        };
+        *state = if failures >= self.config.failure_threshold {
+            State::Open {
+                until: Instant::now() + self.config.cooldown,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+/// One circuit breaker per merchant, shared by every client built for that merchant
+pub struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    breakers: Mutex<HashMap<Uuid, Arc<CircuitBreaker>>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, merchant_id: Uuid) -> Arc<CircuitBreaker> {
+        let mut breakers = self
+            .breakers
+            .lock()
+            .expect("circuit breakers lock poisoned");
+        breakers
+            .entry(merchant_id)
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(self.config)))
+            .clone()
+    }
+}
+
+impl Default for CircuitBreakers {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_probes_once_after_cooldown() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            cooldown: Duration::from_millis(50),
+        });
+
+        breaker.before_call().unwrap();
+        breaker.record_failure();
+        breaker.before_call().unwrap();
+        breaker.record_failure();
+        assert!(matches!(
+            breaker.before_call(),
+            Err(ShopifyErrorType::CircuitOpen)
+        ));
+
+        std::thread::sleep(Duration::from_millis(60));
+        // Only one probe goes out while half-open
+        breaker.before_call().unwrap();
+        assert!(breaker.before_call().is_err());
+
+        // A failed probe reopens the circuit straight away
+        breaker.record_failure();
+        assert!(breaker.before_call().is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.before_call().unwrap();
+        breaker.record_success();
+        breaker.before_call().unwrap();
+        breaker.before_call().unwrap();
+    }
+
+    #[test]
+    fn an_abandoned_probe_is_replaced_after_a_cooldown() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 1,
+            cooldown: Duration::from_millis(50),
+        });
+
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(60));
+        // The probe is let through but never records an outcome
+        breaker.before_call().unwrap();
+        assert!(breaker.before_call().is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.before_call().unwrap();
+        assert!(breaker.before_call().is_err());
+        breaker.record_success();
+        breaker.before_call().unwrap();
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        });
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.before_call().is_ok());
+    }
+
+    #[test]
+    fn zero_threshold_disables_the_breaker() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 0,
+            cooldown: Duration::from_secs(60),
+        });
+
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.before_call().is_ok());
+    }
+}
@@ -1,16 +1,48 @@
+use crate::shopify::circuit::CircuitBreaker;
+use crate::shopify::ids::{ShopifyInventoryItemId, ShopifyOrderId, ShopifyProductId};
 use crate::shopify::types::*;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream, TryStreamExt};
 use reqwest::Client;
-use std::time::Duration;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Shopify's maximum page size for list endpoints
+const PAGE_SIZE: usize = 250;
+
+/// A rate-limited page is retried this many times by default, waiting 1s, 2s, 4s... in between
+pub const MAX_RATE_LIMIT_RETRIES: u32 = 4;
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Histogram of Shopify request durations in seconds, labeled by `endpoint` and `status`
+pub const REQUEST_DURATION_METRIC: &str = "shopify_request_duration_seconds";
+/// Counter of 429 responses from Shopify, labeled by `endpoint`
+pub const RATE_LIMITED_METRIC: &str = "shopify_rate_limited_total";
+
+/// Records one Shopify call; `status` is "error" when no response came back.
+/// These are no-ops unless a metrics recorder has been installed.
+fn record_request(endpoint: &'static str, status: Option<reqwest::StatusCode>, elapsed: Duration) {
+    let status_label = status.map_or_else(|| "error".to_string(), |s| s.as_u16().to_string());
+    metrics::histogram!(REQUEST_DURATION_METRIC, "endpoint" => endpoint, "status" => status_label)
+        .record(elapsed.as_secs_f64());
+    if status == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+        metrics::counter!(RATE_LIMITED_METRIC, "endpoint" => endpoint).increment(1);
+    }
+}
 
 /// Shopify Admin API Client
 /// 
 /// This client handles authentication and API calls to Shopify Admin API
 /// to retrieve products and orders.
 pub struct ShopifyClient {
-    store_name: String,
+    /// Scheme and host of the store, e.g. `https://my-store.myshopify.com`
+    api_root: String,
     access_token: String,
     api_version: String,
     client: Client,
+    breaker: Arc<CircuitBreaker>,
+    max_retries: u32,
 }
 
 impl ShopifyClient {
@@ -27,19 +59,42 @@ impl ShopifyClient {
             .expect("Failed to create HTTP client");
 
         Self {
-            store_name,
+            api_root: format!("https://{}.myshopify.com", store_name),
             access_token,
             api_version,
             client,
+            breaker: Arc::new(CircuitBreaker::default()),
+            max_retries: MAX_RATE_LIMIT_RETRIES,
         }
     }
 
+    /// Send requests to `base_url` instead of `https://{store}.myshopify.com`
+    ///
+    /// Meant for pointing the client at a local mock server in tests; the
+    /// `/admin/api/{version}` path is still appended.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.api_root = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Share `breaker` with other clients for the same store
+    ///
+    /// Without this each client has its own breaker, which only helps callers
+    /// that keep one client around (e.g. a long sync).
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    /// Retry a rate-limited page this many times when streaming (0 never retries)
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
     /// Build the base URL for API requests
     fn base_url(&self) -> String {
-        format!(
-            "https://{}.myshopify.com/admin/api/{}",
-            self.store_name, self.api_version
-        )
+        format!("{}/admin/api/{}", self.api_root, self.api_version)
     }
 
     /// Build headers for authenticated requests
@@ -67,7 +122,7 @@ impl ShopifyClient {
     pub async fn get_products(
         &self,
         limit: Option<u32>,
-        since_id: Option<i64>,
+        since_id: Option<ShopifyProductId>,
     ) -> Result<Vec<ShopifyProduct>, ShopifyErrorType> {
         let limit = limit.unwrap_or(250).min(250);
         let url = format!("{}/products.json", self.base_url());
@@ -78,26 +133,34 @@ impl ShopifyClient {
         }
 
         let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .query(&query_params)
-            .send()
+            .send("products", self.client.get(&url).query(&query_params))
             .await?;
 
         self.handle_response(response).await
     }
 
+    /// Every product in the store, fetched one page at a time as the stream is polled
+    ///
+    /// Only the current page is held in memory. The stream ends after the last
+    /// page, or after yielding the first error.
+    pub fn stream_products(
+        &self,
+    ) -> impl Stream<Item = Result<ShopifyProduct, ShopifyErrorType>> + '_ {
+        paginate(
+            move |since_id| self.get_products(Some(PAGE_SIZE as u32), since_id),
+            |product: &ShopifyProduct| product.id,
+            self.max_retries,
+        )
+    }
+
     /// Fetch a single product by ID
-    pub async fn get_product(&self, product_id: i64) -> Result<ShopifyProduct, ShopifyErrorType> {
+    pub async fn get_product(
+        &self,
+        product_id: ShopifyProductId,
+    ) -> Result<ShopifyProduct, ShopifyErrorType> {
         let url = format!("{}/products/{}.json", self.base_url(), product_id);
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .send()
-            .await?;
+        let response = self.send("products/:id", self.client.get(&url)).await?;
 
         let mut wrapper: serde_json::Value = response.json().await?;
         let product = serde_json::from_value(
@@ -107,8 +170,174 @@ impl ShopifyClient {
         Ok(product)
     }
 
+    /// Fetch all metafields attached to a product
+    ///
+    /// Pages through the metafields endpoint using `since_id` until a short
+    /// page is returned, so products with many metafields are fully loaded.
+    pub async fn get_product_metafields(
+        &self,
+        product_id: ShopifyProductId,
+    ) -> Result<Vec<ShopifyMetafield>, ShopifyErrorType> {
+        const PAGE_SIZE: usize = 250;
+        let url = format!(
+            "{}/products/{}/metafields.json",
+            self.base_url(),
+            product_id
+        );
+
+        let mut metafields = Vec::new();
+        let mut since_id: Option<i64> = None;
+        loop {
+            let mut query_params = vec![("limit", PAGE_SIZE.to_string())];
+            if let Some(id) = since_id {
+                query_params.push(("since_id", id.to_string()));
+            }
+
+            let response = self
+                .send(
+                    "products/:id/metafields",
+                    self.client.get(&url).query(&query_params),
+                )
+                .await?;
+
+            let page: Vec<ShopifyMetafield> = self.handle_response(response).await?;
+            let page_len = page.len();
+            since_id = page.iter().map(|m| m.id).max();
+            metafields.extend(page);
+
+            if page_len < PAGE_SIZE || since_id.is_none() {
+                break;
+            }
+        }
+
+        Ok(metafields)
+    }
+
+    /// Fetch every image of a product, in Shopify's display order
+    ///
+    /// Products without images return an empty list.
+    pub async fn get_product_images(
+        &self,
+        product_id: ShopifyProductId,
+    ) -> Result<Vec<ShopifyProductImage>, ShopifyErrorType> {
+        let url = format!("{}/products/{}/images.json", self.base_url(), product_id);
+
+        let response = self
+            .send("products/:id/images", self.client.get(&url))
+            .await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Fetch basic store info (name, domain, plan, currency, timezone)
+    ///
+    /// This is the cheapest authenticated call in the Admin API, so it also
+    /// serves as a check that the access token is still valid.
+    pub async fn get_shop(&self) -> Result<ShopInfo, ShopifyErrorType> {
+        let url = format!("{}/shop.json", self.base_url());
+
+        let response = self.send("shop", self.client.get(&url)).await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Fetch every location of the store, deactivated ones included
+    ///
+    /// A store without multi-location inventory has exactly one, its default location.
+    pub async fn get_locations(&self) -> Result<Vec<ShopifyLocation>, ShopifyErrorType> {
+        let url = format!("{}/locations.json", self.base_url());
+
+        let response = self.send("locations", self.client.get(&url)).await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Check the store name, access token and API version with one `/shop.json` call
+    ///
+    /// Never fails: each failure mode is reported in its own field instead.
+    /// - no response (DNS, connection refused, timeout) or 404: the store doesn't exist
+    /// - 401/403: the token was rejected
+    /// - 400/406: the API version was rejected
+    /// - 200 from a different version (`X-Shopify-API-Version`): Shopify fell back
+    ///   from an unsupported version, so the requested one is not ok
+    ///
+    /// Other statuses (5xx, 429) leave the token and version unknown.
+    pub async fn test_connection(&self) -> ConnectionStatus {
+        let mut status = ConnectionStatus {
+            reachable: false,
+            authenticated: None,
+            api_version_ok: None,
+            shop_name: None,
+        };
+
+        let url = format!("{}/shop.json", self.base_url());
+        let response = match self.client.get(&url).headers(self.headers()).send().await {
+            Ok(response) => response,
+            Err(_) => return status,
+        };
+        let code = response.status().as_u16();
+        if code == 404 {
+            return status;
+        }
+        status.reachable = true;
+
+        match code {
+            401 | 403 => status.authenticated = Some(false),
+            400 | 406 => status.api_version_ok = Some(false),
+            200..=299 => {
+                status.authenticated = Some(true);
+                status.api_version_ok = Some(
+                    response
+                        .headers()
+                        .get("X-Shopify-API-Version")
+                        .and_then(|version| version.to_str().ok())
+                        .is_none_or(|version| version == self.api_version),
+                );
+                status.shop_name = self
+                    .handle_response::<ShopInfo>(response)
+                    .await
+                    .ok()
+                    .map(|shop| shop.name);
+            }
+            _ => {}
+        }
+        status
+    }
+
+    /// Adjust the available quantity of an inventory item at a location
+    ///
+    /// # Arguments
+    /// * `inventory_item_id` - Shopify inventory item ID
+    /// * `location_id` - Shopify location ID
+    /// * `delta` - Amount to add (negative to remove)
+    ///
+    /// # Returns
+    /// The inventory level after Shopify applied the adjustment
+    pub async fn adjust_inventory(
+        &self,
+        inventory_item_id: ShopifyInventoryItemId,
+        location_id: i64,
+        delta: i32,
+    ) -> Result<ShopifyInventoryLevel, ShopifyErrorType> {
+        let url = format!("{}/inventory_levels/adjust.json", self.base_url());
+
+        let body = serde_json::json!({
+            "inventory_item_id": inventory_item_id,
+            "location_id": location_id,
+            "available_adjustment": delta,
+        });
+        let response = self
+            .send(
+                "inventory_levels/adjust",
+                self.client.post(&url).json(&body),
+            )
+            .await?;
+
+        self.handle_response(response).await
+    }
+
     /// Fetch orders from Shopify
-    /// 
+    ///
     /// # Arguments
     /// * `limit` - Maximum number of orders to fetch per page (default: 250, max: 250)
     /// * `since_id` - Fetch orders with ID greater than this value (for pagination)
@@ -120,9 +349,8 @@ impl ShopifyClient {
     pub async fn get_orders(
         &self,
         limit: Option<u32>,
-        since_id: Option<i64>,
-        status: Option<&str>,
-        financial_status: Option<&str>,
+        since_id: Option<ShopifyOrderId>,
+        filter: &OrderFilter,
     ) -> Result<Vec<ShopifyOrder>, ShopifyErrorType> {
         let limit = limit.unwrap_or(250).min(250);
         let url = format!("{}/orders.json", self.base_url());
@@ -131,35 +359,105 @@ impl ShopifyClient {
         if let Some(id) = since_id {
             query_params.push(("since_id", id.to_string()));
         }
-        if let Some(s) = status {
+        if let Some(s) = &filter.status {
             query_params.push(("status", s.to_string()));
         }
-        if let Some(fs) = financial_status {
+        if let Some(fs) = &filter.financial_status {
             query_params.push(("financial_status", fs.to_string()));
         }
+        if let Some(min) = filter.created_at_min {
+            query_params.push(("created_at_min", min.to_rfc3339()));
+        }
+        if let Some(max) = filter.created_at_max {
+            query_params.push(("created_at_max", max.to_rfc3339()));
+        }
 
         let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .query(&query_params)
-            .send()
+            .send("orders", self.client.get(&url).query(&query_params))
             .await?;
 
         self.handle_response(response).await
     }
 
-    /// Fetch a single order by ID
-    pub async fn get_order(&self, order_id: i64) -> Result<ShopifyOrder, ShopifyErrorType> {
-        let url = format!("{}/orders/{}.json", self.base_url(), order_id);
+    /// Every order matching `filter`, fetched one page at a time as the stream is polled
+    ///
+    /// Rate-limited pages are retried with backoff, so callers only see a
+    /// `RateLimit` error once Shopify keeps refusing. The stream ends after the
+    /// last page, or after yielding the first error.
+    pub fn stream_orders(
+        &self,
+        filter: OrderFilter,
+    ) -> impl Stream<Item = Result<ShopifyOrder, ShopifyErrorType>> + '_ {
+        paginate(
+            move |since_id| {
+                let filter = filter.clone();
+                async move {
+                    self.get_orders(Some(PAGE_SIZE as u32), since_id, &filter)
+                        .await
+                }
+            },
+            |order: &ShopifyOrder| order.id,
+            self.max_retries,
+        )
+    }
+
+    /// One page of abandoned checkouts created at or after `created_at_min`
+    ///
+    /// Checkouts have their own endpoint, paged by `since_id` like orders.
+    async fn get_abandoned_checkouts_page(
+        &self,
+        created_at_min: Option<DateTime<Utc>>,
+        since_id: Option<i64>,
+    ) -> Result<Vec<ShopifyCheckout>, ShopifyErrorType> {
+        let url = format!("{}/checkouts.json", self.base_url());
+
+        let mut query_params = vec![("limit", PAGE_SIZE.to_string())];
+        if let Some(id) = since_id {
+            query_params.push(("since_id", id.to_string()));
+        }
+        if let Some(created_at_min) = created_at_min {
+            query_params.push(("created_at_min", created_at_min.to_rfc3339()));
+        }
 
         let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .send()
+            .send("checkouts", self.client.get(&url).query(&query_params))
             .await?;
 
+        self.handle_response(response).await
+    }
+
+    /// Every abandoned checkout created at or after `created_at_min` (all of them
+    /// when `None`), fetched one page at a time as the stream is polled
+    pub fn stream_abandoned_checkouts(
+        &self,
+        created_at_min: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<ShopifyCheckout, ShopifyErrorType>> + '_ {
+        paginate(
+            move |since_id| self.get_abandoned_checkouts_page(created_at_min, since_id),
+            |checkout: &ShopifyCheckout| checkout.id,
+            self.max_retries,
+        )
+    }
+
+    /// Fetch every abandoned checkout created at or after `created_at_min`
+    pub async fn get_abandoned_checkouts(
+        &self,
+        created_at_min: Option<DateTime<Utc>>,
+    ) -> Result<Vec<ShopifyCheckout>, ShopifyErrorType> {
+        self.stream_abandoned_checkouts(created_at_min)
+            .try_collect()
+            .await
+    }
+
+    /// Fetch a single order by ID
+    pub async fn get_order(
+        &self,
+        order_id: ShopifyOrderId,
+    ) -> Result<ShopifyOrder, ShopifyErrorType> {
+        let url = format!("{}/orders/{}.json", self.base_url(), order_id);
+
+        let response = self.send("orders/:id", self.client.get(&url)).await?;
+
         let mut wrapper: serde_json::Value = response.json().await?;
         let order = serde_json::from_value(
             wrapper["order"].take()
@@ -168,6 +466,71 @@ impl ShopifyClient {
         Ok(order)
     }
 
+    /// Fetch every refund issued against an order
+    pub async fn get_order_refunds(
+        &self,
+        order_id: ShopifyOrderId,
+    ) -> Result<Vec<ShopifyRefund>, ShopifyErrorType> {
+        let url = format!("{}/orders/{}/refunds.json", self.base_url(), order_id);
+
+        let response = self
+            .send("orders/:id/refunds", self.client.get(&url))
+            .await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Fetch every fulfillment (shipment) of an order
+    pub async fn get_order_fulfillments(
+        &self,
+        order_id: ShopifyOrderId,
+    ) -> Result<Vec<ShopifyFulfillment>, ShopifyErrorType> {
+        let url = format!("{}/orders/{}/fulfillments.json", self.base_url(), order_id);
+
+        let response = self
+            .send("orders/:id/fulfillments", self.client.get(&url))
+            .await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Send an authenticated request through the circuit breaker
+    ///
+    /// Fails fast with `CircuitOpen` while the circuit is open. Transport errors and
+    /// 5xx responses count as failures; any other response (including 4xx and 429)
+    /// means Shopify is up.
+    ///
+    /// Each attempt is timed into [`REQUEST_DURATION_METRIC`] under `endpoint`, a route
+    /// template such as `orders/:id` so ids don't blow up the label set.
+    async fn send(
+        &self,
+        endpoint: &'static str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ShopifyErrorType> {
+        self.breaker.before_call()?;
+        let started = Instant::now();
+        let result = request.headers(self.headers()).send().await;
+        record_request(
+            endpoint,
+            result.as_ref().ok().map(|r| r.status()),
+            started.elapsed(),
+        );
+        match result {
+            Ok(response) if !response.status().is_server_error() => {
+                self.breaker.record_success();
+                Ok(response)
+            }
+            Ok(response) => {
+                self.breaker.record_failure();
+                Ok(response)
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                Err(e.into())
+            }
+        }
+    }
+
     /// Handle API response and check for errors
     async fn handle_response<T>(&self, response: reqwest::Response) -> Result<T, ShopifyErrorType>
     where
@@ -206,11 +569,29 @@ impl ShopifyClient {
         let json: serde_json::Value = serde_json::from_str(&text)
             .map_err(|e| ShopifyErrorType::Api(format!("Invalid JSON: {}", e)))?;
 
-        // Handle both {products: [...]} and {orders: [...]} formats
+        // Handle {products: [...]}, {orders: [...]}, {metafields: [...]}, {images: [...]},
+        // {refunds: [...]}, {fulfillments: [...]}, {checkouts: [...]}, {locations: [...]},
+        // {shop: {...}} and {inventory_level: {...}} formats
         let data = if json.get("products").is_some() {
             json["products"].clone()
         } else if json.get("orders").is_some() {
             json["orders"].clone()
+        } else if json.get("metafields").is_some() {
+            json["metafields"].clone()
+        } else if json.get("images").is_some() {
+            json["images"].clone()
+        } else if json.get("refunds").is_some() {
+            json["refunds"].clone()
+        } else if json.get("fulfillments").is_some() {
+            json["fulfillments"].clone()
+        } else if json.get("checkouts").is_some() {
+            json["checkouts"].clone()
+        } else if json.get("locations").is_some() {
+            json["locations"].clone()
+        } else if json.get("shop").is_some() {
+            json["shop"].clone()
+        } else if json.get("inventory_level").is_some() {
+            json["inventory_level"].clone()
         } else {
             json
         };
@@ -220,9 +601,79 @@ impl ShopifyClient {
     }
 }
 
+/// Walk a `since_id`-paginated list endpoint as a stream of items
+///
+/// `since_id` is the cursor: each page asks for ids above the largest one seen so
+/// far, and a short page means there is nothing left to fetch. A page that hits
+/// Shopify's rate limit is retried with exponential backoff.
+fn paginate<'a, T, I, F, Fut>(
+    fetch_page: F,
+    id_of: fn(&T) -> I,
+    max_retries: u32,
+) -> impl Stream<Item = Result<T, ShopifyErrorType>> + 'a
+where
+    T: 'a,
+    I: Copy + Ord + 'a,
+    F: FnMut(Option<I>) -> Fut + 'a,
+    Fut: Future<Output = Result<Vec<T>, ShopifyErrorType>> + 'a,
+{
+    // `None` once the last page has been fetched
+    let cursor: Option<Option<I>> = Some(None);
+    stream::try_unfold(
+        (fetch_page, cursor),
+        move |(mut fetch_page, cursor)| async move {
+            let Some(since_id) = cursor else {
+                return Ok::<_, ShopifyErrorType>(None);
+            };
+            let mut retries = 0;
+            let page = loop {
+                match fetch_page(since_id).await {
+                    Err(ShopifyErrorType::RateLimit) if retries < max_retries => {
+                        tokio::time::sleep(RATE_LIMIT_BACKOFF * 2u32.pow(retries)).await;
+                        retries += 1;
+                    }
+                    result => break result?,
+                }
+            };
+            let next = match page.iter().map(id_of).max() {
+                Some(max_id) if page.len() >= PAGE_SIZE => Some(Some(max_id)),
+                _ => None,
+            };
+            Ok(Some((
+                stream::iter(page.into_iter().map(Ok)),
+                (fetch_page, next),
+            )))
+        },
+    )
+    .try_flatten()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shopify::circuit::CircuitBreakerConfig;
+    use futures::StreamExt;
+    use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn requests_are_recorded_by_endpoint_and_status() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        metrics::with_local_recorder(&recorder, || {
+            record_request(
+                "orders",
+                Some(reqwest::StatusCode::TOO_MANY_REQUESTS),
+                Duration::from_millis(120),
+            );
+            record_request("orders/:id", None, Duration::from_millis(30));
+        });
+
+        let rendered = recorder.handle().render();
+        assert!(rendered.contains(r#"shopify_rate_limited_total{endpoint="orders"} 1"#));
+        assert!(rendered.contains(r#"endpoint="orders",status="429""#));
+        assert!(rendered.contains(r#"endpoint="orders/:id",status="error""#));
+        assert!(!rendered.contains(r#"shopify_rate_limited_total{endpoint="orders/:id"}"#));
+    }
 
     #[test]
     fn test_base_url() {
@@ -236,12 +687,295 @@ mod tests {
             "https://test-store.myshopify.com/admin/api/2024-10"
         );
     }
-}
 
+    /// Serves ids 1..=total in `since_id` pages, recording each cursor it was asked for
+    fn fake_pages(
+        total: i64,
+        cursors: &std::sync::Mutex<Vec<Option<i64>>>,
+    ) -> impl Stream<Item = Result<i64, ShopifyErrorType>> + '_ {
+        paginate(
+            move |since_id: Option<i64>| {
+                cursors.lock().unwrap().push(since_id);
+                let start = since_id.unwrap_or(0) + 1;
+                let end = (start + PAGE_SIZE as i64 - 1).min(total);
+                async move { Ok((start..=end).collect::<Vec<_>>()) }
+            },
+            |id: &i64| *id,
+            MAX_RATE_LIMIT_RETRIES,
+        )
+    }
 
+    #[tokio::test]
+    async fn paginated_stream_ends_after_the_last_page() {
+        let cursors = std::sync::Mutex::new(Vec::new());
+        let ids: Vec<i64> = fake_pages(503, &cursors).try_collect().await.unwrap();
+        assert_eq!(ids, (1..=503).collect::<Vec<_>>());
+        assert_eq!(*cursors.lock().unwrap(), vec![None, Some(250), Some(500)]);
+
+        // A full last page costs one extra, empty request
+        let cursors = std::sync::Mutex::new(Vec::new());
+        let ids: Vec<i64> = fake_pages(500, &cursors).try_collect().await.unwrap();
+        assert_eq!(ids.len(), 500);
+        assert_eq!(*cursors.lock().unwrap(), vec![None, Some(250), Some(500)]);
+    }
 
+    #[tokio::test]
+    async fn paginated_stream_fetches_pages_lazily() {
+        let cursors = std::sync::Mutex::new(Vec::new());
+        let first: Vec<i64> = fake_pages(10_000, &cursors)
+            .take(3)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(first, vec![1, 2, 3]);
+        assert_eq!(*cursors.lock().unwrap(), vec![None]);
+    }
+    /// Minimal order as the Admin API returns it
+    fn order_json(id: i64) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": format!("#{}", id),
+            "email": null,
+            "created_at": "2024-01-05T10:00:00-05:00",
+            "updated_at": "2024-01-05T10:00:00-05:00",
+            "processed_at": null,
+            "currency": "USD",
+            "subtotal_price": "10.00",
+            "total_price": "10.00",
+            "total_discounts": "0.00",
+            "total_shipping_price_set": { "shop_money": { "amount": "0.00", "currency_code": "USD" } },
+            "total_tax": "0.00",
+            "financial_status": "paid",
+            "fulfillment_status": null,
+            "cancelled_at": null,
+            "line_items": [],
+            "customer": null,
+            "shipping_address": null,
+            "billing_address": null
+        })
+    }
 
+    /// A client for a `wiremock` server standing in for the store
+    fn mock_client(server: &MockServer) -> ShopifyClient {
+        ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri())
+    }
 
+    #[test]
+    fn base_url_override_replaces_the_store_host() {
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url("http://127.0.0.1:9999/");
+        assert_eq!(client.base_url(), "http://127.0.0.1:9999/admin/api/2024-10");
+    }
 
+    #[tokio::test]
+    async fn connection_test_reports_each_failure_in_its_own_field() {
+        const SHOP_PATH: &str = "/admin/api/2024-10/shop.json";
+        let shop = serde_json::json!({ "shop": {
+            "id": 1,
+            "name": "Test Store",
+            "domain": "test-store.com",
+            "myshopify_domain": "test-store.myshopify.com",
+            "currency": "USD"
+        }});
+        let status_for = |response: ResponseTemplate| async move {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path(SHOP_PATH))
+                .and(header("X-Shopify-Access-Token", "token"))
+                .respond_with(response)
+                .mount(&server)
+                .await;
+            mock_client(&server).test_connection().await
+        };
 
+        let ok = status_for(
+            ResponseTemplate::new(200)
+                .insert_header("X-Shopify-API-Version", "2024-10")
+                .set_body_json(shop.clone()),
+        )
+        .await;
+        assert_eq!(
+            ok,
+            ConnectionStatus {
+                reachable: true,
+                authenticated: Some(true),
+                api_version_ok: Some(true),
+                shop_name: Some("Test Store".to_string()),
+            }
+        );
+
+        // Shopify answers an unsupported version with the oldest one it still serves
+        let fell_back = status_for(
+            ResponseTemplate::new(200)
+                .insert_header("X-Shopify-API-Version", "2024-01")
+                .set_body_json(shop),
+        )
+        .await;
+        assert_eq!(fell_back.authenticated, Some(true));
+        assert_eq!(fell_back.api_version_ok, Some(false));
+
+        let bad_token = status_for(ResponseTemplate::new(401)).await;
+        assert!(bad_token.reachable);
+        assert_eq!(bad_token.authenticated, Some(false));
+        assert_eq!(bad_token.api_version_ok, None);
+
+        let bad_version = status_for(ResponseTemplate::new(406)).await;
+        assert!(bad_version.reachable);
+        assert_eq!(bad_version.authenticated, None);
+        assert_eq!(bad_version.api_version_ok, Some(false));
+
+        let bad_store = status_for(ResponseTemplate::new(404)).await;
+        assert!(!bad_store.reachable);
+        assert_eq!(bad_store.authenticated, None);
+
+        // Nothing listening at all
+        let server = MockServer::start().await;
+        let unreachable = mock_client(&server);
+        drop(server);
+        assert!(!unreachable.test_connection().await.reachable);
+    }
+
+    #[tokio::test]
+    async fn order_stream_filters_backs_off_and_ends_after_the_last_page() {
+        const TOTAL: i64 = 260;
+        let server = MockServer::start().await;
+        const ORDERS_PATH: &str = "/admin/api/2024-10/orders.json";
+        // Throttle the very first request
+        Mock::given(method("GET"))
+            .and(path(ORDERS_PATH))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        for (cursor, ids) in [(None, 1..=250), (Some("250"), 251..=TOTAL)] {
+            let orders: Vec<_> = ids.map(order_json).collect();
+            let page = Mock::given(method("GET")).and(path(ORDERS_PATH));
+            let page = match cursor {
+                Some(since_id) => page.and(query_param("since_id", since_id)),
+                None => page.and(query_param_is_missing("since_id")),
+            };
+            page.respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "orders": orders })),
+            )
+            .mount(&server)
+            .await;
+        }
+
+        let filter = OrderFilter {
+            status: Some("any".to_string()),
+            financial_status: Some("paid".to_string()),
+            ..Default::default()
+        };
+        let client = mock_client(&server);
+        let ids: Vec<i64> = client
+            .stream_orders(filter)
+            .map_ok(|order| order.id.into_inner())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, (1..=TOTAL).collect::<Vec<_>>());
+
+        // The throttled first page is retried, then one more page finishes the stream
+        let requests = server.received_requests().await.unwrap();
+        let params: Vec<std::collections::HashMap<String, String>> = requests
+            .iter()
+            .map(|r| r.url.query_pairs().into_owned().collect())
+            .collect();
+        let cursors: Vec<_> = params.iter().map(|p| p.get("since_id").cloned()).collect();
+        assert_eq!(cursors, vec![None, None, Some("250".to_string())]);
+        assert!(params
+            .iter()
+            .all(|p| p["status"] == "any" && p["financial_status"] == "paid"));
+    }
 
+    #[tokio::test]
+    async fn abandoned_checkouts_page_by_since_id_and_keep_the_date_filter() {
+        const CHECKOUTS_PATH: &str = "/admin/api/2024-10/checkouts.json";
+        let server = MockServer::start().await;
+        for (cursor, ids) in [(None, 1..=250), (Some("250"), 251..=253)] {
+            let checkouts: Vec<_> = ids
+                .map(|id| serde_json::json!({ "id": id, "created_at": "2024-03-01T00:00:00Z" }))
+                .collect();
+            let page = Mock::given(method("GET"))
+                .and(path(CHECKOUTS_PATH))
+                .and(query_param("created_at_min", "2024-03-01T00:00:00+00:00"));
+            let page = match cursor {
+                Some(since_id) => page.and(query_param("since_id", since_id)),
+                None => page.and(query_param_is_missing("since_id")),
+            };
+            page.respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "checkouts": checkouts })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        }
+
+        let created_at_min = "2024-03-01T00:00:00Z".parse().unwrap();
+        let checkouts = mock_client(&server)
+            .get_abandoned_checkouts(Some(created_at_min))
+            .await
+            .unwrap();
+        assert_eq!(checkouts.len(), 253);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_ends_the_stream_with_an_authentication_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/products.json"))
+            .and(header("X-Shopify-Access-Token", "token"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = mock_client(&server);
+        let results: Vec<_> = client.stream_products().collect().await;
+        assert!(matches!(
+            results.as_slice(),
+            [Err(ShopifyErrorType::Authentication)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn server_errors_open_the_circuit_and_later_calls_fail_fast() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/shop.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let breaker = Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        }));
+        let client = mock_client(&server).with_circuit_breaker(breaker.clone());
+        for _ in 0..2 {
+            assert!(matches!(
+                client.get_shop().await,
+                Err(ShopifyErrorType::Api(_))
+            ));
+        }
+
+        // Another client sharing the breaker doesn't reach Shopify either
+        let other = mock_client(&server).with_circuit_breaker(breaker);
+        assert!(matches!(
+            other.get_shop().await,
+            Err(ShopifyErrorType::CircuitOpen)
+        ));
+    }
+}
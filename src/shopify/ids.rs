@@ -0,0 +1,70 @@
+//! Typed Shopify ids.
+//!
+//! Products, variants, orders and inventory items are all numbered with plain
+//! integers, so a variant id passed where a product id belongs still compiles.
+//! Each kind gets its own wrapper; it serializes and binds as the bare `BIGINT`,
+//! and `into_inner` gets the number back out.
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! shopify_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,
+            Serialize, Deserialize, sqlx::Type,
+        )]
+        #[serde(transparent)]
+        #[sqlx(transparent)]
+        pub struct $name(i64);
+
+        impl $name {
+            pub fn into_inner(self) -> i64 {
+                self.0
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(id: i64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+shopify_id!(
+    /// Id of a Shopify product
+    ShopifyProductId
+);
+shopify_id!(
+    /// Id of a single variant of a Shopify product
+    ShopifyVariantId
+);
+shopify_id!(
+    /// Id of a Shopify order
+    ShopifyOrderId
+);
+shopify_id!(
+    /// Id of the inventory item behind a variant, used by the inventory endpoints
+    ShopifyInventoryItemId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_serialize_as_bare_numbers() {
+        let id: ShopifyProductId = serde_json::from_str("632910392").unwrap();
+        assert_eq!(id, ShopifyProductId::from(632910392));
+        assert_eq!(id.into_inner(), 632910392);
+        assert_eq!(serde_json::to_string(&id).unwrap(), "632910392");
+        assert_eq!(id.to_string(), "632910392");
+    }
+}
@@ -1,8 +1,15 @@
+pub mod cache;
+pub mod circuit;
 pub mod client;
+pub mod ids;
+pub mod sync;
 pub mod types;
+pub mod weight;
 
 pub use client::ShopifyClient;
+pub use ids::{ShopifyInventoryItemId, ShopifyOrderId, ShopifyProductId, ShopifyVariantId};
 pub use types::*;
+pub use weight::WeightUnit;
 
 
 
@@ -0,0 +1,1400 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::{Connection, PgConnection, PgPool};
+use std::pin::pin;
+use uuid::Uuid;
+
+use crate::misc::retry::retry_transient;
+use crate::shopify::client::ShopifyClient;
+use crate::shopify::types::*;
+use crate::shopify::weight::WeightUnit;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error("Shopify error: {0}")]
+    Shopify(#[from] ShopifyErrorType),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Sync already running")]
+    AlreadyRunning,
+}
+
+/// One merchant's sync lock, a Postgres advisory lock so it holds across replicas
+///
+/// The lock belongs to a connection detached from the pool. It goes away with that
+/// connection, so a sync that dies or is dropped half-way can't leave it held.
+pub struct SyncLock {
+    conn: PgConnection,
+    key: String,
+}
+
+impl SyncLock {
+    /// Take the merchant's sync lock without waiting; `None` while another sync holds it
+    pub async fn try_acquire(db: &PgPool, merchant_id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let mut conn = db.acquire().await?.detach();
+        let key = format!("sync:{}", merchant_id);
+        let locked: bool =
+            sqlx::query_scalar("SELECT pg_try_advisory_lock(hashtextextended($1, 0))")
+                .bind(&key)
+                .fetch_one(&mut conn)
+                .await?;
+        if !locked {
+            conn.close().await?;
+            return Ok(None);
+        }
+        Ok(Some(Self { conn, key }))
+    }
+
+    /// Unlock and close the connection
+    ///
+    /// The server only drops a closed connection's locks once its backend has exited,
+    /// so unlock first to free the merchant right away. Should that fail, closing
+    /// (or the connection dying) still releases the lock.
+    pub async fn release(mut self) -> Result<(), sqlx::Error> {
+        let unlocked = sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+            .bind(&self.key)
+            .execute(&mut self.conn)
+            .await;
+        self.conn.close().await?;
+        unlocked.map(|_| ())
+    }
+}
+
+/// How many records one `sync_merchant` run mirrored
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncCounts {
+    pub locations: usize,
+    pub products: usize,
+    pub orders: usize,
+    pub checkouts: usize,
+}
+
+/// Mirror a merchant's locations, products, orders and abandoned checkouts, recording progress on the merchant
+///
+/// `merchants.sync_started_at` is set while the sync runs. On success `last_synced_at`
+/// becomes this run's start and an earlier `last_sync_error` is cleared; on failure
+/// the error is stored and `last_synced_at` is left alone (see migration 025).
+/// Checkouts are only fetched from the last successful sync onwards.
+///
+/// Only one sync runs per merchant at a time: while another holds the merchant's
+/// `SyncLock` this returns `AlreadyRunning` straight away and records nothing.
+pub async fn sync_merchant(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<SyncCounts, SyncError> {
+    let lock = SyncLock::try_acquire(db, merchant_id)
+        .await?
+        .ok_or(SyncError::AlreadyRunning)?;
+    sync_merchant_locked(lock, client, db, merchant_id).await
+}
+
+/// `sync_merchant` under a lock the caller already took; the lock is released when done
+pub async fn sync_merchant_locked(
+    lock: SyncLock,
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<SyncCounts, SyncError> {
+    let (started_at, last_synced_at): (DateTime<Utc>, Option<DateTime<Utc>>) = sqlx::query_as(
+        r#"
+        UPDATE merchants
+        SET sync_started_at = NOW()
+        WHERE id = $1
+        RETURNING sync_started_at, last_synced_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_one(db)
+    .await?;
+
+    let result = async {
+        Ok::<_, SyncError>(SyncCounts {
+            locations: sync_locations(client, db, merchant_id).await?,
+            products: sync_products(client, db, merchant_id).await?,
+            orders: sync_orders(client, db, merchant_id).await?,
+            checkouts: sync_checkouts(client, db, merchant_id, last_synced_at).await?,
+        })
+    }
+    .await;
+
+    match &result {
+        Ok(_) => {
+            sqlx::query(
+                r#"
+                UPDATE merchants
+                SET sync_started_at = NULL, last_synced_at = $2,
+                    last_sync_error = NULL, last_sync_error_at = NULL
+                WHERE id = $1
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(started_at)
+            .execute(db)
+            .await?;
+        }
+        Err(e) => {
+            eprintln!("Sync failed: merchant_id={}, error={}", merchant_id, e);
+            sqlx::query(
+                r#"
+                UPDATE merchants
+                SET sync_started_at = NULL, last_sync_error = $2, last_sync_error_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(merchant_id)
+            .bind(e.to_string())
+            .execute(db)
+            .await?;
+        }
+    }
+    lock.release().await?;
+    result
+}
+
+/// Mirror a merchant's Shopify locations, so inventory levels can be shown by name
+///
+/// Single- and multi-location stores alike list every location, so locations
+/// Shopify no longer lists are deleted. Returns the number of locations synced.
+pub async fn sync_locations(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<usize, SyncError> {
+    let locations = client.get_locations().await?;
+    retry_transient(|| upsert_locations(db, merchant_id, &locations)).await?;
+    Ok(locations.len())
+}
+
+/// Replace the merchant's locations with `locations` in one transaction
+async fn upsert_locations(
+    db: &PgPool,
+    merchant_id: Uuid,
+    locations: &[ShopifyLocation],
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    for location in locations {
+        sqlx::query(
+            r#"
+            INSERT INTO locations (
+                merchant_id, shopify_location_id, name, active,
+                address1, address2, city, province, zip, country_code
+            )
+            VALUES ($1, $2, $3, COALESCE($4, TRUE), $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (merchant_id, shopify_location_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                active = EXCLUDED.active,
+                address1 = EXCLUDED.address1,
+                address2 = EXCLUDED.address2,
+                city = EXCLUDED.city,
+                province = EXCLUDED.province,
+                zip = EXCLUDED.zip,
+                country_code = EXCLUDED.country_code
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(location.id)
+        .bind(&location.name)
+        .bind(location.active)
+        .bind(&location.address1)
+        .bind(&location.address2)
+        .bind(&location.city)
+        .bind(&location.province)
+        .bind(&location.zip)
+        .bind(&location.country_code)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let ids: Vec<i64> = locations.iter().map(|location| location.id).collect();
+    sqlx::query(
+        "DELETE FROM locations WHERE merchant_id = $1 AND NOT (shopify_location_id = ANY($2))",
+    )
+    .bind(merchant_id)
+    .bind(&ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Fail with `Uninstalled` once the merchant's latest install was uninstalled
+///
+/// A sync's client keeps the token it was built with, so one running when
+/// `app/uninstalled` arrives checks this before each product, order and checkout
+/// instead of calling Shopify with a dead token until the 401s open the circuit.
+/// Merchants without any install are let through.
+async fn ensure_installed(db: &PgPool, merchant_id: Uuid) -> Result<(), SyncError> {
+    let status: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT status FROM shopify_installs
+        WHERE merchant_id = $1
+        ORDER BY installed_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_optional(db)
+    .await?;
+    match status.as_deref() {
+        Some(status) if status != "active" => Err(ShopifyErrorType::Uninstalled.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Mirror a merchant's Shopify catalog into the local database
+///
+/// Streams every product page by page, upserting the product, its variants,
+/// metafields, images and tags. Returns the number of products synced.
+pub async fn sync_products(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<usize, SyncError> {
+    let mut synced = 0;
+    let mut products = pin!(client.stream_products());
+
+    while let Some(product) = products.try_next().await? {
+        ensure_installed(db, merchant_id).await?;
+        let metafields = client.get_product_metafields(product.id).await?;
+        let images = match &product.images {
+            Some(images) => images.clone(),
+            None => client.get_product_images(product.id).await?,
+        };
+        retry_transient(|| upsert_product(db, merchant_id, &product, &metafields, &images)).await?;
+        synced += 1;
+    }
+
+    Ok(synced)
+}
+
+/// Upsert a single Shopify product with its variants, metafields, images and tags
+///
+/// Only the live row is updated. If the product was soft-deleted here, it is
+/// re-added as a new row and the deleted one is left alone (see migration 020).
+async fn upsert_product(
+    db: &PgPool,
+    merchant_id: Uuid,
+    product: &ShopifyProduct,
+    metafields: &[ShopifyMetafield],
+    images: &[ShopifyProductImage],
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let product_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO products (merchant_id, shopify_product_id, title, product_type, status)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_id, shopify_product_id) WHERE deleted_at IS NULL DO UPDATE
+        SET
+            -- A field the response left out keeps its stored value; null clears it
+            title = CASE WHEN $6 THEN products.title ELSE EXCLUDED.title END,
+            product_type = CASE WHEN $7 THEN products.product_type ELSE EXCLUDED.product_type END,
+            status = CASE WHEN $8 THEN products.status ELSE EXCLUDED.status END
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(product.id)
+    .bind(product.title.as_ref().into_option())
+    .bind(product.product_type.as_ref().into_option())
+    .bind(product.status.as_ref().into_option())
+    .bind(product.title.is_missing())
+    .bind(product.product_type.is_missing())
+    .bind(product.status.is_missing())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for variant in &product.variants {
+        let weight_unit = variant.weight_unit.as_deref().and_then(|unit| {
+            let parsed = WeightUnit::parse(unit);
+            if parsed.is_none() {
+                eprintln!(
+                    "Unknown weight unit, storing the weight without one: merchant_id={}, variant_id={}, weight_unit={:?}",
+                    merchant_id, variant.id, unit
+                );
+            }
+            parsed
+        });
+        sqlx::query(
+            r#"
+            INSERT INTO variants (
+                merchant_id, shopify_variant_id, shopify_product_id,
+                sku, title, barcode, weight, weight_unit
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (merchant_id, shopify_variant_id) DO UPDATE
+            SET
+                shopify_product_id = EXCLUDED.shopify_product_id,
+                sku = EXCLUDED.sku,
+                title = EXCLUDED.title,
+                barcode = EXCLUDED.barcode,
+                weight = EXCLUDED.weight,
+                weight_unit = EXCLUDED.weight_unit
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(variant.id)
+        .bind(variant.product_id)
+        .bind(&variant.sku)
+        .bind(&variant.title)
+        .bind(&variant.barcode)
+        .bind(variant.weight)
+        .bind(weight_unit)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Metafields are replaced wholesale so ones removed in Shopify disappear here too
+    sqlx::query("DELETE FROM product_metafields WHERE product_id = $1")
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for metafield in metafields {
+        sqlx::query(
+            r#"
+            INSERT INTO product_metafields (
+                merchant_id, product_id, shopify_metafield_id, namespace, key, value, type
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(product_id)
+        .bind(metafield.id)
+        .bind(&metafield.namespace)
+        .bind(&metafield.key)
+        .bind(metafield.value_as_string())
+        .bind(&metafield.value_type)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Images are replaced wholesale too; a product with none ends up with no rows
+    sqlx::query("DELETE FROM product_images WHERE product_id = $1")
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for (index, image) in images.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO product_images (
+                merchant_id, product_id, shopify_image_id, src, position, alt
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(product_id)
+        .bind(image.id)
+        .bind(&image.src)
+        .bind(image.position.unwrap_or(index as i32 + 1))
+        .bind(&image.alt)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Tags are replaced wholesale as well, unless the response left them out
+    if !product.tags.is_missing() {
+        let tags = product
+            .tags
+            .as_ref()
+            .into_option()
+            .map_or("", String::as_str);
+        let tag_ids = upsert_tags(&mut tx, merchant_id, &parse_tags(tags)).await?;
+        sqlx::query("DELETE FROM product_tags WHERE product_id = $1")
+            .bind(product_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO product_tags (merchant_id, product_id, tag_id)
+            SELECT $1, $2, UNNEST($3::uuid[])
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(product_id)
+        .bind(&tag_ids)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(product_id)
+}
+
+/// Mirror a merchant's Shopify orders with their line items, refunds and fulfillments
+///
+/// Streams every order (any status) page by page. Refunds and fulfillments are
+/// fetched per order and upserted by their Shopify ids, so re-syncing is idempotent.
+/// Returns the number of orders synced.
+pub async fn sync_orders(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+) -> Result<usize, SyncError> {
+    let filter = OrderFilter {
+        status: Some("any".to_string()),
+        ..Default::default()
+    };
+    mirror_orders(client, db, merchant_id, filter).await
+}
+
+/// Mirror the orders matching `filter`; returns how many were synced
+async fn mirror_orders(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+    filter: OrderFilter,
+) -> Result<usize, SyncError> {
+    let mut synced = 0;
+    let mut orders = pin!(client.stream_orders(filter));
+
+    while let Some(order) = orders.try_next().await? {
+        ensure_installed(db, merchant_id).await?;
+        let refunds = client.get_order_refunds(order.id).await?;
+        let fulfillments = client.get_order_fulfillments(order.id).await?;
+        retry_transient(|| upsert_order(db, merchant_id, &order, &refunds, &fulfillments)).await?;
+        synced += 1;
+    }
+
+    Ok(synced)
+}
+
+/// Run an order backfill (migration 028), one window of `window_days` at a time
+///
+/// Each window asks Shopify only for orders created in it, so no single pass covers
+/// years of orders. `synced_through` moves forward as each window finishes, and a
+/// backfill starts from there, so running it again after a failure resumes at the
+/// window that failed. Returns the number of orders mirrored by this run.
+pub async fn backfill_orders(
+    client: &ShopifyClient,
+    db: &PgPool,
+    backfill_id: Uuid,
+) -> Result<usize, SyncError> {
+    let (merchant_id, created_from, created_to, window_days, synced_through): (
+        Uuid,
+        DateTime<Utc>,
+        DateTime<Utc>,
+        i32,
+        Option<DateTime<Utc>>,
+    ) = sqlx::query_as(
+        r#"
+        SELECT merchant_id, created_from, created_to, window_days, synced_through
+        FROM order_backfills
+        WHERE id = $1
+        "#,
+    )
+    .bind(backfill_id)
+    .fetch_one(db)
+    .await?;
+
+    let mut synced = 0;
+    let mut window_start = synced_through.unwrap_or(created_from);
+    while window_start < created_to {
+        let window_end = (window_start + Duration::days(window_days.into())).min(created_to);
+        // Shopify's created_at_max is inclusive and to the second
+        let filter = OrderFilter {
+            status: Some("any".to_string()),
+            created_at_min: Some(window_start),
+            created_at_max: Some(window_end - Duration::seconds(1)),
+            ..Default::default()
+        };
+        let orders = mirror_orders(client, db, merchant_id, filter).await?;
+        sqlx::query(
+            "UPDATE order_backfills SET synced_through = $2, orders = orders + $3 WHERE id = $1",
+        )
+        .bind(backfill_id)
+        .bind(window_end)
+        .bind(orders as i64)
+        .execute(db)
+        .await?;
+        eprintln!(
+            "Backfilled orders: backfill_id={}, window={}..{}, orders={}",
+            backfill_id, window_start, window_end, orders
+        );
+        synced += orders;
+        window_start = window_end;
+    }
+
+    Ok(synced)
+}
+
+/// Upsert a single Shopify order with its line items, refunds, fulfillments and tags
+pub async fn upsert_order(
+    db: &PgPool,
+    merchant_id: Uuid,
+    order: &ShopifyOrder,
+    refunds: &[ShopifyRefund],
+    fulfillments: &[ShopifyFulfillment],
+) -> Result<i64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    // Shopify leaves the order's own email empty for some channels (e.g. POS) while
+    // the customer record still has one
+    let customer_email = [
+        order.email.as_deref(),
+        order.customer.as_ref().and_then(|c| c.email.as_deref()),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|email| !email.trim().is_empty());
+
+    let order_id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO orders (
+            merchant_id, shopify_order_id, name, processed_at, currency,
+            subtotal_price, total_price, total_discounts,
+            total_shipping_price_set_amount, total_tax, financial_status, cancelled_at,
+            shopify_checkout_id, customer_email
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (merchant_id, shopify_order_id) DO UPDATE
+        SET
+            name = EXCLUDED.name,
+            processed_at = EXCLUDED.processed_at,
+            currency = EXCLUDED.currency,
+            subtotal_price = EXCLUDED.subtotal_price,
+            total_price = EXCLUDED.total_price,
+            total_discounts = EXCLUDED.total_discounts,
+            total_shipping_price_set_amount = EXCLUDED.total_shipping_price_set_amount,
+            total_tax = EXCLUDED.total_tax,
+            financial_status = EXCLUDED.financial_status,
+            cancelled_at = EXCLUDED.cancelled_at,
+            shopify_checkout_id = EXCLUDED.shopify_checkout_id,
+            customer_email = EXCLUDED.customer_email
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order.id)
+    .bind(&order.name)
+    .bind(parse_timestamp(order.processed_at.as_deref()))
+    .bind(&order.currency)
+    .bind(order.subtotal_price.as_deref().and_then(parse_money))
+    .bind(order.total_price.as_deref().and_then(parse_money))
+    .bind(order.total_discounts.as_deref().and_then(parse_money))
+    .bind(
+        order
+            .total_shipping_price_set
+            .as_ref()
+            .and_then(|set| parse_money(&set.shop_money.amount)),
+    )
+    .bind(order.total_tax.as_deref().and_then(parse_money))
+    .bind(&order.financial_status)
+    .bind(parse_timestamp(order.cancelled_at.as_deref()))
+    .bind(order.checkout_id)
+    .bind(customer_email)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for line_item in &order.line_items {
+        sqlx::query(
+            r#"
+            INSERT INTO order_line_items (
+                merchant_id, order_id, shopify_line_item_id, shopify_product_id,
+                shopify_variant_id, title, sku, quantity, price
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE($9, 0))
+            ON CONFLICT (order_id, shopify_line_item_id) DO UPDATE
+            SET
+                shopify_product_id = EXCLUDED.shopify_product_id,
+                shopify_variant_id = EXCLUDED.shopify_variant_id,
+                title = EXCLUDED.title,
+                sku = EXCLUDED.sku,
+                quantity = EXCLUDED.quantity,
+                price = EXCLUDED.price
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(order_id)
+        .bind(line_item.id)
+        .bind(line_item.product_id)
+        .bind(line_item.variant_id)
+        .bind(&line_item.title)
+        .bind(&line_item.sku)
+        .bind(line_item.quantity)
+        .bind(line_item.price.as_deref().and_then(parse_money))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for refund in refunds {
+        sqlx::query(
+            r#"
+            INSERT INTO order_refunds (
+                merchant_id, order_id, shopify_refund_id, amount, reason, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, COALESCE($6, NOW()))
+            ON CONFLICT (order_id, shopify_refund_id) DO UPDATE
+            SET
+                amount = EXCLUDED.amount,
+                reason = EXCLUDED.reason
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(order_id)
+        .bind(refund.id)
+        .bind(refund.amount())
+        .bind(&refund.note)
+        .bind(parse_timestamp(Some(&refund.created_at)))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for fulfillment in fulfillments {
+        upsert_fulfillment(&mut tx, merchant_id, order_id, fulfillment).await?;
+    }
+
+    sqlx::query("DELETE FROM order_discount_codes WHERE order_id = $1")
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await?;
+    for discount_code in &order.discount_codes {
+        sqlx::query(
+            r#"
+            INSERT INTO order_discount_codes (merchant_id, order_id, code, amount, discount_type)
+            VALUES ($1, $2, $3, COALESCE($4, 0), $5)
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(order_id)
+        .bind(&discount_code.code)
+        .bind(parse_money(&discount_code.amount))
+        .bind(&discount_code.discount_type)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let tag_ids = upsert_tags(&mut tx, merchant_id, &parse_tags(&order.tags)).await?;
+    sqlx::query("DELETE FROM order_tags WHERE order_id = $1")
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO order_tags (merchant_id, order_id, tag_id)
+        SELECT $1, $2, UNNEST($3::uuid[])
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order_id)
+    .bind(&tag_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(order_id)
+}
+
+/// Mirror a merchant's abandoned checkouts created at or after `created_at_min`
+///
+/// Pass the previous sync's start time to only fetch new checkouts; `None` fetches
+/// all of them. Upserted by Shopify id, so re-syncing is idempotent. Returns the
+/// number of checkouts synced.
+pub async fn sync_checkouts(
+    client: &ShopifyClient,
+    db: &PgPool,
+    merchant_id: Uuid,
+    created_at_min: Option<DateTime<Utc>>,
+) -> Result<usize, SyncError> {
+    let mut synced = 0;
+    let mut checkouts = pin!(client.stream_abandoned_checkouts(created_at_min));
+
+    while let Some(checkout) = checkouts.try_next().await? {
+        ensure_installed(db, merchant_id).await?;
+        retry_transient(|| upsert_checkout(db, merchant_id, &checkout)).await?;
+        synced += 1;
+    }
+
+    Ok(synced)
+}
+
+async fn upsert_checkout(
+    db: &PgPool,
+    merchant_id: Uuid,
+    checkout: &ShopifyCheckout,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO checkouts (
+            merchant_id, shopify_checkout_id, email, abandoned_checkout_url, currency,
+            total_price, shopify_created_at, completed_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, NOW()), $8)
+        ON CONFLICT (merchant_id, shopify_checkout_id) DO UPDATE
+        SET
+            email = EXCLUDED.email,
+            abandoned_checkout_url = EXCLUDED.abandoned_checkout_url,
+            currency = EXCLUDED.currency,
+            total_price = EXCLUDED.total_price,
+            completed_at = EXCLUDED.completed_at
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(checkout.id)
+    .bind(&checkout.email)
+    .bind(&checkout.abandoned_checkout_url)
+    .bind(&checkout.currency)
+    .bind(checkout.total_price.as_deref().and_then(parse_money))
+    .bind(parse_timestamp(Some(&checkout.created_at)))
+    .bind(parse_timestamp(checkout.completed_at.as_deref()))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Ids of the merchant's tags with these names, creating any that don't exist yet
+///
+/// Names match case-insensitively, so an existing tag keeps its original spelling.
+async fn upsert_tags(
+    conn: &mut PgConnection,
+    merchant_id: Uuid,
+    names: &[String],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    // The no-op update makes RETURNING include tags that already existed
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO tags (merchant_id, name)
+        SELECT $1, UNNEST($2::text[])
+        ON CONFLICT (merchant_id, lower(name)) DO UPDATE SET name = tags.name
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(names)
+    .fetch_all(conn)
+    .await
+}
+
+/// Upsert one fulfillment of a local order (also used by the fulfillments/create webhook)
+pub async fn upsert_fulfillment(
+    conn: &mut PgConnection,
+    merchant_id: Uuid,
+    order_id: i64,
+    fulfillment: &ShopifyFulfillment,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO fulfillments (
+            merchant_id, order_id, shopify_fulfillment_id,
+            status, tracking_number, tracking_company, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, NOW()))
+        ON CONFLICT (order_id, shopify_fulfillment_id) DO UPDATE
+        SET
+            status = EXCLUDED.status,
+            tracking_number = EXCLUDED.tracking_number,
+            tracking_company = EXCLUDED.tracking_company
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order_id)
+    .bind(fulfillment.id)
+    .bind(&fulfillment.status)
+    .bind(&fulfillment.tracking_number)
+    .bind(&fulfillment.tracking_company)
+    .bind(parse_timestamp(Some(&fulfillment.created_at)))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Shopify sends money as decimal strings, e.g. "12.50"
+fn parse_money(amount: &str) -> Option<Decimal> {
+    amount.parse().ok()
+}
+
+/// Shopify timestamps are RFC 3339 with an offset, e.g. "2024-01-05T10:00:00-05:00"
+fn parse_timestamp(timestamp: Option<&str>) -> Option<DateTime<Utc>> {
+    timestamp
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Patch;
+    use crate::test_support::{insert_merchant, test_db};
+    use serde_json::json;
+    use wiremock::matchers::{method, path, path_regex, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn product(id: i64, images: Option<serde_json::Value>) -> serde_json::Value {
+        let mut product = json!({
+            "id": id,
+            "title": format!("Product {}", id),
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+            "variants": []
+        });
+        if let Some(images) = images {
+            product["images"] = images;
+        }
+        product
+    }
+
+    #[test]
+    fn tags_are_trimmed_and_deduplicated_case_insensitively() {
+        assert_eq!(
+            parse_tags(" Summer,sale ,, summer, VIP"),
+            vec!["Summer", "sale", "VIP"]
+        );
+        assert!(parse_tags("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn soft_deleted_products_are_re_added_as_new_rows() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let product: ShopifyProduct = serde_json::from_value(product(7, Some(json!([]))))?;
+
+        let original = upsert_product(&db, merchant_id, &product, &[], &[]).await?;
+        // Re-syncing a live product updates it in place
+        assert_eq!(
+            upsert_product(&db, merchant_id, &product, &[], &[]).await?,
+            original
+        );
+
+        sqlx::query("UPDATE products SET deleted_at = NOW() WHERE id = $1")
+            .bind(original)
+            .execute(&db)
+            .await?;
+        let recreated = upsert_product(&db, merchant_id, &product, &[], &[]).await?;
+
+        let rows = sqlx::query_as::<_, (Uuid, bool)>(
+            r#"
+            SELECT id, deleted_at IS NOT NULL
+            FROM products
+            WHERE merchant_id = $1 AND shopify_product_id = 7
+            ORDER BY deleted_at IS NULL
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_ne!(recreated, original);
+        assert_eq!(rows, vec![(original, true), (recreated, false)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn product_sync_stores_every_image_in_position_order() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+
+        let server = MockServer::start().await;
+        let image = |id: i64, product_id: i64, position: i32| {
+            json!({
+                "id": id,
+                "product_id": product_id,
+                "position": position,
+                "src": format!("https://cdn.test/{}.jpg", id),
+                "alt": null
+            })
+        };
+        let products = json!({ "products": [
+            // Listed out of order; position decides the primary image
+            product(1, Some(json!([image(12, 1, 2), image(11, 1, 1)]))),
+            // No images at all
+            product(2, Some(json!([]))),
+            // Listing without images; fetched from the images endpoint instead
+            product(3, None),
+        ]});
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/products.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(products))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(
+                r"^/admin/api/2024-10/products/\d+/metafields\.json$",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "metafields": [] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/products/3/images.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "images": [image(31, 3, 1)] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+
+        let synced = sync_products(&client, &db, merchant_id).await?;
+        let images = sqlx::query_as::<_, (i64, i64, i32)>(
+            r#"
+            SELECT p.shopify_product_id, i.shopify_image_id, i.position
+            FROM product_images i
+            JOIN products p ON p.id = i.product_id
+            WHERE i.merchant_id = $1
+            ORDER BY p.shopify_product_id, i.position
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_eq!(synced, 3);
+        assert_eq!(images, vec![(1, 11, 1), (1, 12, 2), (3, 31, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_and_null_fields_do_not_fail_deserialization() {
+        let product: ShopifyProduct = serde_json::from_value(json!({
+            "id": 1,
+            "product_type": null,
+            "tags": ""
+        }))
+        .unwrap();
+        assert!(product.title.is_missing());
+        assert_eq!(product.product_type, Patch::Null);
+        assert_eq!(product.tags, Patch::Value(String::new()));
+        assert!(product.variants.is_empty());
+
+        let order: ShopifyOrder = serde_json::from_value(json!({
+            "id": 2,
+            "name": null,
+            "tags": null,
+            "discount_codes": null,
+            "line_items": [{ "id": 3, "quantity": 1, "title": null, "price": null }],
+            "total_shipping_price_set": null
+        }))
+        .unwrap();
+        assert_eq!(order.name, None);
+        assert_eq!(order.tags, "");
+        assert!(order.discount_codes.is_empty());
+        assert_eq!(order.line_items[0].price, None);
+        assert!(order.total_shipping_price_set.is_none());
+    }
+
+    #[tokio::test]
+    async fn fields_left_out_keep_their_stored_values() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let stored = |product_id: Uuid| {
+            let db = db.clone();
+            async move {
+                sqlx::query_as::<_, (Option<String>, Option<String>, i64)>(
+                    r#"
+                    SELECT title, product_type,
+                           (SELECT COUNT(*) FROM product_tags WHERE product_id = p.id)
+                    FROM products p WHERE id = $1
+                    "#,
+                )
+                .bind(product_id)
+                .fetch_one(&db)
+                .await
+            }
+        };
+
+        let mut full = product(9, Some(json!([])));
+        full["product_type"] = json!("Shirts");
+        full["tags"] = json!("summer, sale");
+        let product_id =
+            upsert_product(&db, merchant_id, &serde_json::from_value(full)?, &[], &[]).await?;
+        assert_eq!(
+            stored(product_id).await?,
+            (Some("Product 9".into()), Some("Shirts".into()), 2)
+        );
+
+        // Not returned: everything stays as it was
+        let sparse: ShopifyProduct = serde_json::from_value(json!({ "id": 9 }))?;
+        upsert_product(&db, merchant_id, &sparse, &[], &[]).await?;
+        assert_eq!(
+            stored(product_id).await?,
+            (Some("Product 9".into()), Some("Shirts".into()), 2)
+        );
+
+        // Explicitly empty: cleared
+        let emptied: ShopifyProduct =
+            serde_json::from_value(json!({ "id": 9, "product_type": null, "tags": null }))?;
+        upsert_product(&db, merchant_id, &emptied, &[], &[]).await?;
+        assert_eq!(
+            stored(product_id).await?,
+            (Some("Product 9".into()), None, 0)
+        );
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn variant_weight_units_are_normalized() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+
+        let mut shopify_product = product(4, Some(json!([])));
+        shopify_product["variants"] = json!([
+            { "id": 41, "product_id": 4, "weight": 1.5, "weight_unit": "KILOGRAMS" },
+            { "id": 42, "product_id": 4, "weight": 2.0, "weight_unit": "lb" },
+            { "id": 43, "product_id": 4, "weight": 3.0, "weight_unit": "stone" },
+        ]);
+        upsert_product(
+            &db,
+            merchant_id,
+            &serde_json::from_value(shopify_product)?,
+            &[],
+            &[],
+        )
+        .await?;
+        let variants: Vec<crate::http::Variant> = sqlx::query_as(
+            r#"
+            SELECT id, merchant_id, shopify_variant_id, shopify_product_id, sku, title, barcode,
+                weight::float8 AS weight, weight_unit, created_at, updated_at
+            FROM variants
+            WHERE merchant_id = $1
+            ORDER BY shopify_variant_id
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        let units: Vec<_> = variants.iter().map(|v| v.weight_unit).collect();
+        assert_eq!(
+            units,
+            [Some(WeightUnit::Kilograms), Some(WeightUnit::Pounds), None]
+        );
+        // An unknown unit keeps the weight, but it can't be compared
+        assert_eq!(variants[2].weight, Some(3.0));
+        assert_eq!(variants[0].weight_in_grams(), Some(1500.0));
+        assert_eq!(variants[2].weight_in_grams(), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_merchant_records_failures_and_successes() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        let status = || {
+            sqlx::query_as::<_, (bool, bool, Option<String>)>(
+                r#"
+                SELECT sync_started_at IS NOT NULL, last_synced_at IS NOT NULL, last_sync_error
+                FROM merchants
+                WHERE id = $1
+                "#,
+            )
+            .bind(merchant_id)
+            .fetch_one(&db)
+        };
+
+        // Nothing mounted yet, so the products page is a 404
+        let server = MockServer::start().await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+        assert!(sync_merchant(&client, &db, merchant_id).await.is_err());
+        let (running, synced, error) = status().await?;
+        assert!(!running);
+        assert!(!synced);
+        assert!(error.is_some());
+
+        for (resource, body) in [
+            (
+                "locations",
+                json!({ "locations": [{ "id": 655441491, "name": "Warehouse", "active": true }] }),
+            ),
+            (
+                "products",
+                json!({ "products": [product(1, Some(json!([])))] }),
+            ),
+            ("orders", json!({ "orders": [] })),
+            ("checkouts", json!({ "checkouts": [] })),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/admin/api/2024-10/{}.json", resource)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/products/1/metafields.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "metafields": [] })))
+            .mount(&server)
+            .await;
+        let counts = sync_merchant(&client, &db, merchant_id).await?;
+        let after = status().await?;
+        let locations: Vec<(i64, Option<String>)> = sqlx::query_as(
+            "SELECT shopify_location_id, name FROM locations WHERE merchant_id = $1",
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        assert_eq!(
+            counts,
+            SyncCounts {
+                locations: 1,
+                products: 1,
+                orders: 0,
+                checkouts: 0
+            }
+        );
+        assert_eq!(after, (false, true, None));
+        assert_eq!(locations, [(655441491, Some("Warehouse".to_string()))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backfill_resumes_from_the_last_finished_window() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        // Two windows: [Jan 1, Jan 31) and [Jan 31, Mar 1)
+        let backfill_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO order_backfills (merchant_id, created_from, created_to, window_days)
+            VALUES ($1, '2024-01-01T00:00:00Z', '2024-03-01T00:00:00Z', 30)
+            RETURNING id
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await?;
+        let progress = || {
+            sqlx::query_as::<_, (Option<DateTime<Utc>>, i64)>(
+                "SELECT synced_through, orders FROM order_backfills WHERE id = $1",
+            )
+            .bind(backfill_id)
+            .fetch_one(&db)
+        };
+
+        let server = MockServer::start().await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+        let window = |min: &str, max: &str, order_id: i64| {
+            Mock::given(method("GET"))
+                .and(path("/admin/api/2024-10/orders.json"))
+                .and(query_param("created_at_min", min))
+                .and(query_param("created_at_max", max))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    json!({ "orders": [{ "id": order_id, "name": format!("#{}", order_id) }] }),
+                ))
+        };
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/admin/api/2024-10/orders/\d+/refunds\.json$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "refunds": [] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(
+                r"^/admin/api/2024-10/orders/\d+/fulfillments\.json$",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "fulfillments": [] })))
+            .mount(&server)
+            .await;
+        let first = window("2024-01-01T00:00:00+00:00", "2024-01-30T23:59:59+00:00", 1);
+        first.expect(1).mount(&server).await;
+
+        // The second window isn't mounted yet, so it fails after the first is recorded
+        assert!(backfill_orders(&client, &db, backfill_id).await.is_err());
+        let jan_31 = "2024-01-31T00:00:00Z".parse::<DateTime<Utc>>()?;
+        assert_eq!(progress().await?, (Some(jan_31), 1));
+
+        // Running it again only fetches the window that failed
+        window("2024-01-31T00:00:00+00:00", "2024-02-29T23:59:59+00:00", 2)
+            .mount(&server)
+            .await;
+        assert_eq!(backfill_orders(&client, &db, backfill_id).await?, 1);
+        let mar_1 = "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        assert_eq!(progress().await?, (Some(mar_1), 2));
+        let orders: Vec<i64> = sqlx::query_scalar(
+            "SELECT shopify_order_id FROM orders WHERE merchant_id = $1 ORDER BY shopify_order_id",
+        )
+        .bind(merchant_id)
+        .fetch_all(&db)
+        .await?;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        assert_eq!(orders, [1, 2]);
+        server.verify().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn syncs_stop_once_the_app_is_uninstalled() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO shopify_installs (merchant_id, access_scopes, installed_at, status, access_token)
+            VALUES ($1, 'read_products,read_orders', NOW(), 'active', 'token')
+            "#,
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/products.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "products": [product(1, Some(json!([])))] })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/admin/api/2024-10/orders.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "orders": [{ "id": 1, "name": "#1" }] })),
+            )
+            .mount(&server)
+            .await;
+        // Per-item calls that would only get 401s with the dropped token
+        Mock::given(path_regex(r"/(metafields|refunds|fulfillments)\.json$"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(0)
+            .mount(&server)
+            .await;
+        // Built before the uninstall, as for a sync already running
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+
+        // What `app/uninstalled` does (see `mark_uninstalled`)
+        sqlx::query(
+            "UPDATE shopify_installs SET status = 'uninstalled', access_token = NULL WHERE merchant_id = $1",
+        )
+        .bind(merchant_id)
+        .execute(&db)
+        .await?;
+        let products = sync_products(&client, &db, merchant_id).await;
+        let orders = sync_orders(&client, &db, merchant_id).await;
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+
+        for result in [products, orders] {
+            assert!(
+                matches!(
+                    result,
+                    Err(SyncError::Shopify(ShopifyErrorType::Uninstalled))
+                ),
+                "{:?}",
+                result
+            );
+        }
+        server.verify().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_one_sync_runs_per_merchant() -> anyhow::Result<()> {
+        let Some(db) = test_db().await? else {
+            return Ok(());
+        };
+        let merchant_id = insert_merchant(&db).await?;
+        // Never called: a sync that gets past the lock fails on the 404 and records it
+        let server = MockServer::start().await;
+        let client = ShopifyClient::new(
+            "test-store".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        )
+        .with_base_url(server.uri());
+
+        // Held through a separate pool, as another replica would
+        let other_replica = PgPool::connect_with((*db.connect_options()).clone()).await?;
+        let lock = SyncLock::try_acquire(&other_replica, merchant_id).await?;
+        assert!(lock.is_some());
+        assert!(SyncLock::try_acquire(&db, merchant_id).await?.is_none());
+        let second = sync_merchant(&client, &db, merchant_id).await;
+        assert!(
+            matches!(second, Err(SyncError::AlreadyRunning)),
+            "{:?}",
+            second
+        );
+        let (started, error): (Option<DateTime<Utc>>, Option<String>) =
+            sqlx::query_as("SELECT sync_started_at, last_sync_error FROM merchants WHERE id = $1")
+                .bind(merchant_id)
+                .fetch_one(&db)
+                .await?;
+        assert_eq!((started, error), (None, None));
+
+        // Other merchants aren't blocked, and releasing frees the merchant's lock
+        let other = SyncLock::try_acquire(&db, Uuid::new_v4()).await?;
+        assert!(other.is_some());
+        other.unwrap().release().await?;
+        lock.unwrap().release().await?;
+        let again = SyncLock::try_acquire(&db, merchant_id).await?;
+        assert!(again.is_some());
+        again.unwrap().release().await?;
+        let result = sync_merchant(&client, &db, merchant_id).await;
+        assert!(matches!(result, Err(SyncError::Shopify(_))), "{:?}", result);
+
+        sqlx::query("DELETE FROM merchants WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await?;
+        Ok(())
+    }
+}
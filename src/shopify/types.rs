@@ -1,6 +1,32 @@
-use serde::{Deserialize, Serialize};
+//! Shopify API response types.
+//!
+//! Shopify leaves fields out of some responses (e.g. with a `fields` filter or on older
+//! API versions) and sends `null` for others, so one odd record must not fail a whole
+//! sync. Only ids, and what a record can't be stored without, are required. The rest:
+//!
+//! | Field type | Missing | `null` |
+//! |------------|---------|--------|
+//! | `Option<T>` | `None`, stored as NULL | `None`, stored as NULL |
+//! | `Patch<T>` | `Missing`, the stored value is kept | `Null`, the stored value is cleared |
+//! | `null_as_default` | empty (`""`, `[]`) | empty |
+//!
+//! `Patch` is for product fields that a partial response may leave out but that we
+//! store, so "not returned" never overwrites what we have.
 
-// Shopify API Response Types
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::ids::{ShopifyInventoryItemId, ShopifyOrderId, ShopifyProductId, ShopifyVariantId};
+use crate::http::Patch;
+
+/// Deserialize `null` like a missing field, as `T::default()`; use with `#[serde(default)]`
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
 
 #[derive(Debug, Deserialize)]
 pub struct ShopifyApiResponse<T> {
@@ -11,64 +37,244 @@ pub struct ShopifyApiResponse<T> {
 // Product Types
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ShopifyProduct {
-    pub id: i64,
-    pub title: String,
+    pub id: ShopifyProductId,
+    #[serde(default, skip_serializing_if = "Patch::is_missing")]
+    pub title: Patch<String>,
     pub body_html: Option<String>,
     pub vendor: Option<String>,
-    pub product_type: Option<String>,
-    pub created_at: String,
-    pub updated_at: String,
-    pub status: Option<String>,
+    /// `Null` or `Value("")` when the merchant left it empty
+    #[serde(default, skip_serializing_if = "Patch::is_missing")]
+    pub product_type: Patch<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_missing")]
+    pub status: Patch<String>,
+    /// Comma-separated, e.g. "summer, sale"; see `parse_tags`. `Missing` keeps the stored tags
+    #[serde(default, skip_serializing_if = "Patch::is_missing")]
+    pub tags: Patch<String>,
+    /// Variants not listed are left alone, so a response without any changes nothing
+    #[serde(default, deserialize_with = "null_as_default")]
     pub variants: Vec<ShopifyVariant>,
-    pub images: Vec<ShopifyProductImage>,
+    /// `None` when the listing left images out (e.g. a `fields` filter); fetch them separately
+    #[serde(default)]
+    pub images: Option<Vec<ShopifyProductImage>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ShopifyVariant {
-    pub id: i64,
-    pub product_id: i64,
-    pub title: String,
-    pub price: String,
+    pub id: ShopifyVariantId,
+    pub product_id: ShopifyProductId,
+    pub title: Option<String>,
+    pub price: Option<String>,
     pub sku: Option<String>,
     pub barcode: Option<String>,
     pub weight: Option<f64>,
     pub weight_unit: Option<String>,
     pub inventory_quantity: Option<i32>,
-    pub inventory_item_id: Option<i64>,
+    pub inventory_item_id: Option<ShopifyInventoryItemId>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ShopifyProductImage {
     pub id: i64,
-    pub product_id: i64,
+    pub product_id: ShopifyProductId,
+    /// 1-based display order; position 1 is the primary image
+    pub position: Option<i32>,
     pub src: String,
     pub alt: Option<String>,
 }
 
-// Order Types
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ShopifyOrder {
+pub struct ShopifyMetafield {
+    pub id: i64,
+    pub namespace: String,
+    pub key: String,
+    pub value: serde_json::Value,
+    #[serde(rename = "type")]
+    pub value_type: Option<String>,
+}
+
+impl ShopifyMetafield {
+    /// Metafield values come back as strings, numbers or JSON depending on
+    /// their type, so normalise them to text for storage.
+    pub fn value_as_string(&self) -> Option<String> {
+        match &self.value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+/// Split Shopify's comma-separated `tags` field into distinct, trimmed tag names
+///
+/// Shopify treats tags case-insensitively, so the first spelling of a tag wins.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in tags.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        if !names
+            .iter()
+            .any(|seen| seen.to_lowercase() == name.to_lowercase())
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+// Shop Types
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopInfo {
     pub id: i64,
     pub name: String,
+    pub domain: String,
+    pub myshopify_domain: String,
+    pub plan_name: Option<String>,
+    pub plan_display_name: Option<String>,
+    pub currency: String,
+    pub iana_timezone: Option<String>,
+}
+
+/// Outcome of `ShopifyClient::test_connection`, one field per setting being checked
+///
+/// `None` means the check couldn't tell, e.g. the token can't be judged when the
+/// store doesn't exist.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ConnectionStatus {
+    /// The store exists and answered
+    pub reachable: bool,
+    /// The access token was accepted
+    pub authenticated: Option<bool>,
+    /// Shopify served the requested API version rather than rejecting or replacing it
+    pub api_version_ok: Option<bool>,
+    pub shop_name: Option<String>,
+}
+
+// Inventory Types
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyInventoryLevel {
+    pub inventory_item_id: ShopifyInventoryItemId,
+    pub location_id: i64,
+    pub available: Option<i32>,
+    pub updated_at: Option<String>,
+}
+
+/// A place stock is kept; every store has at least one, its default location
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyLocation {
+    pub id: i64,
+    pub name: Option<String>,
+    /// Deactivated locations are still listed, with `false`
+    pub active: Option<bool>,
+    pub address1: Option<String>,
+    pub address2: Option<String>,
+    pub city: Option<String>,
+    pub province: Option<String>,
+    pub zip: Option<String>,
+    pub country_code: Option<String>,
+}
+
+// Order Types
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyOrder {
+    pub id: ShopifyOrderId,
+    pub name: Option<String>,
     pub email: Option<String>,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
     pub processed_at: Option<String>,
-    pub currency: String,
-    pub subtotal_price: String,
-    pub total_price: String,
-    pub total_discounts: String,
-    pub total_shipping_price_set: ShopifyPriceSet,
-    pub total_tax: String,
+    pub currency: Option<String>,
+    pub subtotal_price: Option<String>,
+    pub total_price: Option<String>,
+    pub total_discounts: Option<String>,
+    pub total_shipping_price_set: Option<ShopifyPriceSet>,
+    pub total_tax: Option<String>,
     pub financial_status: Option<String>,
     pub fulfillment_status: Option<String>,
     pub cancelled_at: Option<String>,
+    /// The checkout the order was placed from, if any; links an abandoned checkout to its order
+    pub checkout_id: Option<i64>,
+    /// Comma-separated, e.g. "wholesale, vip"; see `parse_tags`
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub tags: String,
+    /// Codes the customer entered at checkout; an order can carry several
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub discount_codes: Vec<ShopifyDiscountCode>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub line_items: Vec<ShopifyLineItem>,
     pub customer: Option<ShopifyCustomer>,
     pub shipping_address: Option<ShopifyAddress>,
     pub billing_address: Option<ShopifyAddress>,
 }
 
+/// Filters for `ShopifyClient::stream_orders`; `None` leaves Shopify's default
+#[derive(Debug, Clone, Default)]
+pub struct OrderFilter {
+    /// "open" (Shopify's default), "closed", "cancelled" or "any"
+    pub status: Option<String>,
+    /// e.g. "paid", "pending", "refunded" or "any"
+    pub financial_status: Option<String>,
+    /// Only orders created at or after this
+    pub created_at_min: Option<DateTime<Utc>>,
+    /// Only orders created at or before this
+    pub created_at_max: Option<DateTime<Utc>>,
+}
+
+/// An abandoned checkout from `/checkouts.json`
+///
+/// Shopify doesn't say which order a checkout became; the order carries the
+/// link instead (`ShopifyOrder::checkout_id`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyCheckout {
+    pub id: i64,
+    pub email: Option<String>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub abandoned_checkout_url: Option<String>,
+    pub currency: Option<String>,
+    pub total_price: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyRefund {
+    pub id: i64,
+    pub order_id: ShopifyOrderId,
+    pub created_at: String,
+    pub note: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub transactions: Vec<ShopifyTransaction>,
+}
+
+impl ShopifyRefund {
+    /// Money actually returned: the sum of successful refund transactions
+    pub fn amount(&self) -> rust_decimal::Decimal {
+        self.transactions
+            .iter()
+            .filter(|t| t.kind == "refund" && t.status.as_deref().unwrap_or("success") == "success")
+            .filter_map(|t| t.amount.parse::<rust_decimal::Decimal>().ok())
+            .sum()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyTransaction {
+    pub id: i64,
+    pub kind: String,
+    pub status: Option<String>,
+    pub amount: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyFulfillment {
+    pub id: i64,
+    pub order_id: ShopifyOrderId,
+    pub status: Option<String>,
+    pub tracking_number: Option<String>,
+    pub tracking_company: Option<String>,
+    pub created_at: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ShopifyPriceSet {
     pub shop_money: ShopifyMoney,
@@ -80,14 +286,24 @@ pub struct ShopifyMoney {
     pub currency_code: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyDiscountCode {
+    pub code: String,
+    /// Discount the code gave on this order (not the percentage for percentage codes)
+    pub amount: String,
+    /// "fixed_amount", "percentage" or "shipping"
+    #[serde(rename = "type")]
+    pub discount_type: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ShopifyLineItem {
     pub id: i64,
-    pub product_id: Option<i64>,
-    pub variant_id: Option<i64>,
-    pub title: String,
+    pub product_id: Option<ShopifyProductId>,
+    pub variant_id: Option<ShopifyVariantId>,
+    pub title: Option<String>,
     pub quantity: i32,
-    pub price: String,
+    pub price: Option<String>,
     pub sku: Option<String>,
 }
 
@@ -126,5 +342,9 @@ pub enum ShopifyErrorType {
     Authentication,
     #[error("Rate limit exceeded")]
     RateLimit,
+    #[error("Shopify is failing; calls are paused until the circuit breaker cooldown ends")]
+    CircuitOpen,
+    #[error("The app was uninstalled from this store; reinstall it to resume Shopify calls")]
+    Uninstalled,
 }
 
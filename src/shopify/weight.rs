@@ -0,0 +1,84 @@
+//! Variant weight units.
+//!
+//! Shopify's REST API sends `g`, `kg`, `oz` or `lb`, but older payloads and
+//! imports spell them out or change the case (`"KG"`, `"kilograms"`). Units are
+//! parsed into `WeightUnit` before they are stored, so the `variants.weight_unit`
+//! column only ever holds the short codes (migration 029).
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text")]
+pub enum WeightUnit {
+    #[serde(rename = "g")]
+    #[sqlx(rename = "g")]
+    Grams,
+    #[serde(rename = "kg")]
+    #[sqlx(rename = "kg")]
+    Kilograms,
+    #[serde(rename = "oz")]
+    #[sqlx(rename = "oz")]
+    Ounces,
+    #[serde(rename = "lb")]
+    #[sqlx(rename = "lb")]
+    Pounds,
+}
+
+impl WeightUnit {
+    /// Parse a unit however Shopify spelled it; `None` for units we don't know
+    pub fn parse(unit: &str) -> Option<Self> {
+        match unit.trim().to_ascii_lowercase().as_str() {
+            "g" | "gram" | "grams" => Some(WeightUnit::Grams),
+            "kg" | "kgs" | "kilogram" | "kilograms" => Some(WeightUnit::Kilograms),
+            "oz" | "ounce" | "ounces" => Some(WeightUnit::Ounces),
+            "lb" | "lbs" | "pound" | "pounds" => Some(WeightUnit::Pounds),
+            _ => None,
+        }
+    }
+
+    /// `weight` in this unit, converted to grams
+    pub fn to_grams(self, weight: f64) -> f64 {
+        match self {
+            WeightUnit::Grams => weight,
+            WeightUnit::Kilograms => weight * 1000.0,
+            WeightUnit::Ounces => weight * 28.349_523_125,
+            WeightUnit::Pounds => weight * 453.592_37,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shopify_spellings_are_normalized() {
+        for (raw, unit) in [
+            ("kg", WeightUnit::Kilograms),
+            ("KG", WeightUnit::Kilograms),
+            (" kilograms ", WeightUnit::Kilograms),
+            ("g", WeightUnit::Grams),
+            ("Ounces", WeightUnit::Ounces),
+            ("lbs", WeightUnit::Pounds),
+        ] {
+            assert_eq!(WeightUnit::parse(raw), Some(unit), "{:?}", raw);
+        }
+        assert_eq!(WeightUnit::parse("stone"), None);
+        assert_eq!(WeightUnit::parse(""), None);
+
+        assert_eq!(
+            serde_json::to_string(&WeightUnit::Pounds).unwrap(),
+            r#""lb""#
+        );
+    }
+
+    #[test]
+    fn weights_convert_to_grams() {
+        assert_eq!(WeightUnit::Kilograms.to_grams(1.5), 1500.0);
+        assert_eq!(WeightUnit::Grams.to_grams(250.0), 250.0);
+        assert!((WeightUnit::Pounds.to_grams(1.0) - 453.59237).abs() < 1e-9);
+        assert!(
+            (WeightUnit::Ounces.to_grams(16.0) - WeightUnit::Pounds.to_grams(1.0)).abs() < 1e-9
+        );
+    }
+}
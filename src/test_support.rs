@@ -0,0 +1,117 @@
+//! Fixtures shared by tests that need Postgres or drive the HTTP router
+//!
+//! Database tests start with `let Some(db) = test_db().await? else { return Ok(()) };`
+//! so they are skipped, not failed, where `DATABASE_URL` isn't set.
+
+use axum::{body::Body, http::Request, http::StatusCode, Router};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::PgExecutor;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Password of every user created by `insert_user`
+pub const PASSWORD: &str = "password";
+
+/// `DATABASE_URL`, or `None` after noting that the running test is skipped
+pub fn database_url() -> Option<String> {
+    match std::env::var("DATABASE_URL") {
+        Ok(url) if !url.trim().is_empty() => Some(url),
+        _ => {
+            // Tests run on a thread named after their path, e.g. `http::tags::tests::<name>`
+            let thread = std::thread::current();
+            let test = thread.name().and_then(|path| path.rsplit("::").next());
+            println!("Skipping {}: DATABASE_URL not set", test.unwrap_or("test"));
+            None
+        }
+    }
+}
+
+/// A migrated pool on `DATABASE_URL`; `None` if it isn't set
+pub async fn test_db() -> anyhow::Result<Option<PgPool>> {
+    test_db_with(PgPoolOptions::new()).await
+}
+
+/// `test_db` with pool options, e.g. a single connection for tests that roll back a transaction
+pub async fn test_db_with(options: PgPoolOptions) -> anyhow::Result<Option<PgPool>> {
+    let Some(database_url) = database_url() else {
+        return Ok(None);
+    };
+    let db = options.connect(&database_url).await?;
+    sqlx::migrate!("./sql/migrations").run(&db).await?;
+    Ok(Some(db))
+}
+
+/// Insert a merchant with a unique shop domain; returns its id
+pub async fn insert_merchant<'e>(db: impl PgExecutor<'e>) -> sqlx::Result<Uuid> {
+    sqlx::query_scalar("INSERT INTO merchants (shop_domain) VALUES ($1) RETURNING id")
+        .bind(format!("test-{}.myshopify.com", Uuid::new_v4()))
+        .fetch_one(db)
+        .await
+}
+
+/// Insert a user of `merchant_id` with `role` who logs in with `PASSWORD`; returns their id and email
+pub async fn insert_user<'e>(
+    db: impl PgExecutor<'e>,
+    merchant_id: Uuid,
+    role: &str,
+) -> sqlx::Result<(Uuid, String)> {
+    let email = format!("{}-{}@test-shop.com", role, Uuid::new_v4());
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (merchant_id, email, password_hash, role)
+        VALUES ($1, $2, encode(sha256(convert_to($3, 'UTF8')), 'hex'), $4)
+        RETURNING id
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&email)
+    .bind(PASSWORD)
+    .bind(role)
+    .fetch_one(db)
+    .await?;
+    Ok((id, email))
+}
+
+/// Send one request through the router with an optional bearer token; returns the
+/// status and the JSON body (null if empty)
+pub async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    bearer: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+    let authorization = bearer.map(|token| format!("Bearer {}", token));
+    let headers: Vec<_> = authorization
+        .iter()
+        .map(|value| ("Authorization", value.as_str()))
+        .collect();
+    send_with_headers(app, method, uri, &headers, body).await
+}
+
+/// `send` with any request headers, e.g. `&[("X-Api-Key", key)]`
+pub async fn send_with_headers(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = match body {
+        Some(body) => request
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    };
+    let response = app.clone().oneshot(request.unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}